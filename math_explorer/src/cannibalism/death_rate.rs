@@ -1,7 +1,14 @@
 // The Death Rate Equation
 // mu(t, a) = nu(a) + C(a) * k(t) * Phi(c(t))
 
-/// Placeholder function for the death rate equation.
+use crate::error::{self, Result};
+
+use super::mckendrick_von_foerster::{trapezoid, McKendrickSolver, PopulationSolution};
+use super::renewal::FertilityKernel;
+
+/// The death rate equation.
+///
+/// See `DeathRateModel` for a death rate whose cannibal density is computed from the population.
 ///
 /// # Arguments
 ///
@@ -14,6 +21,188 @@
 ///
 /// The per capita death rate.
 pub fn death_rate(nu_a: f64, c_a: f64, k_t: f64, phi_c_t: f64) -> f64 {
-    // This is a placeholder implementation.
     nu_a + c_a * k_t * phi_c_t
 }
+
+/// The population quantities that the death rate depends on at one time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CannibalismState {
+    /// The cannibal density k(t) = ∫ w(a) * n(t, a) da.
+    pub cannibals: f64,
+    /// The victim density c(t) = ∫ C(a) * n(t, a) da, the food available to cannibals.
+    pub victims: f64,
+}
+
+/// Holling type II correction Phi(c) = 1 / (1 + h * c): each cannibal spends time `handling`
+/// on every victim, so the per capita predation saturates as victims become abundant.
+///
+/// # Arguments
+///
+/// * `handling` - the handling time h
+/// * `victims` - the victim density c
+///
+/// # Returns
+///
+/// The density-dependent correction factor.
+pub fn holling_type_ii(handling: f64, victims: f64) -> f64 {
+    1.0 / (1.0 + handling * victims)
+}
+
+/// A density-dependent death rate mu(t, a) = nu(a) + C(a) * k(t) * Phi(c(t)).
+///
+/// The cannibal density k(t) = ∫ w(a) * n(t, a) da weighs the population by `cannibal_weight`:
+/// an indicator of a >= alpha gives the adult density, and the body mass at age a the
+/// cannibal biomass. The victim density c(t) = ∫ C(a) * n(t, a) da weighs it by the attack
+/// rate, so that Phi(c) can describe satiation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DeathRateModel<N, C, W, P> {
+    /// The natural death rate nu(a).
+    pub natural: N,
+    /// The attack rate C(a) of cannibals on individuals of age a.
+    pub attack: C,
+    /// The weight w(a) of an individual of age a in the cannibal density.
+    pub cannibal_weight: W,
+    /// The density-dependent correction factor Phi(c).
+    pub correction: P,
+}
+
+impl<N, C, W, P> DeathRateModel<N, C, W, P>
+where
+    N: Fn(f64) -> f64,
+    C: Fn(f64) -> f64,
+    W: Fn(f64) -> f64,
+    P: Fn(f64) -> f64,
+{
+    /// Creates a new `DeathRateModel`.
+    pub fn new(natural: N, attack: C, cannibal_weight: W, correction: P) -> Self {
+        Self { natural, attack, cannibal_weight, correction }
+    }
+
+    /// The cannibal and victim densities of an age density on a uniform grid.
+    ///
+    /// # Arguments
+    ///
+    /// * `ages` - the ages of the grid, starting at 0
+    /// * `density` - the density at each age
+    ///
+    /// # Returns
+    ///
+    /// The `CannibalismState`, with both integrals by the trapezoidal rule.
+    pub fn state(&self, ages: &[f64], density: &[f64]) -> CannibalismState {
+        let h = if ages.len() > 1 { ages[1] - ages[0] } else { 0.0 };
+        let weighted = |weight: &dyn Fn(f64) -> f64| {
+            let values: Vec<f64> = ages.iter().zip(density).map(|(&a, &n)| weight(a) * n).collect();
+            trapezoid(&values, h)
+        };
+        CannibalismState { cannibals: weighted(&self.cannibal_weight), victims: weighted(&self.attack) }
+    }
+
+    /// The death rate at age `a` in the population state `state`.
+    pub fn rate(&self, a: f64, state: &CannibalismState) -> f64 {
+        death_rate((self.natural)(a), (self.attack)(a), state.cannibals, (self.correction)(state.victims))
+    }
+}
+
+/// The solution of the McKendrick-von Foerster equation with a density-dependent death rate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CannibalismSolution {
+    /// The age density, total population and births.
+    pub population: PopulationSolution,
+    /// The cannibal and victim densities at each time.
+    pub states: Vec<CannibalismState>,
+}
+
+impl McKendrickSolver {
+    /// Solves the McKendrick-von Foerster equation with a density-dependent death rate.
+    ///
+    /// The cannibal and victim densities are computed from the density at the start of each
+    /// step and held fixed over it, so the feedback of mortality on itself is explicit and
+    /// first-order accurate in time.
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - the death rate
+    /// * `birth` - the boundary condition b(t) = n(t, 0)
+    /// * `initial` - the initial age density n_0(a)
+    /// * `end_time` - the time to solve up to
+    ///
+    /// # Returns
+    ///
+    /// The population and the cannibal and victim densities at every grid time.
+    ///
+    /// # Errors
+    ///
+    /// `Error::InvalidParameter` if `end_time` is negative or not finite.
+    pub fn solve_with_death_rate<N, C, W, P>(
+        &self,
+        model: &DeathRateModel<N, C, W, P>,
+        birth: impl Fn(f64) -> f64,
+        initial: impl Fn(f64) -> f64,
+        end_time: f64,
+    ) -> Result<CannibalismSolution>
+    where
+        N: Fn(f64) -> f64,
+        C: Fn(f64) -> f64,
+        W: Fn(f64) -> f64,
+        P: Fn(f64) -> f64,
+    {
+        error::non_negative("end_time", end_time)?;
+        Ok(self.march_with_death_rate(model, |t, _| birth(t), initial, end_time))
+    }
+
+    /// Solves the McKendrick-von Foerster equation with a density-dependent death rate and
+    /// births given by the renewal equation.
+    ///
+    /// See `solve_with_death_rate` and `solve_renewal`.
+    ///
+    /// # Errors
+    ///
+    /// `Error::InvalidParameter` if `end_time` is negative or not finite, or if h * beta(0) / 2 >= 1.
+    pub fn solve_renewal_with_death_rate<N, C, W, P>(
+        &self,
+        model: &DeathRateModel<N, C, W, P>,
+        kernel: &impl FertilityKernel,
+        initial: impl Fn(f64) -> f64,
+        end_time: f64,
+    ) -> Result<CannibalismSolution>
+    where
+        N: Fn(f64) -> f64,
+        C: Fn(f64) -> f64,
+        W: Fn(f64) -> f64,
+        P: Fn(f64) -> f64,
+    {
+        error::non_negative("end_time", end_time)?;
+        let births = self.renewal_boundary(kernel)?;
+        Ok(self.march_with_death_rate(model, births, initial, end_time))
+    }
+
+    fn march_with_death_rate<N, C, W, P>(
+        &self,
+        model: &DeathRateModel<N, C, W, P>,
+        births: impl FnMut(f64, &[f64]) -> f64,
+        initial: impl Fn(f64) -> f64,
+        end_time: f64,
+    ) -> CannibalismSolution
+    where
+        N: Fn(f64) -> f64,
+        C: Fn(f64) -> f64,
+        W: Fn(f64) -> f64,
+        P: Fn(f64) -> f64,
+    {
+        let ages = self.ages();
+        let mut states = Vec::new();
+        let population = self.march(
+            initial,
+            end_time,
+            |density| {
+                let state = model.state(&ages, density);
+                states.push(state);
+                state
+            },
+            |state, _, a| model.rate(a, state),
+            births,
+        );
+        states.push(model.state(&ages, population.final_density()));
+        CannibalismSolution { population, states }
+    }
+}
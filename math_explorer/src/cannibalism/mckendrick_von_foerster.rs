@@ -1,8 +1,14 @@
 // The McKendrick-von Foerster Equation
 // dn/dt + dn/da = -mu(t, a) * n(t, a)
 // n(t, 0) = b(t)
+// n(0, a) = n_0(a)
 
-/// Placeholder function for the McKendrick-von Foerster equation.
+use crate::error::{self, Error, Result};
+
+/// Right-hand side of the McKendrick-von Foerster equation along a characteristic.
+///
+/// Along a characteristic a - t = constant the equation reduces to dn/dt = -mu(t, a) * n,
+/// which is what this function evaluates. Use `McKendrickSolver` to solve the equation.
 ///
 /// # Arguments
 ///
@@ -14,14 +20,15 @@
 /// # Returns
 ///
 /// The rate of change of the number of individuals.
-pub fn mckendrick_von_foerster(t: f64, a: f64, mu: f64, n: f64) -> f64 {
-    // This is a placeholder implementation.
-    // The actual implementation would involve solving a partial differential equation.
+pub fn mckendrick_von_foerster(_t: f64, _a: f64, mu: f64, n: f64) -> f64 {
     -mu * n
 }
 
 /// Placeholder function for the boundary condition of the McKendrick-von Foerster equation.
 ///
+/// A constant birth rate. For births that depend on the population, see `FertilityKernel`
+/// and `McKendrickSolver::solve_renewal`.
+///
 /// # Arguments
 ///
 /// * `t` - time
@@ -29,8 +36,206 @@ pub fn mckendrick_von_foerster(t: f64, a: f64, mu: f64, n: f64) -> f64 {
 /// # Returns
 ///
 /// The birth rate of the population at time t.
-pub fn birth_rate(t: f64) -> f64 {
+pub fn birth_rate(_t: f64) -> f64 {
     // This is a placeholder implementation.
     // The actual implementation would depend on the specific model.
     100.0 // Placeholder value
 }
+
+/// The solution of the McKendrick-von Foerster equation on an (age, time) grid.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PopulationSolution {
+    /// The times t_k = k * h.
+    pub times: Vec<f64>,
+    /// The ages a_j = j * h, from 0 to the maximum age.
+    pub ages: Vec<f64>,
+    /// The density n(t_k, a_j), indexed by time and then by age.
+    pub density: Vec<Vec<f64>>,
+    /// The total population ∫ n(t_k, a) da at each time, by the trapezoidal rule.
+    pub total: Vec<f64>,
+    /// The births n(t_k, 0) at each time.
+    pub births: Vec<f64>,
+}
+
+impl PopulationSolution {
+    /// The age density at the last time.
+    pub fn final_density(&self) -> &[f64] {
+        self.density.last().map_or(&[], Vec::as_slice)
+    }
+
+    /// The total population at the last time.
+    pub fn final_total(&self) -> f64 {
+        self.total.last().copied().unwrap_or(0.0)
+    }
+}
+
+/// Solver of the McKendrick-von Foerster equation by the method of characteristics.
+///
+/// The time step equals the age step h = max_age / age_intervals, so every grid point
+/// (t_{k+1}, a_{j+1}) lies on the characteristic through (t_k, a_j) and no interpolation
+/// is needed:
+///
+/// n(t_{k+1}, a_{j+1}) = n(t_k, a_j) * exp(-h/2 * [mu(t_k, a_j) + mu(t_{k+1}, a_{j+1})])
+///
+/// The trapezoidal rule in the exponent makes the scheme exact when mu is constant or
+/// linear in t and a, and second-order accurate otherwise. Individuals older than
+/// `max_age` leave the domain.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct McKendrickSolver {
+    /// The largest age on the grid.
+    pub max_age: f64,
+    /// The number of age intervals.
+    pub age_intervals: usize,
+}
+
+impl McKendrickSolver {
+    /// Creates a new `McKendrickSolver`.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_age` - the largest age on the grid
+    /// * `age_intervals` - the number of age intervals; the time step equals the age step
+    ///
+    /// # Errors
+    ///
+    /// `Error::InvalidParameter` if `max_age` is not positive and finite or `age_intervals` is zero.
+    pub fn new(max_age: f64, age_intervals: usize) -> Result<Self> {
+        if !(max_age.is_finite() && max_age > 0.0) {
+            return Err(Error::InvalidParameter { name: "max_age", value: max_age });
+        }
+        if age_intervals == 0 {
+            return Err(Error::InvalidParameter { name: "age_intervals", value: 0.0 });
+        }
+        Ok(Self { max_age, age_intervals })
+    }
+
+    /// The age and time step h.
+    pub fn step(&self) -> f64 {
+        self.max_age / self.age_intervals as f64
+    }
+
+    /// The ages of the grid.
+    pub fn ages(&self) -> Vec<f64> {
+        let h = self.step();
+        (0..=self.age_intervals).map(|j| j as f64 * h).collect()
+    }
+
+    /// Solves the McKendrick-von Foerster equation with a prescribed birth rate.
+    ///
+    /// # Arguments
+    ///
+    /// * `mu` - the per capita death rate mu(t, a)
+    /// * `birth` - the boundary condition b(t) = n(t, 0)
+    /// * `initial` - the initial age density n_0(a) = n(0, a)
+    /// * `end_time` - the time to solve up to; the last time is the first grid time at or after it
+    ///
+    /// # Returns
+    ///
+    /// The density n(t, a), the total population and the births at every grid time.
+    ///
+    /// # Errors
+    ///
+    /// `Error::InvalidParameter` if `end_time` is negative or not finite.
+    pub fn solve(
+        &self,
+        mu: impl Fn(f64, f64) -> f64,
+        birth: impl Fn(f64) -> f64,
+        initial: impl Fn(f64) -> f64,
+        end_time: f64,
+    ) -> Result<PopulationSolution> {
+        error::non_negative("end_time", end_time)?;
+        Ok(self.march(initial, end_time, |_| (), |_, t, a| mu(t, a), |t, _| birth(t)))
+    }
+
+    /// Marches the density along the characteristics.
+    ///
+    /// At the start of each step `state(n)` summarises the density `n`, and the mortality
+    /// over the step is `mortality(&state, t, a)`, so that mu may depend on the population
+    /// explicitly. `births(t, n)` receives the density at t with every age but zero filled in.
+    pub(crate) fn march<S>(
+        &self,
+        initial: impl Fn(f64) -> f64,
+        end_time: f64,
+        mut state: impl FnMut(&[f64]) -> S,
+        mortality: impl Fn(&S, f64, f64) -> f64,
+        mut births: impl FnMut(f64, &[f64]) -> f64,
+    ) -> PopulationSolution {
+        let h = self.step();
+        let ages = self.ages();
+        let steps = (end_time / h - 1e-9).ceil().max(0.0) as usize;
+
+        let mut current: Vec<f64> = ages.iter().map(|&a| initial(a)).collect();
+        let mut times = Vec::with_capacity(steps + 1);
+        let mut density = Vec::with_capacity(steps + 1);
+        let mut total = Vec::with_capacity(steps + 1);
+        let mut birth_series = Vec::with_capacity(steps + 1);
+        times.push(0.0);
+        total.push(trapezoid(&current, h));
+        birth_series.push(current[0]);
+        density.push(current.clone());
+
+        for k in 0..steps {
+            let t = k as f64 * h;
+            let next_t = (k + 1) as f64 * h;
+            let summary = state(&current);
+            let mut next = vec![0.0; ages.len()];
+            for j in 0..self.age_intervals {
+                let exponent = 0.5 * h * (mortality(&summary, t, ages[j]) + mortality(&summary, next_t, ages[j + 1]));
+                next[j + 1] = current[j] * (-exponent).exp();
+            }
+            next[0] = births(next_t, &next);
+
+            times.push(next_t);
+            total.push(trapezoid(&next, h));
+            birth_series.push(next[0]);
+            density.push(next.clone());
+            current = next;
+        }
+
+        PopulationSolution { times, ages, density, total, births: birth_series }
+    }
+}
+
+/// The trapezoidal rule on a uniform grid with step `h`.
+pub(crate) fn trapezoid(values: &[f64], h: f64) -> f64 {
+    match values {
+        [] | [_] => 0.0,
+        [first, interior @ .., last] => h * (0.5 * (first + last) + interior.iter().sum::<f64>()),
+    }
+}
+
+/// Solves the McKendrick-von Foerster equation with a prescribed birth rate.
+///
+/// A shorthand for `McKendrickSolver::new(max_age, age_intervals)?.solve(mu, birth, initial, end_time)`.
+///
+/// # Arguments
+///
+/// * `mu` - the per capita death rate mu(t, a)
+/// * `birth` - the boundary condition b(t) = n(t, 0)
+/// * `initial` - the initial age density n_0(a)
+/// * `max_age` - the largest age on the grid
+/// * `age_intervals` - the number of age intervals
+/// * `end_time` - the time to solve up to
+///
+/// # Returns
+///
+/// The density n(t, a), the total population and the births at every grid time.
+///
+/// # Example
+///
+/// ```
+/// use math_explorer::cannibalism::solve_mckendrick_von_foerster;
+/// // With constant mortality and births the density n(t, a) = exp(-0.5 a) is stationary.
+/// let solution = solve_mckendrick_von_foerster(|_, _| 0.5, |_| 1.0, |a| (-0.5 * a).exp(), 10.0, 200, 5.0).unwrap();
+/// assert!(solution.final_density().iter().zip(&solution.ages).all(|(n, a)| (n - (-0.5 * a).exp()).abs() < 1e-12));
+/// ```
+pub fn solve_mckendrick_von_foerster(
+    mu: impl Fn(f64, f64) -> f64,
+    birth: impl Fn(f64) -> f64,
+    initial: impl Fn(f64) -> f64,
+    max_age: f64,
+    age_intervals: usize,
+    end_time: f64,
+) -> Result<PopulationSolution> {
+    McKendrickSolver::new(max_age, age_intervals)?.solve(mu, birth, initial, end_time)
+}
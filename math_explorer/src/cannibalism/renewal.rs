@@ -0,0 +1,180 @@
+// The Renewal Equation
+// b(t) = n(t, 0) = ∫ beta(a) * n(t, a) da
+// R_0 = ∫ beta(a) * l(a) da, with l(a) = exp(-∫_0^a mu(s) ds)
+// Euler-Lotka: ∫ exp(-r * a) * beta(a) * l(a) da = 1
+
+use crate::error::{self, Error, Result};
+use crate::integration::Quadrature;
+
+use super::mckendrick_von_foerster::{trapezoid, McKendrickSolver, PopulationSolution};
+
+/// A fertility schedule beta(a): the per capita birth rate of individuals of age a.
+///
+/// Implemented for every closure `Fn(f64) -> f64`.
+pub trait FertilityKernel {
+    /// The per capita birth rate at age `a`.
+    fn fertility(&self, a: f64) -> f64;
+}
+
+impl<F: Fn(f64) -> f64> FertilityKernel for F {
+    fn fertility(&self, a: f64) -> f64 {
+        self(a)
+    }
+}
+
+/// Births of an age density on a uniform grid, b = ∫ beta(a) * n(a) da by the trapezoidal rule.
+///
+/// # Arguments
+///
+/// * `kernel` - the fertility schedule
+/// * `ages` - the ages of the grid, starting at 0 with step `h`
+/// * `density` - the density at each age
+///
+/// # Returns
+///
+/// The birth rate.
+pub fn renewal_births(kernel: &impl FertilityKernel, ages: &[f64], density: &[f64]) -> f64 {
+    let h = if ages.len() > 1 { ages[1] - ages[0] } else { 0.0 };
+    let weighted: Vec<f64> = ages.iter().zip(density).map(|(&a, &n)| kernel.fertility(a) * n).collect();
+    trapezoid(&weighted, h)
+}
+
+impl McKendrickSolver {
+    /// Solves the McKendrick-von Foerster equation with births given by the renewal equation.
+    ///
+    /// The boundary condition n(t, 0) = ∫ beta(a) * n(t, a) da is evaluated with the
+    /// trapezoidal rule on the age grid. The newborns at age 0 enter their own integral
+    /// with weight h/2, which is solved for exactly:
+    ///
+    /// n(t, 0) = h * [sum_{0<j<J} beta(a_j) n(t, a_j) + beta(a_J) n(t, a_J) / 2] / (1 - h beta(0) / 2)
+    ///
+    /// # Arguments
+    ///
+    /// * `mu` - the per capita death rate mu(t, a)
+    /// * `kernel` - the fertility schedule beta(a)
+    /// * `initial` - the initial age density n_0(a)
+    /// * `end_time` - the time to solve up to
+    ///
+    /// # Returns
+    ///
+    /// The density n(t, a), the total population and the births at every grid time.
+    ///
+    /// # Errors
+    ///
+    /// `Error::InvalidParameter` if `end_time` is negative or not finite, or if h * beta(0) / 2 >= 1.
+    pub fn solve_renewal(
+        &self,
+        mu: impl Fn(f64, f64) -> f64,
+        kernel: &impl FertilityKernel,
+        initial: impl Fn(f64) -> f64,
+        end_time: f64,
+    ) -> Result<PopulationSolution> {
+        error::non_negative("end_time", end_time)?;
+        let births = self.renewal_boundary(kernel)?;
+        Ok(self.march(initial, end_time, |_| (), |_, t, a| mu(t, a), births))
+    }
+
+    /// The renewal boundary condition on this grid, for `march`.
+    pub(crate) fn renewal_boundary(&self, kernel: &impl FertilityKernel) -> Result<impl FnMut(f64, &[f64]) -> f64> {
+        let h = self.step();
+        let fertility: Vec<f64> = self.ages().iter().map(|&a| kernel.fertility(a)).collect();
+        let denominator = 1.0 - 0.5 * h * fertility[0];
+        if denominator <= 0.0 {
+            return Err(Error::InvalidParameter { name: "fertility", value: fertility[0] });
+        }
+        // The density passed to the boundary is zero at age 0, so the trapezoidal rule leaves out the newborns.
+        Ok(move |_t: f64, density: &[f64]| {
+            let weighted: Vec<f64> = fertility.iter().zip(density).map(|(beta, n)| beta * n).collect();
+            trapezoid(&weighted, h) / denominator
+        })
+    }
+}
+
+/// A stable population model: an age-dependent fertility and mortality that do not change in time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenewalModel<B, M> {
+    /// The fertility schedule beta(a).
+    pub fertility: B,
+    /// The per capita death rate mu(a).
+    pub mortality: M,
+    /// The largest age; beta is taken to vanish beyond it.
+    pub max_age: f64,
+    /// The quadrature used for the integrals over age.
+    pub quadrature: Quadrature,
+}
+
+impl<B: FertilityKernel, M: Fn(f64) -> f64> RenewalModel<B, M> {
+    /// Creates a new `RenewalModel` with the default quadrature.
+    pub fn new(fertility: B, mortality: M, max_age: f64) -> Self {
+        Self { fertility, mortality, max_age, quadrature: Quadrature::default() }
+    }
+
+    /// Sets the quadrature used for the integrals over age.
+    pub fn with_quadrature(mut self, quadrature: Quadrature) -> Self {
+        self.quadrature = quadrature;
+        self
+    }
+
+    /// The probability of surviving to age `a`, l(a) = exp(-∫_0^a mu(s) ds).
+    pub fn survival(&self, a: f64) -> f64 {
+        (-self.quadrature.integrate(&self.mortality, 0.0, a).integral).exp()
+    }
+
+    /// The Euler-Lotka function ∫ exp(-r * a) * beta(a) * l(a) da, which decreases in r.
+    pub fn euler_lotka(&self, r: f64) -> f64 {
+        let integrand = |a: f64| (-r * a).exp() * self.fertility.fertility(a) * self.survival(a);
+        self.quadrature.integrate(integrand, 0.0, self.max_age).integral
+    }
+
+    /// The net reproductive number R_0 = ∫ beta(a) * l(a) da, the expected number of
+    /// offspring of a newborn over its lifetime.
+    pub fn net_reproductive_number(&self) -> f64 {
+        self.euler_lotka(0.0)
+    }
+
+    /// The intrinsic growth rate r, the root of the Euler-Lotka equation
+    ///
+    /// ∫ exp(-r * a) * beta(a) * l(a) da = 1.
+    ///
+    /// r has the sign of R_0 - 1, and the population approaches the stable age distribution
+    /// exp(-r * a) * l(a), growing as exp(r * t). The root is bracketed by doubling and then
+    /// found by bisection.
+    ///
+    /// # Errors
+    ///
+    /// `Error::InvalidParameter` with the name `net_reproductive_number` if R_0 is not positive
+    /// and finite, in which case no root exists.
+    pub fn intrinsic_growth_rate(&self) -> Result<f64> {
+        let r0 = self.net_reproductive_number();
+        if !(r0.is_finite() && r0 > 0.0) {
+            return Err(Error::InvalidParameter { name: "net_reproductive_number", value: r0 });
+        }
+        let (mut lo, mut hi) = if r0 > 1.0 { (0.0, 1.0) } else { (-1.0, 0.0) };
+        while self.euler_lotka(hi) > 1.0 {
+            lo = hi;
+            hi *= 2.0;
+            if !hi.is_finite() {
+                return Err(Error::InvalidParameter { name: "net_reproductive_number", value: r0 });
+            }
+        }
+        while self.euler_lotka(lo) < 1.0 {
+            hi = lo;
+            lo *= 2.0;
+            if !lo.is_finite() {
+                return Err(Error::InvalidParameter { name: "net_reproductive_number", value: r0 });
+            }
+        }
+        for _ in 0..100 {
+            let mid = 0.5 * (lo + hi);
+            if self.euler_lotka(mid) > 1.0 {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+            if hi - lo <= 1e-14 * mid.abs().max(1.0) {
+                break;
+            }
+        }
+        Ok(0.5 * (lo + hi))
+    }
+}
@@ -3,7 +3,12 @@
 // dN/dt = beta_N(N, C) * N + beta_C(N, C) * C - K(N) * N - phi(N, C) - mu_N(N, C) * N
 // dC/dt = K(N) * N - mu_C(N, C) * C
 
-/// Placeholder function for the rate of change of normal individuals.
+use nalgebra::DVector;
+
+use crate::error::Result;
+use crate::ode::{OdeSolution, OdeSolver, OdeSystem};
+
+/// The rate of change of normal individuals.
 ///
 /// # Arguments
 ///
@@ -19,11 +24,10 @@
 ///
 /// The rate of change of the number of normal individuals.
 pub fn dndt(n: f64, c: f64, beta_n: f64, beta_c: f64, k_n: f64, phi_n_c: f64, mu_n: f64) -> f64 {
-    // This is a placeholder implementation.
     beta_n * n + beta_c * c - k_n * n - phi_n_c - mu_n * n
 }
 
-/// Placeholder function for the rate of change of cannibalistic individuals.
+/// The rate of change of cannibalistic individuals.
 ///
 /// # Arguments
 ///
@@ -36,6 +40,75 @@ pub fn dndt(n: f64, c: f64, beta_n: f64, beta_c: f64, k_n: f64, phi_n_c: f64, mu
 ///
 /// The rate of change of the number of cannibalistic individuals.
 pub fn dcdt(n: f64, c: f64, k_n: f64, mu_c: f64) -> f64 {
-    // This is a placeholder implementation.
     k_n * n - mu_c * c
 }
+
+/// The two-dimensional cannibalism model as an `OdeSystem` with state (N, C).
+///
+/// Every rate is a function of the current numbers of normal and cannibalistic individuals,
+/// and `dndt` and `dcdt` are evaluated with their values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CannibalismOde<BN, BC, K, P, MN, MC> {
+    /// The birth rate beta_N(N, C) of normal individuals.
+    pub beta_n: BN,
+    /// The birth rate beta_C(N, C) of cannibalistic individuals.
+    pub beta_c: BC,
+    /// The rate K(N, C) at which normal individuals become cannibals.
+    pub k: K,
+    /// The loss phi(N, C) of normal individuals due to cannibalism.
+    pub phi: P,
+    /// The death rate mu_N(N, C) of normal individuals.
+    pub mu_n: MN,
+    /// The death rate mu_C(N, C) of cannibalistic individuals.
+    pub mu_c: MC,
+}
+
+impl<BN, BC, K, P, MN, MC> CannibalismOde<BN, BC, K, P, MN, MC>
+where
+    BN: Fn(f64, f64) -> f64,
+    BC: Fn(f64, f64) -> f64,
+    K: Fn(f64, f64) -> f64,
+    P: Fn(f64, f64) -> f64,
+    MN: Fn(f64, f64) -> f64,
+    MC: Fn(f64, f64) -> f64,
+{
+    /// Creates a new `CannibalismOde`.
+    pub fn new(beta_n: BN, beta_c: BC, k: K, phi: P, mu_n: MN, mu_c: MC) -> Self {
+        Self { beta_n, beta_c, k, phi, mu_n, mu_c }
+    }
+
+    /// Solves the model from N(0) = `n0` and C(0) = `c0` up to `t_end`.
+    ///
+    /// # Arguments
+    ///
+    /// * `solver` - the ODE solver
+    /// * `n0` - initial number of normal individuals
+    /// * `c0` - initial number of cannibalistic individuals
+    /// * `t_end` - the final time
+    ///
+    /// # Returns
+    ///
+    /// The solution, with states (N, C).
+    pub fn solve(&self, solver: &OdeSolver, n0: f64, c0: f64, t_end: f64) -> Result<OdeSolution> {
+        solver.solve(self, 0.0, DVector::from_vec(vec![n0, c0]), t_end)
+    }
+}
+
+impl<BN, BC, K, P, MN, MC> OdeSystem for CannibalismOde<BN, BC, K, P, MN, MC>
+where
+    BN: Fn(f64, f64) -> f64,
+    BC: Fn(f64, f64) -> f64,
+    K: Fn(f64, f64) -> f64,
+    P: Fn(f64, f64) -> f64,
+    MN: Fn(f64, f64) -> f64,
+    MC: Fn(f64, f64) -> f64,
+{
+    fn derivative(&self, _t: f64, y: &DVector<f64>) -> DVector<f64> {
+        let (n, c) = (y[0], y[1]);
+        let k = (self.k)(n, c);
+        DVector::from_vec(vec![
+            dndt(n, c, (self.beta_n)(n, c), (self.beta_c)(n, c), k, (self.phi)(n, c), (self.mu_n)(n, c)),
+            dcdt(n, c, k, (self.mu_c)(n, c)),
+        ])
+    }
+}
@@ -0,0 +1,95 @@
+//! # Errors
+//!
+//! The error type shared by the crate. Functions that used to signal invalid input with a
+//! sentinel value, such as 0.0, `f64::INFINITY` or `f64::NAN`, return a `Result` with one of
+//! these errors instead; the sentinel behaviour remains available through the `_lenient`
+//! variants.
+//!
+//! In `win_ratio` this covers the estimators and tests on patient records, including those
+//! taking a user-supplied pairwise kernel, the simulators, which validate the model and the
+//! censoring design, and the time-dependent curves, which fail if any point is undefined.
+
+use std::fmt;
+
+use crate::win_ratio::bmi::BmiError;
+
+/// Errors returned by the crate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error {
+    /// A parameter is outside its domain, e.g. a negative time or a probability above one.
+    InvalidParameter {
+        /// Name of the parameter.
+        name: &'static str,
+        /// The rejected value.
+        value: f64,
+    },
+    /// There are no observations or no decided pairs to estimate from.
+    EmptyData,
+    /// There are no wins, so a quantity on the log scale is unbounded.
+    NoWins,
+    /// There are no losses (or the loss probability is zero), so the win ratio is unbounded.
+    NoLosses,
+    /// A test statistic is undefined because its estimated variance is zero.
+    ZeroVariance,
+    /// A body measurement is invalid.
+    Bmi(BmiError),
+    /// An iterative estimator did not converge within its iteration limit.
+    NoConvergence {
+        /// The number of iterations performed.
+        iterations: usize,
+    },
+    /// An ODE solver could not advance past `time`: the step size underflowed, the step
+    /// limit was reached or a linear system was singular.
+    StepFailure {
+        /// The time reached.
+        time: f64,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidParameter { name, value } => write!(f, "invalid value of `{name}`: {value}"),
+            Error::EmptyData => write!(f, "no observations to estimate from"),
+            Error::NoWins => write!(f, "there are no wins"),
+            Error::NoLosses => write!(f, "there are no losses, so the win ratio is unbounded"),
+            Error::ZeroVariance => write!(f, "the estimated variance is zero"),
+            Error::Bmi(error) => write!(f, "{error}"),
+            Error::NoConvergence { iterations } => write!(f, "no convergence after {iterations} iterations"),
+            Error::StepFailure { time } => write!(f, "the ODE solver failed to advance past t = {time}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Bmi(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<BmiError> for Error {
+    fn from(error: BmiError) -> Self {
+        Error::Bmi(error)
+    }
+}
+
+/// A `Result` with the crate's `Error`.
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Checks that a parameter is finite and non-negative.
+pub(crate) fn non_negative(name: &'static str, value: f64) -> Result<f64> {
+    if value.is_finite() && value >= 0.0 { Ok(value) } else { Err(Error::InvalidParameter { name, value }) }
+}
+
+/// Checks that a parameter is finite and positive.
+pub(crate) fn positive(name: &'static str, value: f64) -> Result<f64> {
+    if value.is_finite() && value > 0.0 { Ok(value) } else { Err(Error::InvalidParameter { name, value }) }
+}
+
+/// Checks that a parameter is a probability, in [0, 1].
+pub(crate) fn probability(name: &'static str, value: f64) -> Result<f64> {
+    if (0.0..=1.0).contains(&value) { Ok(value) } else { Err(Error::InvalidParameter { name, value }) }
+}
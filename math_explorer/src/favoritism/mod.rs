@@ -3,9 +3,10 @@
 //! This module contains the implementation of the satirical favoritism formula.
 
 use nalgebra::{DMatrix, DVector};
-use quadrature::clenshaw_curtis;
 use rand::Rng;
 
+use crate::integration::{Method, Quadrature};
+
 #[derive(Debug)]
 pub struct FavoritismInputs {
     pub t: f64,
@@ -64,18 +65,13 @@ impl Default for FavoritismInputs {
 pub fn calculate_favoritism_score(inputs: &FavoritismInputs) -> f64 {
     let mut rng = rand::thread_rng();
     let r = rng.gen_range(0.9..1.1);
+    let quadrature = Quadrature::new(Method::ClenshawCurtis, 1e-9);
 
-    let proximity_integral = clenshaw_curtis::integrate(|_t| 1.0 / inputs.x_0, 0.0, inputs.t, 1e-9).integral;
+    let proximity_integral = quadrature.integrate(|_t| 1.0 / inputs.x_0, 0.0, inputs.t).integral;
 
-    let emotional_support_integral = clenshaw_curtis::integrate(
-        |_t| {
-            clenshaw_curtis::integrate(|_x| 8.0, 0.0, 1.0, 1e-9).integral
-        },
-        0.0,
-        inputs.t,
-        1e-9,
-    )
-    .integral;
+    let emotional_support_integral = quadrature
+        .integrate(|_t| quadrature.integrate(|_x| 8.0, 0.0, 1.0).integral, 0.0, inputs.t)
+        .integral;
 
     let gift_matrix = DMatrix::from_diagonal(&DVector::from_vec(vec![inputs.g_emotional, inputs.g_practical]));
     let gift_matrix_determinant = gift_matrix.determinant();
@@ -93,19 +89,9 @@ pub fn calculate_favoritism_score(inputs: &FavoritismInputs) -> f64 {
     let s = if inputs.active_on_social_media { 1.3 } else { 1.0 };
     let d = (-inputs.decay_constant * inputs.time_since_last_contact).exp();
 
-    let sibling_proximity_integral = clenshaw_curtis::integrate(
-        |_t| {
-            inputs
-                .sibling_distances
-                .iter()
-                .map(|distance| 1.0 / distance)
-                .sum()
-        },
-        0.0,
-        inputs.t,
-        1e-9,
-    )
-    .integral;
+    let sibling_proximity_integral = quadrature
+        .integrate(|_t| inputs.sibling_distances.iter().map(|distance| 1.0 / distance).sum(), 0.0, inputs.t)
+        .integral;
 
     let numerator = proximity_integral
         * emotional_support_integral
@@ -3,7 +3,10 @@ pub mod favoritism;
 pub mod number_theory;
 pub mod quantum;
 pub mod freesurfer;
+pub mod integration;
+pub mod ode;
 pub mod cannibalism;
+pub mod error;
 pub mod win_ratio;
 
 #[cfg(test)]
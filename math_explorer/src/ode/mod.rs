@@ -0,0 +1,438 @@
+//! # Ordinary Differential Equations
+//!
+//! Solvers for initial value problems y' = f(t, y). An `OdeSystem` provides the right-hand
+//! side, and optionally its Jacobian, and an `OdeSolver` selects the method: the classical
+//! fixed-step Runge–Kutta method, the adaptive Dormand–Prince 5(4) pair for non-stiff
+//! problems, or an adaptive linearly implicit Rosenbrock method for stiff ones. Every
+//! solution carries a continuous interpolant, so it can be evaluated between the steps.
+
+use nalgebra::{DMatrix, DVector};
+
+use crate::error::{Error, Result};
+
+/// A system of ordinary differential equations y' = f(t, y).
+///
+/// Implemented for every closure `Fn(f64, &DVector<f64>) -> DVector<f64>`.
+pub trait OdeSystem {
+    /// The right-hand side f(t, y).
+    fn derivative(&self, t: f64, y: &DVector<f64>) -> DVector<f64>;
+
+    /// The Jacobian ∂f/∂y, by forward differences unless overridden.
+    fn jacobian(&self, t: f64, y: &DVector<f64>) -> DMatrix<f64> {
+        let f0 = self.derivative(t, y);
+        let mut jacobian = DMatrix::zeros(y.len(), y.len());
+        let mut shifted = y.clone();
+        for j in 0..y.len() {
+            let h = f64::EPSILON.sqrt() * y[j].abs().max(1.0);
+            shifted[j] = y[j] + h;
+            jacobian.set_column(j, &((self.derivative(t, &shifted) - &f0) / h));
+            shifted[j] = y[j];
+        }
+        jacobian
+    }
+
+    /// The partial derivative ∂f/∂t, by forward differences unless overridden.
+    fn time_derivative(&self, t: f64, y: &DVector<f64>) -> DVector<f64> {
+        let h = f64::EPSILON.sqrt() * t.abs().max(1.0);
+        (self.derivative(t + h, y) - self.derivative(t, y)) / h
+    }
+}
+
+impl<F: Fn(f64, &DVector<f64>) -> DVector<f64>> OdeSystem for F {
+    fn derivative(&self, t: f64, y: &DVector<f64>) -> DVector<f64> {
+        self(t, y)
+    }
+}
+
+/// An ODE integration method.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Method {
+    /// The classical fourth-order Runge–Kutta method with a fixed step.
+    RungeKutta4 {
+        /// The step size.
+        step: f64,
+    },
+    /// The Dormand–Prince 5(4) embedded pair with step size control and a fourth-order
+    /// continuous extension. Accurate and cheap for non-stiff problems.
+    DormandPrince,
+    /// The two-stage L-stable Rosenbrock method ROS2 with an embedded first-order error
+    /// estimate. Each step solves two linear systems with the matrix I - γ h J, so it stays
+    /// stable for stiff problems whose fast modes would force an explicit method to tiny steps.
+    /// The method is of second order, so it suits moderate tolerances.
+    Rosenbrock,
+}
+
+/// An ODE solver: a method with its error tolerances and step limits.
+///
+/// The adaptive methods keep the local error of each component below
+/// `absolute_tolerance + relative_tolerance * |y|` in the root-mean-square norm.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OdeSolver {
+    /// The method.
+    pub method: Method,
+    /// The relative tolerance of the adaptive methods.
+    pub relative_tolerance: f64,
+    /// The absolute tolerance of the adaptive methods.
+    pub absolute_tolerance: f64,
+    /// The first step of the adaptive methods; estimated from the initial derivative if `None`.
+    pub initial_step: Option<f64>,
+    /// The largest step of the adaptive methods.
+    pub max_step: f64,
+    /// The largest number of steps, accepted or rejected.
+    pub max_steps: usize,
+}
+
+impl OdeSolver {
+    /// Creates a new `OdeSolver` with no limit on the step size and at most 100,000 steps.
+    pub fn new(method: Method, relative_tolerance: f64, absolute_tolerance: f64) -> Self {
+        Self {
+            method,
+            relative_tolerance,
+            absolute_tolerance,
+            initial_step: None,
+            max_step: f64::INFINITY,
+            max_steps: 100_000,
+        }
+    }
+
+    /// Sets the first step of the adaptive methods.
+    pub fn with_initial_step(mut self, initial_step: f64) -> Self {
+        self.initial_step = Some(initial_step);
+        self
+    }
+
+    /// Sets the largest step of the adaptive methods.
+    pub fn with_max_step(mut self, max_step: f64) -> Self {
+        self.max_step = max_step;
+        self
+    }
+
+    /// Sets the largest number of steps.
+    pub fn with_max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// Solves y' = f(t, y), y(t0) = y0 on [t0, t_end].
+    ///
+    /// ## Parameters
+    ///
+    /// * `system`: The right-hand side.
+    /// * `t0`: The initial time.
+    /// * `y0`: The initial state.
+    /// * `t_end`: The final time, at least `t0`.
+    ///
+    /// ## Returns
+    ///
+    /// An `OdeSolution` with the state after every accepted step.
+    ///
+    /// ## Errors
+    ///
+    /// `Error::InvalidParameter` if a time, step or tolerance is invalid, and
+    /// `Error::StepFailure` if the solver cannot reach `t_end`.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use math_explorer::ode::{Method, OdeSolver};
+    /// use nalgebra::DVector;
+    /// let decay = |_t: f64, y: &DVector<f64>| -y;
+    /// let solution = OdeSolver::new(Method::DormandPrince, 1e-10, 1e-12)
+    ///     .solve(&decay, 0.0, DVector::from_vec(vec![1.0]), 2.0)
+    ///     .unwrap();
+    /// assert!((solution.final_state()[0] - (-2.0f64).exp()).abs() < 1e-9);
+    /// assert!((solution.at(1.0).unwrap()[0] - (-1.0f64).exp()).abs() < 1e-8);
+    /// ```
+    pub fn solve<S: OdeSystem>(&self, system: &S, t0: f64, y0: DVector<f64>, t_end: f64) -> Result<OdeSolution> {
+        if !t0.is_finite() {
+            return Err(Error::InvalidParameter { name: "t0", value: t0 });
+        }
+        if !(t_end.is_finite() && t_end >= t0) {
+            return Err(Error::InvalidParameter { name: "t_end", value: t_end });
+        }
+        match self.method {
+            Method::RungeKutta4 { step } => {
+                if !(step.is_finite() && step > 0.0) {
+                    return Err(Error::InvalidParameter { name: "step", value: step });
+                }
+                self.runge_kutta_4(system, t0, y0, t_end, step)
+            }
+            Method::DormandPrince | Method::Rosenbrock => {
+                if !(self.relative_tolerance >= 0.0 && self.absolute_tolerance >= 0.0)
+                    || self.relative_tolerance + self.absolute_tolerance <= 0.0
+                {
+                    return Err(Error::InvalidParameter { name: "tolerance", value: self.relative_tolerance });
+                }
+                if self.max_step.is_nan() || self.max_step <= 0.0 {
+                    return Err(Error::InvalidParameter { name: "max_step", value: self.max_step });
+                }
+                self.adaptive(system, t0, y0, t_end)
+            }
+        }
+    }
+
+    fn runge_kutta_4<S: OdeSystem>(
+        &self,
+        system: &S,
+        t0: f64,
+        y0: DVector<f64>,
+        t_end: f64,
+        step: f64,
+    ) -> Result<OdeSolution> {
+        let steps = ((t_end - t0) / step - 1e-9).ceil().max(0.0) as usize;
+        if steps > self.max_steps {
+            return Err(Error::StepFailure { time: t0 });
+        }
+        let mut solution = OdeSolution::start(t0, y0);
+        let mut f0 = system.derivative(t0, &solution.states[0]);
+        solution.evaluations += 1;
+        for i in 0..steps {
+            let t = solution.final_time();
+            // The last step ends exactly at t_end.
+            let h = if i + 1 == steps { t_end - t } else { step };
+            let y = solution.final_state();
+            let k1 = f0;
+            let k2 = system.derivative(t + 0.5 * h, &(y + &k1 * (0.5 * h)));
+            let k3 = system.derivative(t + 0.5 * h, &(y + &k2 * (0.5 * h)));
+            let k4 = system.derivative(t + h, &(y + &k3 * h));
+            let y1 = y + (&k1 + &k2 * 2.0 + &k3 * 2.0 + &k4) * (h / 6.0);
+            let f1 = system.derivative(t + h, &y1);
+            solution.evaluations += 4;
+            solution.push(t + h, y1, Interpolant::Hermite { f0: k1, f1: f1.clone() });
+            f0 = f1;
+        }
+        Ok(solution)
+    }
+
+    fn adaptive<S: OdeSystem>(&self, system: &S, t0: f64, y0: DVector<f64>, t_end: f64) -> Result<OdeSolution> {
+        let mut solution = OdeSolution::start(t0, y0);
+        if t_end == t0 {
+            return Ok(solution);
+        }
+        let mut f0 = system.derivative(t0, &solution.states[0]);
+        solution.evaluations += 1;
+        // The error estimate of Dormand–Prince is O(h^5) and that of ROS2 O(h^2).
+        let order = match self.method {
+            Method::Rosenbrock => 2.0,
+            _ => 5.0,
+        };
+        let mut h = match self.initial_step {
+            Some(h) if h > 0.0 => h,
+            _ => self.initial_step_estimate(&solution.states[0], &f0),
+        }
+        .min(self.max_step);
+        let mut attempts = 0;
+
+        while solution.final_time() < t_end {
+            attempts += 1;
+            let t = solution.final_time();
+            if attempts > self.max_steps || h <= 1e-14 * t.abs().max(1.0) {
+                return Err(Error::StepFailure { time: t });
+            }
+            let last = t + h >= t_end;
+            if last {
+                h = t_end - t;
+            }
+            let y = solution.final_state();
+            let step = match self.method {
+                Method::Rosenbrock => rosenbrock_step(system, t, y, &f0, h)?,
+                _ => dormand_prince_step(system, t, y, &f0, h),
+            };
+
+            let scale = y.zip_map(&step.y1, |a, b| self.absolute_tolerance + self.relative_tolerance * a.abs().max(b.abs()));
+            let error = (step.error.component_div(&scale).norm_squared() / y.len().max(1) as f64).sqrt();
+            solution.evaluations += step.evaluations;
+            let factor = if error == 0.0 { 5.0 } else { (0.9 * error.powf(-1.0 / order)).clamp(0.2, 5.0) };
+            if error <= 1.0 {
+                let t1 = if last { t_end } else { t + h };
+                let f1 = step.f1;
+                solution.push(t1, step.y1, step.interpolant);
+                f0 = f1;
+            } else {
+                solution.rejected_steps += 1;
+            }
+            h = (h * factor).min(self.max_step);
+        }
+        Ok(solution)
+    }
+
+    /// A first step h = 0.01 ‖y‖ / ‖f‖ in the scaled norm, as suggested by Hairer, Nørsett
+    /// and Wanner; the step control corrects it after the first step.
+    fn initial_step_estimate(&self, y0: &DVector<f64>, f0: &DVector<f64>) -> f64 {
+        let n = y0.len().max(1) as f64;
+        let scale = y0.map(|y| self.absolute_tolerance + self.relative_tolerance * y.abs());
+        let d0 = (y0.component_div(&scale).norm_squared() / n).sqrt();
+        let d1 = (f0.component_div(&scale).norm_squared() / n).sqrt();
+        if d0 < 1e-5 || d1 < 1e-5 { 1e-6 } else { 0.01 * d0 / d1 }
+    }
+}
+
+/// One attempted step of an adaptive method.
+struct Step {
+    y1: DVector<f64>,
+    f1: DVector<f64>,
+    error: DVector<f64>,
+    interpolant: Interpolant,
+    evaluations: usize,
+}
+
+/// Nodes of the Dormand–Prince pair.
+const DP_C: [f64; 6] = [0.2, 0.3, 0.8, 8.0 / 9.0, 1.0, 1.0];
+
+/// The Dormand–Prince 5(4) step, using the first-same-as-last property for `f0`.
+fn dormand_prince_step<S: OdeSystem>(system: &S, t: f64, y: &DVector<f64>, f0: &DVector<f64>, h: f64) -> Step {
+    let k1 = f0;
+    let k2 = system.derivative(t + DP_C[0] * h, &(y + k1 * (h * 0.2)));
+    let k3 = system.derivative(t + DP_C[1] * h, &(y + (k1 * (3.0 / 40.0) + &k2 * (9.0 / 40.0)) * h));
+    let k4 = system.derivative(
+        t + DP_C[2] * h,
+        &(y + (k1 * (44.0 / 45.0) - &k2 * (56.0 / 15.0) + &k3 * (32.0 / 9.0)) * h),
+    );
+    let k5 = system.derivative(
+        t + DP_C[3] * h,
+        &(y + (k1 * (19372.0 / 6561.0) - &k2 * (25360.0 / 2187.0) + &k3 * (64448.0 / 6561.0)
+            - &k4 * (212.0 / 729.0))
+            * h),
+    );
+    let k6 = system.derivative(
+        t + DP_C[4] * h,
+        &(y + (k1 * (9017.0 / 3168.0) - &k2 * (355.0 / 33.0)
+            + &k3 * (46732.0 / 5247.0)
+            + &k4 * (49.0 / 176.0)
+            - &k5 * (5103.0 / 18656.0))
+            * h),
+    );
+    let y1 = y + (k1 * (35.0 / 384.0) + &k3 * (500.0 / 1113.0) + &k4 * (125.0 / 192.0) - &k5 * (2187.0 / 6784.0)
+        + &k6 * (11.0 / 84.0))
+        * h;
+    let k7 = system.derivative(t + DP_C[5] * h, &y1);
+
+    // The difference between the fifth- and fourth-order solutions.
+    let error = (k1 * (71.0 / 57600.0) - &k3 * (71.0 / 16695.0) + &k4 * (71.0 / 1920.0) - &k5 * (17253.0 / 339200.0)
+        + &k6 * (22.0 / 525.0)
+        - &k7 * (1.0 / 40.0))
+        * h;
+
+    // The continuous extension of Hairer, Nørsett and Wanner (DOPRI5).
+    let difference = &y1 - y;
+    let b = k1 * h - &difference;
+    let dense = (k1 * (-12715105075.0 / 11282082432.0)
+        + &k3 * (87487479700.0 / 32700410799.0)
+        + &k4 * (-10690763975.0 / 1880347072.0)
+        + &k5 * (701980252875.0 / 199316789632.0)
+        + &k6 * (-1453857185.0 / 822651844.0)
+        + &k7 * (69997945.0 / 29380423.0))
+        * h;
+    let interpolant = Interpolant::DormandPrince {
+        coefficients: [difference.clone(), b.clone(), &difference - &k7 * h - &b, dense],
+    };
+    Step { y1, f1: k7, error, interpolant, evaluations: 6 }
+}
+
+/// The ROS2 step: with W = I - γ h J and γ = 1 + 1/√2,
+///
+/// W k1 = f(t, y) + γ h f_t,  W k2 = f(t + h, y + h k1) - 2 k1 - γ h f_t,
+///
+/// y1 = y + h (3 k1 + k2) / 2, and y + h k1 is the embedded first-order solution.
+fn rosenbrock_step<S: OdeSystem>(system: &S, t: f64, y: &DVector<f64>, f0: &DVector<f64>, h: f64) -> Result<Step> {
+    let gamma = 1.0 + std::f64::consts::FRAC_1_SQRT_2;
+    let n = y.len();
+    let w = DMatrix::identity(n, n) - system.jacobian(t, y) * (gamma * h);
+    let lu = w.lu();
+    let time_term = system.time_derivative(t, y) * (gamma * h);
+    let k1 = lu.solve(&(f0 + &time_term)).ok_or(Error::StepFailure { time: t })?;
+    let stage = system.derivative(t + h, &(y + &k1 * h));
+    let k2 = lu.solve(&(stage - &k1 * 2.0 - &time_term)).ok_or(Error::StepFailure { time: t })?;
+    let y1 = y + (&k1 * 3.0 + &k2) * (0.5 * h);
+    let f1 = system.derivative(t + h, &y1);
+    let error = (&k1 + &k2) * (0.5 * h);
+    // The forward-difference Jacobian takes n + 1 evaluations and the time derivative two,
+    // besides the stage and f(t + h, y1).
+    let evaluations = n + 5;
+    Ok(Step { y1, f1: f1.clone(), error, interpolant: Interpolant::Hermite { f0: f0.clone(), f1 }, evaluations })
+}
+
+/// The continuous extension of one step.
+#[derive(Debug, Clone, PartialEq)]
+enum Interpolant {
+    /// Cubic Hermite interpolation of the end values and derivatives.
+    Hermite { f0: DVector<f64>, f1: DVector<f64> },
+    /// The fourth-order Dormand–Prince extension
+    /// y(t + θh) = y0 + θ (r1 + (1 - θ) (r2 + θ (r3 + (1 - θ) r4))).
+    DormandPrince { coefficients: [DVector<f64>; 4] },
+}
+
+/// The solution of an initial value problem.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OdeSolution {
+    /// The initial time and the end of every accepted step.
+    pub times: Vec<f64>,
+    /// The state at each time.
+    pub states: Vec<DVector<f64>>,
+    /// Number of evaluations of the right-hand side, including finite-difference Jacobians.
+    pub evaluations: usize,
+    /// Number of rejected steps.
+    pub rejected_steps: usize,
+    interpolants: Vec<Interpolant>,
+}
+
+impl OdeSolution {
+    fn start(t0: f64, y0: DVector<f64>) -> Self {
+        Self { times: vec![t0], states: vec![y0], evaluations: 0, rejected_steps: 0, interpolants: Vec::new() }
+    }
+
+    fn push(&mut self, t: f64, y: DVector<f64>, interpolant: Interpolant) {
+        self.times.push(t);
+        self.states.push(y);
+        self.interpolants.push(interpolant);
+    }
+
+    /// The number of accepted steps.
+    pub fn steps(&self) -> usize {
+        self.interpolants.len()
+    }
+
+    /// The last time.
+    pub fn final_time(&self) -> f64 {
+        *self.times.last().expect("a solution has an initial time")
+    }
+
+    /// The state at the last time.
+    pub fn final_state(&self) -> &DVector<f64> {
+        self.states.last().expect("a solution has an initial state")
+    }
+
+    /// The state at time `t` from the continuous extension of the step containing it.
+    ///
+    /// ## Returns
+    ///
+    /// The interpolated state, or `None` if `t` is outside the solved interval.
+    pub fn at(&self, t: f64) -> Option<DVector<f64>> {
+        let first = self.times[0];
+        if !(first..=self.final_time()).contains(&t) {
+            return None;
+        }
+        if self.interpolants.is_empty() {
+            return Some(self.states[0].clone());
+        }
+        let i = self.times.partition_point(|&s| s <= t).clamp(1, self.interpolants.len()) - 1;
+        let (t0, t1) = (self.times[i], self.times[i + 1]);
+        let h = t1 - t0;
+        let theta = (t - t0) / h;
+        let (y0, y1) = (&self.states[i], &self.states[i + 1]);
+        Some(match &self.interpolants[i] {
+            Interpolant::Hermite { f0, f1 } => {
+                let theta2 = theta * theta;
+                let theta3 = theta2 * theta;
+                y0 * (2.0 * theta3 - 3.0 * theta2 + 1.0)
+                    + f0 * (h * (theta3 - 2.0 * theta2 + theta))
+                    + y1 * (-2.0 * theta3 + 3.0 * theta2)
+                    + f1 * (h * (theta3 - theta2))
+            }
+            Interpolant::DormandPrince { coefficients: [r1, r2, r3, r4] } => {
+                let inner = r3 + r4 * (1.0 - theta);
+                y0 + (r1 + (r2 + inner * theta) * (1.0 - theta)) * theta
+            }
+        })
+    }
+}
@@ -1,6 +1,19 @@
 //! # Body Mass Index (BMI)
 //!
-//! A function to calculate the Body Mass Index (BMI).
+//! Functions and types for the Body Mass Index (BMI): validated BMI values, the WHO adult
+//! categories, BMI-for-age z-scores and percentiles for children and adolescents aged 5–19
+//! years, and the percent change in BMI as an outcome tier for win ratio comparisons.
+//!
+//! The BMI-for-age reference is an approximation of the WHO 2007 tables built from their
+//! yearly cut-offs, not the published monthly LMS values. It is adequate for summarising
+//! outcomes, but z-scores near a category boundary can differ from the WHO tools.
+
+use std::fmt;
+
+use crate::error;
+
+use super::distributions::standard_normal_cdf;
+use super::hierarchy::{compare_with_margin, Direction, OutcomeTier, TierOutcome};
 
 /// Calculates the Body Mass Index (BMI).
 ///
@@ -19,19 +32,445 @@
 ///
 /// ## Returns
 ///
-/// The calculated BMI as a `f64`.
+/// The calculated BMI as a `f64`, or `Error::Bmi` if the weight or height is not positive
+/// and finite.
 ///
 /// ## Example
 ///
 /// ```
 /// let weight = 70.0; // kg
 /// let height = 1.75; // m
-/// let bmi = math_explorer::win_ratio::bmi::calculate_bmi(weight, height);
+/// let bmi = math_explorer::win_ratio::bmi::calculate_bmi(weight, height).unwrap();
 /// assert!((bmi - 22.857).abs() < 1e-3);
 /// ```
-pub fn calculate_bmi(weight_kg: f64, height_m: f64) -> f64 {
+pub fn calculate_bmi(weight_kg: f64, height_m: f64) -> error::Result<f64> {
+    Ok(Bmi::new(weight_kg, height_m)?.value())
+}
+
+/// Calculates the BMI without validation, returning 0.0 for a non-positive height.
+///
+/// See `calculate_bmi` for the formula.
+pub fn calculate_bmi_lenient(weight_kg: f64, height_m: f64) -> f64 {
     if height_m <= 0.0 {
         return 0.0;
     }
     weight_kg / (height_m * height_m)
 }
+
+/// Errors returned when a BMI cannot be computed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BmiError {
+    /// The weight is not a positive, finite number of kilograms.
+    InvalidWeight(f64),
+    /// The height is not a positive, finite number of meters.
+    InvalidHeight(f64),
+    /// The BMI value is not a positive, finite number.
+    InvalidBmi(f64),
+    /// The age in months is outside the range of the BMI-for-age reference (61–228 months).
+    AgeOutOfRange(f64),
+}
+
+impl fmt::Display for BmiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BmiError::InvalidWeight(weight) => write!(f, "invalid weight: {weight} kg"),
+            BmiError::InvalidHeight(height) => write!(f, "invalid height: {height} m"),
+            BmiError::InvalidBmi(value) => write!(f, "invalid BMI: {value} kg/m²"),
+            BmiError::AgeOutOfRange(age) => {
+                write!(f, "age of {age} months is outside the BMI-for-age reference (61–228 months)")
+            }
+        }
+    }
+}
+
+impl std::error::Error for BmiError {}
+
+/// The WHO classification of adult BMI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum WhoCategory {
+    /// BMI below 16.0.
+    SevereThinness,
+    /// BMI from 16.0 to below 17.0.
+    ModerateThinness,
+    /// BMI from 17.0 to below 18.5.
+    MildThinness,
+    /// BMI from 18.5 to below 25.0.
+    Normal,
+    /// BMI from 25.0 to below 30.0.
+    PreObese,
+    /// BMI from 30.0 to below 35.0.
+    ObeseClassI,
+    /// BMI from 35.0 to below 40.0.
+    ObeseClassII,
+    /// BMI of 40.0 or more.
+    ObeseClassIII,
+}
+
+impl WhoCategory {
+    /// Classifies a BMI value.
+    pub fn from_value(bmi: f64) -> Self {
+        if bmi < 16.0 {
+            WhoCategory::SevereThinness
+        } else if bmi < 17.0 {
+            WhoCategory::ModerateThinness
+        } else if bmi < 18.5 {
+            WhoCategory::MildThinness
+        } else if bmi < 25.0 {
+            WhoCategory::Normal
+        } else if bmi < 30.0 {
+            WhoCategory::PreObese
+        } else if bmi < 35.0 {
+            WhoCategory::ObeseClassI
+        } else if bmi < 40.0 {
+            WhoCategory::ObeseClassII
+        } else {
+            WhoCategory::ObeseClassIII
+        }
+    }
+
+    /// Whether the category is underweight (BMI below 18.5).
+    pub fn is_underweight(&self) -> bool {
+        *self < WhoCategory::Normal
+    }
+
+    /// Whether the category is overweight, which includes obesity (BMI of 25.0 or more).
+    pub fn is_overweight(&self) -> bool {
+        *self >= WhoCategory::PreObese
+    }
+
+    /// Whether the category is obese (BMI of 30.0 or more).
+    pub fn is_obese(&self) -> bool {
+        *self >= WhoCategory::ObeseClassI
+    }
+}
+
+/// A validated Body Mass Index in kg/m².
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Bmi {
+    value: f64,
+}
+
+impl Bmi {
+    /// Computes the BMI from a weight and a height.
+    ///
+    /// ## Parameters
+    ///
+    /// * `weight_kg`: Weight in kilograms (kg).
+    /// * `height_m`: Height in meters (m).
+    ///
+    /// ## Returns
+    ///
+    /// The BMI, or an error if the weight or height is not positive and finite.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use math_explorer::win_ratio::bmi::{Bmi, BmiError, WhoCategory};
+    /// let bmi = Bmi::new(70.0, 1.75).unwrap();
+    /// assert_eq!(bmi.who_category(), WhoCategory::Normal);
+    /// assert_eq!(Bmi::new(70.0, 0.0), Err(BmiError::InvalidHeight(0.0)));
+    /// ```
+    pub fn new(weight_kg: f64, height_m: f64) -> Result<Self, BmiError> {
+        if !(weight_kg.is_finite() && weight_kg > 0.0) {
+            return Err(BmiError::InvalidWeight(weight_kg));
+        }
+        if !(height_m.is_finite() && height_m > 0.0) {
+            return Err(BmiError::InvalidHeight(height_m));
+        }
+        Ok(Self { value: weight_kg / (height_m * height_m) })
+    }
+
+    /// Wraps an already computed BMI value, which must be positive and finite.
+    pub fn from_value(value: f64) -> Result<Self, BmiError> {
+        if !(value.is_finite() && value > 0.0) {
+            return Err(BmiError::InvalidBmi(value));
+        }
+        Ok(Self { value })
+    }
+
+    /// The BMI in kg/m².
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// The WHO adult category of the BMI.
+    pub fn who_category(&self) -> WhoCategory {
+        WhoCategory::from_value(self.value)
+    }
+
+    /// The BMI-for-age z-score and percentile of a child or adolescent.
+    ///
+    /// See `bmi_for_age_lms` for the approximate reference and `LmsParameters::z_score` for
+    /// the formula.
+    ///
+    /// ## Parameters
+    ///
+    /// * `sex`: Sex of the child.
+    /// * `age_months`: Age in months, from 61 to 228 (5 to 19 years).
+    pub fn for_age(&self, sex: Sex, age_months: f64) -> Result<BmiForAge, BmiError> {
+        let lms = bmi_for_age_lms(sex, age_months)?;
+        let z_score = lms.z_score(self.value);
+        Ok(BmiForAge { bmi: self.value, z_score, percentile: 100.0 * standard_normal_cdf(z_score) })
+    }
+}
+
+/// Sex, which selects the growth reference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Sex {
+    /// Boys.
+    Male,
+    /// Girls.
+    Female,
+}
+
+/// The L (Box–Cox power), M (median) and S (coefficient of variation) parameters of a
+/// growth reference at one age.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LmsParameters {
+    /// The Box–Cox power L.
+    pub l: f64,
+    /// The median M.
+    pub m: f64,
+    /// The coefficient of variation S.
+    pub s: f64,
+}
+
+impl LmsParameters {
+    /// Creates a new `LmsParameters`.
+    pub fn new(l: f64, m: f64, s: f64) -> Self {
+        Self { l, m, s }
+    }
+
+    /// The measurement at a given z-score, the z-th centile curve.
+    ///
+    /// ## Formula
+    ///
+    /// X(z) = M (1 + L S z)^(1/L), or M exp(S z) when L = 0.
+    pub fn value_at(&self, z: f64) -> f64 {
+        if self.l.abs() < 1e-12 {
+            self.m * (self.s * z).exp()
+        } else {
+            self.m * (1.0 + self.l * self.s * z).powf(1.0 / self.l)
+        }
+    }
+
+    /// The z-score of a measurement, with the WHO restriction beyond ±3.
+    ///
+    /// ## Formula
+    ///
+    /// z = ((X / M)^L - 1) / (L S), or ln(X / M) / S when L = 0.
+    ///
+    /// Beyond ±3 the LMS tails are replaced by a linear extrapolation with the distance
+    /// between the 2 and 3 SD curves, as recommended for the WHO 2007 reference:
+    /// z = 3 + (X - X(3)) / (X(3) - X(2)) above and z = -3 + (X - X(-3)) / (X(-2) - X(-3)) below.
+    pub fn z_score(&self, value: f64) -> f64 {
+        let z = if self.l.abs() < 1e-12 {
+            (value / self.m).ln() / self.s
+        } else {
+            ((value / self.m).powf(self.l) - 1.0) / (self.l * self.s)
+        };
+        if z > 3.0 {
+            let sd3 = self.value_at(3.0);
+            3.0 + (value - sd3) / (sd3 - self.value_at(2.0))
+        } else if z < -3.0 {
+            let sd3 = self.value_at(-3.0);
+            -3.0 + (value - sd3) / (self.value_at(-2.0) - sd3)
+        } else {
+            z
+        }
+    }
+}
+
+/// An approximation of the WHO 2007 BMI-for-age reference for boys at whole years, as
+/// (age in months, L, M, S).
+///
+/// Only the first row is the published value, at 61 months. The others are fitted to the
+/// published -2 SD, median and +2 SD cut-offs at each birthday, with a smoothed L; they
+/// reproduce those cut-offs to within 0.05 SD but are not the published monthly values,
+/// and the medians are rounded to 0.1 kg/m².
+const APPROXIMATE_BOYS_BMI_FOR_AGE: [(f64, f64, f64, f64); 15] = [
+    (61.0, -0.7387, 15.2641, 0.0839),
+    (72.0, -0.8772, 15.3, 0.0875),
+    (84.0, -1.135, 15.5, 0.0918),
+    (96.0, -1.52, 15.7, 0.0952),
+    (108.0, -1.7625, 16.0, 0.0997),
+    (120.0, -1.845, 16.4, 0.1059),
+    (132.0, -1.925, 16.9, 0.1092),
+    (144.0, -1.9025, 17.5, 0.1135),
+    (156.0, -1.77, 18.2, 0.1196),
+    (168.0, -1.6275, 19.0, 0.1212),
+    (180.0, -1.4775, 19.8, 0.1248),
+    (192.0, -1.34, 20.5, 0.1261),
+    (204.0, -1.2025, 21.1, 0.1273),
+    (216.0, -1.04, 21.7, 0.1277),
+    (228.0, -0.9267, 22.2, 0.1286),
+];
+
+/// An approximation of the WHO 2007 BMI-for-age reference for girls; see
+/// `APPROXIMATE_BOYS_BMI_FOR_AGE`.
+const APPROXIMATE_GIRLS_BMI_FOR_AGE: [(f64, f64, f64, f64); 15] = [
+    (61.0, -0.8886, 15.2441, 0.09692),
+    (72.0, -1.0021, 15.3, 0.102),
+    (84.0, -1.19, 15.4, 0.1085),
+    (96.0, -1.345, 15.7, 0.1131),
+    (108.0, -1.435, 16.1, 0.1192),
+    (120.0, -1.5225, 16.6, 0.1223),
+    (132.0, -1.525, 17.2, 0.1263),
+    (144.0, -1.415, 18.0, 0.1313),
+    (156.0, -1.27, 18.8, 0.1353),
+    (168.0, -1.17, 19.6, 0.1383),
+    (180.0, -1.1325, 20.2, 0.1382),
+    (192.0, -1.085, 20.7, 0.1402),
+    (204.0, -0.97, 21.0, 0.141),
+    (216.0, -0.83, 21.3, 0.1444),
+    (228.0, -0.7767, 21.4, 0.1444),
+];
+
+/// Approximate WHO 2007 BMI-for-age LMS parameters for children and adolescents aged 5–19 years.
+///
+/// The embedded table is fitted to the WHO cut-offs at whole years, not copied from the
+/// published monthly LMS tables, and ages in between are interpolated linearly in L, M and S.
+/// The z-scores agree with the WHO reference to within about 0.05 SD at birthdays, and may
+/// differ by more between them. Use the published tables where exact WHO z-scores are needed.
+///
+/// ## Parameters
+///
+/// * `sex`: Sex of the child.
+/// * `age_months`: Age in months, from 61 to 228.
+///
+/// ## Returns
+///
+/// The LMS parameters, or `BmiError::AgeOutOfRange` outside the reference.
+pub fn bmi_for_age_lms(sex: Sex, age_months: f64) -> Result<LmsParameters, BmiError> {
+    let table = match sex {
+        Sex::Male => &APPROXIMATE_BOYS_BMI_FOR_AGE,
+        Sex::Female => &APPROXIMATE_GIRLS_BMI_FOR_AGE,
+    };
+    if !(table[0].0..=table[table.len() - 1].0).contains(&age_months) {
+        return Err(BmiError::AgeOutOfRange(age_months));
+    }
+    let upper = table.iter().position(|row| row.0 >= age_months).unwrap_or(table.len() - 1).max(1);
+    let (a0, l0, m0, s0) = table[upper - 1];
+    let (a1, l1, m1, s1) = table[upper];
+    let w = (age_months - a0) / (a1 - a0);
+    Ok(LmsParameters::new(l0 + w * (l1 - l0), m0 + w * (m1 - m0), s0 + w * (s1 - s0)))
+}
+
+/// The WHO classification of BMI-for-age in children and adolescents aged 5–19 years.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PaediatricCategory {
+    /// z-score below -3.
+    SevereThinness,
+    /// z-score from -3 to below -2.
+    Thinness,
+    /// z-score from -2 to +1.
+    Normal,
+    /// z-score above +1 up to +2.
+    Overweight,
+    /// z-score above +2.
+    Obesity,
+}
+
+/// A child's BMI relative to the BMI-for-age reference.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BmiForAge {
+    /// The BMI in kg/m².
+    pub bmi: f64,
+    /// The BMI-for-age z-score.
+    pub z_score: f64,
+    /// The BMI-for-age percentile, between 0 and 100.
+    pub percentile: f64,
+}
+
+impl BmiForAge {
+    /// The WHO category of the z-score.
+    pub fn category(&self) -> PaediatricCategory {
+        if self.z_score < -3.0 {
+            PaediatricCategory::SevereThinness
+        } else if self.z_score < -2.0 {
+            PaediatricCategory::Thinness
+        } else if self.z_score <= 1.0 {
+            PaediatricCategory::Normal
+        } else if self.z_score <= 2.0 {
+            PaediatricCategory::Overweight
+        } else {
+            PaediatricCategory::Obesity
+        }
+    }
+}
+
+/// The change in BMI between a baseline and a follow-up visit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BmiChange {
+    /// The BMI at baseline.
+    pub baseline: Bmi,
+    /// The BMI at follow-up.
+    pub follow_up: Bmi,
+}
+
+impl BmiChange {
+    /// Creates a new `BmiChange`.
+    pub fn new(baseline: Bmi, follow_up: Bmi) -> Self {
+        Self { baseline, follow_up }
+    }
+
+    /// The absolute change, follow-up minus baseline, in kg/m².
+    pub fn absolute(&self) -> f64 {
+        self.follow_up.value() - self.baseline.value()
+    }
+
+    /// The change as a percentage of the baseline BMI.
+    ///
+    /// ## Formula
+    ///
+    /// %ΔBMI = 100 (BMI_follow-up - BMI_baseline) / BMI_baseline
+    pub fn percent(&self) -> f64 {
+        100.0 * self.absolute() / self.baseline.value()
+    }
+}
+
+/// Closure extracting the BMI change of a patient, if both visits are available.
+type BmiChangeFn<P> = Box<dyn Fn(&P) -> Option<BmiChange>>;
+
+/// A tier comparing the percent change in BMI with a clinical margin.
+///
+/// The percent change is comparable between patients of different baseline BMI, unlike the
+/// absolute change. A pair in which either patient lacks a follow-up BMI is tied.
+pub struct PercentBmiChangeTier<P> {
+    name: String,
+    change: BmiChangeFn<P>,
+    margin: f64,
+    direction: Direction,
+}
+
+impl<P> PercentBmiChangeTier<P> {
+    /// Creates a new `PercentBmiChangeTier`.
+    ///
+    /// ## Parameters
+    ///
+    /// * `name`: Name of the tier.
+    /// * `change`: Closure returning the BMI change of a patient, or `None` if it is missing.
+    /// * `margin`: Smallest difference in percentage points regarded as clinically meaningful.
+    /// * `direction`: Whether a larger or a smaller change is better; `LowerIsBetter` rewards weight loss.
+    pub fn new(
+        name: &str,
+        change: impl Fn(&P) -> Option<BmiChange> + 'static,
+        margin: f64,
+        direction: Direction,
+    ) -> Self {
+        Self { name: name.to_string(), change: Box::new(change), margin, direction }
+    }
+}
+
+impl<P> OutcomeTier<P> for PercentBmiChangeTier<P> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn compare(&self, treatment: &P, control: &P) -> TierOutcome {
+        match ((self.change)(treatment), (self.change)(control)) {
+            (Some(t), Some(c)) => compare_with_margin(t.percent() - c.percent(), self.margin, self.direction),
+            _ => TierOutcome::Tie,
+        }
+    }
+}
@@ -0,0 +1,369 @@
+//! # Copula Models
+//!
+//! Alternative dependence structures for the joint survival model of the simulation study.
+//! The joint survival function is written as S(t, x) = C(S_T(t), S_X(x)), where S_T and S_X
+//! are Weibull marginals and C is a copula. The Gumbel–Hougaard copula reproduces the
+//! paper's model exp[-(λ1 t + λ2 x)^α]; the Clayton, Frank, Gaussian and independence
+//! copulas can be used to stress-test analyses under other forms of dependence.
+
+use rand::Rng;
+
+use crate::error::{positive, Result};
+
+use super::distributions::{
+    bivariate_normal_cdf, sample_open_uniform, sample_positive_stable, sample_standard_exponential,
+    standard_normal_cdf, standard_normal_quantile,
+};
+use super::simulation::{Arm, JointSurvivalModel, SimulationParams};
+
+/// A bivariate copula C(u, v) on the unit square.
+pub trait Copula {
+    /// The copula C(u, v) = P(U <= u, V <= v).
+    fn cdf(&self, u: f64, v: f64) -> f64;
+
+    /// The conditional distribution P(V <= v | U = u) = ∂C/∂u.
+    fn partial_u(&self, u: f64, v: f64) -> f64;
+
+    /// The conditional distribution P(U <= u | V = v) = ∂C/∂v.
+    ///
+    /// The default implementation assumes an exchangeable copula, C(u, v) = C(v, u).
+    fn partial_v(&self, u: f64, v: f64) -> f64 {
+        self.partial_u(v, u)
+    }
+
+    /// Solves ∂C/∂u (u, v) = w for v.
+    ///
+    /// The default implementation uses bisection, since ∂C/∂u is non-decreasing in v.
+    fn inverse_partial_u(&self, u: f64, w: f64) -> f64 {
+        let (mut low, mut high) = (0.0, 1.0);
+        for _ in 0..100 {
+            let mid = 0.5 * (low + high);
+            if self.partial_u(u, mid) < w {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        0.5 * (low + high)
+    }
+
+    /// Draws `(u, v)` from the copula by the conditional distribution method.
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> (f64, f64) {
+        let u = sample_open_uniform(rng);
+        let w = sample_open_uniform(rng);
+        (u, self.inverse_partial_u(u, w))
+    }
+}
+
+/// The independence copula, C(u, v) = uv.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct IndependenceCopula;
+
+impl Copula for IndependenceCopula {
+    fn cdf(&self, u: f64, v: f64) -> f64 {
+        u * v
+    }
+
+    fn partial_u(&self, _u: f64, v: f64) -> f64 {
+        v
+    }
+
+    fn inverse_partial_u(&self, _u: f64, w: f64) -> f64 {
+        w
+    }
+}
+
+/// The Gumbel–Hougaard copula in the parameterization of the paper,
+/// C(u, v) = exp(-[(-ln u)^(1/α) + (-ln v)^(1/α)]^α).
+///
+/// `alpha` lies in (0, 1]; `alpha = 1` is independence and Kendall's τ is 1 - α.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GumbelHougaardCopula {
+    /// The dependence parameter α.
+    pub alpha: f64,
+}
+
+impl GumbelHougaardCopula {
+    /// Creates a new `GumbelHougaardCopula`.
+    pub fn new(alpha: f64) -> Self {
+        Self { alpha }
+    }
+
+    fn sum_of_powers(&self, u: f64, v: f64) -> f64 {
+        (-u.ln()).powf(1.0 / self.alpha) + (-v.ln()).powf(1.0 / self.alpha)
+    }
+}
+
+impl Copula for GumbelHougaardCopula {
+    fn cdf(&self, u: f64, v: f64) -> f64 {
+        if u <= 0.0 || v <= 0.0 {
+            return 0.0;
+        }
+        (-self.sum_of_powers(u, v).powf(self.alpha)).exp()
+    }
+
+    fn partial_u(&self, u: f64, v: f64) -> f64 {
+        if v <= 0.0 {
+            return 0.0;
+        }
+        if v >= 1.0 {
+            return 1.0;
+        }
+        let a = self.sum_of_powers(u, v);
+        self.cdf(u, v) * a.powf(self.alpha - 1.0) * (-u.ln()).powf(1.0 / self.alpha - 1.0) / u
+    }
+
+    /// Marshall–Olkin sampling with a positive stable frailty Z: given Z, -ln U and -ln V are
+    /// (E / Z)^α for independent standard exponentials E, as in `simulation::sample_event_times`.
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> (f64, f64) {
+        let frailty = sample_positive_stable(self.alpha, rng);
+        let u = (-(sample_standard_exponential(rng) / frailty).powf(self.alpha)).exp();
+        let v = (-(sample_standard_exponential(rng) / frailty).powf(self.alpha)).exp();
+        (u, v)
+    }
+}
+
+/// The Clayton copula, C(u, v) = (u^-θ + v^-θ - 1)^(-1/θ), for θ > 0.
+///
+/// Kendall's τ is θ / (θ + 2). The dependence is concentrated in the lower tail, which for
+/// survival probabilities means late events.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClaytonCopula {
+    /// The dependence parameter θ.
+    pub theta: f64,
+}
+
+impl ClaytonCopula {
+    /// Creates a new `ClaytonCopula`.
+    pub fn new(theta: f64) -> Self {
+        Self { theta }
+    }
+}
+
+impl Copula for ClaytonCopula {
+    fn cdf(&self, u: f64, v: f64) -> f64 {
+        if u <= 0.0 || v <= 0.0 {
+            return 0.0;
+        }
+        (u.powf(-self.theta) + v.powf(-self.theta) - 1.0).powf(-1.0 / self.theta)
+    }
+
+    fn partial_u(&self, u: f64, v: f64) -> f64 {
+        if v <= 0.0 {
+            return 0.0;
+        }
+        if v >= 1.0 {
+            return 1.0;
+        }
+        // u^(-θ-1) (u^-θ + v^-θ - 1)^(-1/θ-1), with u^-θ factored out so that tiny u and v
+        // do not give inf * 0.
+        (1.0 + (u / v).powf(self.theta) - u.powf(self.theta)).powf(-1.0 / self.theta - 1.0)
+    }
+
+    fn inverse_partial_u(&self, u: f64, w: f64) -> f64 {
+        ((w.powf(-self.theta / (1.0 + self.theta)) - 1.0) * u.powf(-self.theta) + 1.0).powf(-1.0 / self.theta)
+    }
+}
+
+/// The Frank copula, C(u, v) = -1/θ ln(1 + (e^(-θu) - 1)(e^(-θv) - 1) / (e^(-θ) - 1)), for θ ≠ 0.
+///
+/// Negative θ gives negative dependence. The dependence is symmetric in the two tails.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrankCopula {
+    /// The dependence parameter θ.
+    pub theta: f64,
+}
+
+impl FrankCopula {
+    /// Creates a new `FrankCopula`.
+    pub fn new(theta: f64) -> Self {
+        Self { theta }
+    }
+}
+
+impl Copula for FrankCopula {
+    fn cdf(&self, u: f64, v: f64) -> f64 {
+        let a_u = (-self.theta * u).exp_m1();
+        let a_v = (-self.theta * v).exp_m1();
+        let a_1 = (-self.theta).exp_m1();
+        -(a_u * a_v / a_1).ln_1p() / self.theta
+    }
+
+    fn partial_u(&self, u: f64, v: f64) -> f64 {
+        let a_u = (-self.theta * u).exp_m1();
+        let a_v = (-self.theta * v).exp_m1();
+        let a_1 = (-self.theta).exp_m1();
+        (-self.theta * u).exp() * a_v / (a_1 + a_u * a_v)
+    }
+
+    fn inverse_partial_u(&self, u: f64, w: f64) -> f64 {
+        let a_1 = (-self.theta).exp_m1();
+        -(w * a_1 / (w + (1.0 - w) * (-self.theta * u).exp())).ln_1p() / self.theta
+    }
+}
+
+/// The Gaussian copula with correlation ρ, C(u, v) = Φ2(Φ⁻¹(u), Φ⁻¹(v); ρ), for |ρ| < 1.
+///
+/// Kendall's τ is 2 asin(ρ) / π. The copula has no tail dependence.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GaussianCopula {
+    /// The correlation ρ of the underlying bivariate normal distribution.
+    pub rho: f64,
+}
+
+impl GaussianCopula {
+    /// Creates a new `GaussianCopula`.
+    pub fn new(rho: f64) -> Self {
+        Self { rho }
+    }
+
+    /// The Gaussian copula with Kendall's τ equal to `tau`.
+    pub fn from_kendall_tau(tau: f64) -> Self {
+        Self::new((tau * std::f64::consts::FRAC_PI_2).sin())
+    }
+}
+
+impl Copula for GaussianCopula {
+    fn cdf(&self, u: f64, v: f64) -> f64 {
+        bivariate_normal_cdf(standard_normal_quantile(u), standard_normal_quantile(v), self.rho)
+    }
+
+    fn partial_u(&self, u: f64, v: f64) -> f64 {
+        let scale = (1.0 - self.rho * self.rho).sqrt();
+        standard_normal_cdf((standard_normal_quantile(v) - self.rho * standard_normal_quantile(u)) / scale)
+    }
+
+    fn inverse_partial_u(&self, u: f64, w: f64) -> f64 {
+        let scale = (1.0 - self.rho * self.rho).sqrt();
+        standard_normal_cdf(self.rho * standard_normal_quantile(u) + scale * standard_normal_quantile(w))
+    }
+}
+
+/// A Weibull marginal with survival function S(t) = exp(-(λt)^k).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WeibullMarginal {
+    /// The rate λ.
+    pub rate: f64,
+    /// The shape k.
+    pub shape: f64,
+}
+
+impl WeibullMarginal {
+    /// Creates a new `WeibullMarginal`.
+    pub fn new(rate: f64, shape: f64) -> Self {
+        Self { rate, shape }
+    }
+
+    /// The survival function, S(t).
+    pub fn survival(&self, t: f64) -> f64 {
+        if t <= 0.0 {
+            return 1.0;
+        }
+        (-(self.rate * t).powf(self.shape)).exp()
+    }
+
+    /// The probability density function, f(t) = -dS/dt.
+    pub fn pdf(&self, t: f64) -> f64 {
+        if t <= 0.0 {
+            return 0.0;
+        }
+        self.shape * self.rate.powf(self.shape) * t.powf(self.shape - 1.0) * self.survival(t)
+    }
+
+    /// The time t with S(t) = `s`.
+    pub fn inverse_survival(&self, s: f64) -> f64 {
+        (-s.ln()).powf(1.0 / self.shape) / self.rate
+    }
+
+    /// The marginal with its rate multiplied by `multiplier`.
+    fn scaled(&self, multiplier: f64) -> Self {
+        Self::new(self.rate * multiplier, self.shape)
+    }
+}
+
+/// A joint survival model S(t, x) = C(S_T(t), S_X(x)) with Weibull marginals.
+///
+/// The treatment arm multiplies both marginal rates by θ, as in `SimulationParams`, while the
+/// copula is shared by the two arms.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CopulaModel<C> {
+    /// Marginal distribution of the fatal event time T in the control arm.
+    pub fatal: WeibullMarginal,
+    /// Marginal distribution of the non-fatal event time X in the control arm.
+    pub non_fatal: WeibullMarginal,
+    /// Treatment effect θ on the marginal rates.
+    pub theta: f64,
+    /// The copula linking the marginal survival probabilities.
+    pub copula: C,
+}
+
+impl<C: Copula> CopulaModel<C> {
+    /// Creates a new `CopulaModel`.
+    pub fn new(fatal: WeibullMarginal, non_fatal: WeibullMarginal, theta: f64, copula: C) -> Self {
+        Self { fatal, non_fatal, theta, copula }
+    }
+
+    /// Uses the marginals and treatment effect of `params` with a different copula.
+    ///
+    /// With `GumbelHougaardCopula::new(params.alpha)` this reproduces `params` exactly.
+    pub fn from_params(params: &SimulationParams, copula: C) -> Self {
+        Self::new(
+            WeibullMarginal::new(params.lambda1, params.alpha),
+            WeibullMarginal::new(params.lambda2, params.alpha),
+            params.theta,
+            copula,
+        )
+    }
+
+    /// Marginals of T and X in `arm`.
+    pub fn marginals(&self, arm: Arm) -> (WeibullMarginal, WeibullMarginal) {
+        let multiplier = match arm {
+            Arm::Control => 1.0,
+            Arm::Treatment => self.theta,
+        };
+        (self.fatal.scaled(multiplier), self.non_fatal.scaled(multiplier))
+    }
+}
+
+impl<C: Copula> JointSurvivalModel for CopulaModel<C> {
+    fn joint_survival(&self, arm: Arm, t: f64, x: f64) -> f64 {
+        let (fatal, non_fatal) = self.marginals(arm);
+        self.copula.cdf(fatal.survival(t), non_fatal.survival(x))
+    }
+
+    fn pdf_t(&self, arm: Arm, t: f64) -> f64 {
+        self.marginals(arm).0.pdf(t)
+    }
+
+    /// f(x|c) = ∂C/∂v (S_T(c), S_X(x)) * f_X(x) / S_T(c).
+    fn pdf_x_given_t(&self, arm: Arm, x: f64, c: f64) -> f64 {
+        let (fatal, non_fatal) = self.marginals(arm);
+        let s_c = fatal.survival(c);
+        let f_x = non_fatal.pdf(x);
+        if s_c == 0.0 || f_x == 0.0 {
+            return 0.0;
+        }
+        self.copula.partial_v(s_c, non_fatal.survival(x)) * f_x / s_c
+    }
+
+    fn sample_event_times<R: Rng + ?Sized>(&self, arm: Arm, rng: &mut R) -> (f64, f64) {
+        let (fatal, non_fatal) = self.marginals(arm);
+        let (u, v) = self.copula.sample(rng);
+        (fatal.inverse_survival(u), non_fatal.inverse_survival(v))
+    }
+
+    fn marginal_survival_t(&self, arm: Arm, t: f64) -> f64 {
+        self.marginals(arm).0.survival(t)
+    }
+
+    /// Requires positive, finite marginal rates and shapes and θ. The copula parameter is
+    /// not checked.
+    fn validate(&self) -> Result<()> {
+        positive("fatal.rate", self.fatal.rate)?;
+        positive("fatal.shape", self.fatal.shape)?;
+        positive("non_fatal.rate", self.non_fatal.rate)?;
+        positive("non_fatal.shape", self.non_fatal.shape)?;
+        positive("theta", self.theta)?;
+        Ok(())
+    }
+}
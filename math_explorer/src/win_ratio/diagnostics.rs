@@ -0,0 +1,234 @@
+//! # Density Diagnostics
+//!
+//! Numerical checks that the densities of a joint survival model agree with its survival
+//! functions. The densities of `SimulationParams` and the copula models are derived by hand,
+//! so a sign or chain-rule error would silently bias every probability win ratio computed
+//! from them. Each density is compared with the central difference of the survival function
+//! it comes from and integrated over [0, ∞) to check that it carries the right mass.
+
+use crate::integration::{Method, Quadrature};
+
+use super::simulation::{Arm, JointSurvivalModel, SimulationParams};
+
+/// Which density of a model was checked.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DensityKind {
+    /// The marginal density of the fatal event time, f(t) = -dS(t)/dt.
+    FatalEvent,
+    /// The conditional density of the non-fatal event time given T > c, f(x|c) = -dG(x|c)/dx.
+    NonFatalEvent {
+        /// The conditioning time c.
+        follow_up: f64,
+    },
+}
+
+/// The result of checking one density of one arm.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DensityCheck {
+    /// The arm.
+    pub arm: Arm,
+    /// The density.
+    pub kind: DensityKind,
+    /// Largest absolute difference between the density and the numerical derivative.
+    pub max_derivative_error: f64,
+    /// The time at which `max_derivative_error` occurs.
+    pub worst_time: f64,
+    /// Absolute difference between the integral of the density and the mass it should carry.
+    pub normalisation_error: f64,
+    /// Whether the quadrature for the normalisation met its tolerance.
+    pub quadrature_converged: bool,
+}
+
+impl DensityCheck {
+    /// The larger of the derivative and normalisation errors, or NaN if either is NaN.
+    pub fn max_error(&self) -> f64 {
+        max_propagating_nan(self.max_derivative_error, self.normalisation_error)
+    }
+}
+
+/// The larger of two errors. Unlike `f64::max`, a NaN error wins, so a broken density is not
+/// hidden by a maximum over many checks.
+fn max_propagating_nan(a: f64, b: f64) -> f64 {
+    if a.is_nan() || b.is_nan() { f64::NAN } else { a.max(b) }
+}
+
+/// All density checks of one model.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiagnosticsReport {
+    /// One check per arm and density.
+    pub checks: Vec<DensityCheck>,
+}
+
+impl DiagnosticsReport {
+    /// Largest derivative error over all checks, or NaN if any is NaN.
+    pub fn max_derivative_error(&self) -> f64 {
+        self.checks.iter().map(|c| c.max_derivative_error).fold(0.0, max_propagating_nan)
+    }
+
+    /// Largest normalisation error over all checks, or NaN if any is NaN.
+    pub fn max_normalisation_error(&self) -> f64 {
+        self.checks.iter().map(|c| c.normalisation_error).fold(0.0, max_propagating_nan)
+    }
+
+    /// The check with the largest error.
+    pub fn worst(&self) -> Option<&DensityCheck> {
+        self.checks.iter().max_by(|a, b| a.max_error().total_cmp(&b.max_error()))
+    }
+
+    /// Whether every error is at most `tolerance`. NaN errors fail.
+    pub fn passes(&self, tolerance: f64) -> bool {
+        self.checks.iter().all(|c| c.max_derivative_error <= tolerance && c.normalisation_error <= tolerance)
+    }
+}
+
+/// Where and how the densities are checked.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiagnosticSettings {
+    /// Times at which each density is compared with the numerical derivative.
+    pub times: Vec<f64>,
+    /// Conditioning times c of the non-fatal event densities.
+    pub follow_ups: Vec<f64>,
+    /// Step of the central difference relative to max(t, 1).
+    pub relative_step: f64,
+    /// The quadrature used for the normalisation integrals.
+    pub quadrature: Quadrature,
+}
+
+impl Default for DiagnosticSettings {
+    fn default() -> Self {
+        Self {
+            times: vec![0.05, 0.1, 0.25, 0.5, 1.0, 2.0, 5.0, 10.0],
+            follow_ups: vec![0.5, 1.0, 2.0, 5.0],
+            relative_step: 1e-5,
+            quadrature: Quadrature::new(Method::GaussKronrod, 1e-10),
+        }
+    }
+}
+
+/// Checks a density against the survival function it is derived from.
+fn check_density(
+    arm: Arm,
+    kind: DensityKind,
+    density: impl Fn(f64) -> f64,
+    survival: impl Fn(f64) -> f64,
+    settings: &DiagnosticSettings,
+) -> DensityCheck {
+    let mut max_derivative_error = 0.0;
+    let mut worst_time = f64::NAN;
+    for &t in &settings.times {
+        let h = settings.relative_step * t.max(1.0);
+        let derivative = -(survival(t + h) - survival(t - h)) / (2.0 * h);
+        let error = (density(t) - derivative).abs();
+        // A NaN error is recorded too, so a broken density cannot hide.
+        if error.is_nan() || error > max_derivative_error {
+            max_derivative_error = error;
+            worst_time = t;
+        }
+    }
+    let mass = survival(0.0) - survival(f64::INFINITY);
+    let integral = settings.quadrature.integrate(&density, 0.0, f64::INFINITY);
+    DensityCheck {
+        arm,
+        kind,
+        max_derivative_error,
+        worst_time,
+        normalisation_error: (integral.integral - mass).abs(),
+        quadrature_converged: integral.converged,
+    }
+}
+
+/// Checks the densities of a joint survival model against its survival functions.
+///
+/// ## Formula
+///
+/// For each arm, time t and follow-up c:
+///
+/// - f(t) is compared with -(S(t + h) - S(t - h)) / 2h, and ∫_0^∞ f(t) dt with S(0) - S(∞).
+/// - f(x|c) is compared with -(G(x + h|c) - G(x - h|c)) / 2h, and ∫_0^∞ f(x|c) dx with
+///   G(0|c) - G(∞|c).
+///
+/// The central difference has an error of order h² f''', so errors of about 1e-8 are expected
+/// with the default step, while a sign or factor error is of the size of the density itself.
+///
+/// ## Parameters
+///
+/// * `model`: The joint survival model.
+/// * `settings`: The evaluation times, follow-ups, step and quadrature.
+///
+/// ## Returns
+///
+/// A `DiagnosticsReport` with one check per arm for f(t) and per arm and follow-up for f(x|c).
+pub fn diagnose_model<M: JointSurvivalModel>(model: &M, settings: &DiagnosticSettings) -> DiagnosticsReport {
+    let mut checks = Vec::new();
+    for arm in [Arm::Control, Arm::Treatment] {
+        checks.push(check_density(
+            arm,
+            DensityKind::FatalEvent,
+            |t| model.pdf_t(arm, t),
+            |t| model.marginal_survival_t(arm, t),
+            settings,
+        ));
+        for &c in &settings.follow_ups {
+            checks.push(check_density(
+                arm,
+                DensityKind::NonFatalEvent { follow_up: c },
+                |x| model.pdf_x_given_t(arm, x, c),
+                |x| model.conditional_survival_x_given_t(arm, x, c),
+                settings,
+            ));
+        }
+    }
+    DiagnosticsReport { checks }
+}
+
+/// Diagnostics of several models, such as a grid of parameter values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GridDiagnostics<M> {
+    /// Each model with its report.
+    pub reports: Vec<(M, DiagnosticsReport)>,
+}
+
+impl<M> GridDiagnostics<M> {
+    /// Largest derivative error over the grid, or NaN if any is NaN.
+    pub fn max_derivative_error(&self) -> f64 {
+        self.reports.iter().map(|(_, r)| r.max_derivative_error()).fold(0.0, max_propagating_nan)
+    }
+
+    /// Largest normalisation error over the grid, or NaN if any is NaN.
+    pub fn max_normalisation_error(&self) -> f64 {
+        self.reports.iter().map(|(_, r)| r.max_normalisation_error()).fold(0.0, max_propagating_nan)
+    }
+
+    /// The model with the largest error, with its report.
+    pub fn worst(&self) -> Option<&(M, DiagnosticsReport)> {
+        let error = |r: &DiagnosticsReport| r.worst().map_or(0.0, DensityCheck::max_error);
+        self.reports.iter().max_by(|a, b| error(&a.1).total_cmp(&error(&b.1)))
+    }
+
+    /// Whether every model passes with `tolerance`.
+    pub fn passes(&self, tolerance: f64) -> bool {
+        self.reports.iter().all(|(_, r)| r.passes(tolerance))
+    }
+}
+
+/// Checks the densities of every model in a grid.
+///
+/// See `diagnose_model` for the checks.
+pub fn diagnose_grid<M: JointSurvivalModel + Clone>(models: &[M], settings: &DiagnosticSettings) -> GridDiagnostics<M> {
+    GridDiagnostics { reports: models.iter().map(|m| (m.clone(), diagnose_model(m, settings))).collect() }
+}
+
+/// The Cartesian product of parameter values of the simulation model.
+pub fn parameter_grid(lambda1: &[f64], lambda2: &[f64], alpha: &[f64], theta: &[f64]) -> Vec<SimulationParams> {
+    let mut grid = Vec::with_capacity(lambda1.len() * lambda2.len() * alpha.len() * theta.len());
+    for &l1 in lambda1 {
+        for &l2 in lambda2 {
+            for &a in alpha {
+                for &th in theta {
+                    grid.push(SimulationParams::new(l1, l2, a, th));
+                }
+            }
+        }
+    }
+    grid
+}
@@ -0,0 +1,428 @@
+//! # Distribution Functions
+//!
+//! Cumulative distribution and quantile functions used to build confidence intervals
+//! and p-values for the win statistics, and random variate generators used by the
+//! simulation modules.
+
+use std::f64::consts::PI;
+
+use rand::Rng;
+
+use crate::integration::{Method, Quadrature};
+
+/// Cumulative distribution function of the standard normal distribution, Φ(x).
+///
+/// Uses Hart's double-precision rational approximation (as given by West, 2005),
+/// which is accurate to about 1e-15 over the whole real line.
+///
+/// ## Example
+///
+/// ```
+/// use math_explorer::win_ratio::distributions::standard_normal_cdf;
+/// assert!((standard_normal_cdf(1.96) - 0.975).abs() < 1e-4);
+/// ```
+pub fn standard_normal_cdf(x: f64) -> f64 {
+    let x_abs = x.abs();
+    let tail = if x_abs > 37.0 {
+        0.0
+    } else {
+        let exponential = (-x_abs * x_abs / 2.0).exp();
+        if x_abs < 7.071_067_811_865_47 {
+            let mut numerator = 3.526_249_659_989_11e-2 * x_abs + 0.700_383_064_443_688;
+            numerator = numerator * x_abs + 6.373_962_203_531_65;
+            numerator = numerator * x_abs + 33.912_866_078_383;
+            numerator = numerator * x_abs + 112.079_291_497_871;
+            numerator = numerator * x_abs + 221.213_596_169_931;
+            numerator = numerator * x_abs + 220.206_867_912_376;
+
+            let mut denominator = 8.838_834_764_831_84e-2 * x_abs + 1.755_667_163_182_64;
+            denominator = denominator * x_abs + 16.064_177_579_207;
+            denominator = denominator * x_abs + 86.780_732_202_946_1;
+            denominator = denominator * x_abs + 296.564_248_779_674;
+            denominator = denominator * x_abs + 637.333_633_378_831;
+            denominator = denominator * x_abs + 793.826_512_519_948;
+            denominator = denominator * x_abs + 440.413_735_824_752;
+
+            exponential * numerator / denominator
+        } else {
+            let mut fraction = x_abs + 0.65;
+            fraction = x_abs + 4.0 / fraction;
+            fraction = x_abs + 3.0 / fraction;
+            fraction = x_abs + 2.0 / fraction;
+            fraction = x_abs + 1.0 / fraction;
+            exponential / fraction / (2.0 * PI).sqrt()
+        }
+    };
+
+    if x > 0.0 { 1.0 - tail } else { tail }
+}
+
+/// Probability density function of the standard normal distribution, φ(x).
+pub fn standard_normal_pdf(x: f64) -> f64 {
+    (-x * x / 2.0).exp() / (2.0 * PI).sqrt()
+}
+
+/// Quantile function of the standard normal distribution, Φ⁻¹(p).
+///
+/// Uses Acklam's rational approximation followed by one Halley refinement step.
+/// Returns `f64::NEG_INFINITY` for `p <= 0` and `f64::INFINITY` for `p >= 1`.
+///
+/// ## Example
+///
+/// ```
+/// use math_explorer::win_ratio::distributions::standard_normal_quantile;
+/// assert!((standard_normal_quantile(0.975) - 1.959964).abs() < 1e-6);
+/// ```
+pub fn standard_normal_quantile(p: f64) -> f64 {
+    if p <= 0.0 {
+        return f64::NEG_INFINITY;
+    }
+    if p >= 1.0 {
+        return f64::INFINITY;
+    }
+
+    const A: [f64; 6] = [
+        -3.969_683_028_665_376e1,
+        2.209_460_984_245_205e2,
+        -2.759_285_104_469_687e2,
+        1.383_577_518_672_69e2,
+        -3.066_479_806_614_716e1,
+        2.506_628_277_459_239,
+    ];
+    const B: [f64; 5] = [
+        -5.447_609_879_822_406e1,
+        1.615_858_368_580_409e2,
+        -1.556_989_798_598_866e2,
+        6.680_131_188_771_972e1,
+        -1.328_068_155_288_572e1,
+    ];
+    const C: [f64; 6] = [
+        -7.784_894_002_430_293e-3,
+        -3.223_964_580_411_365e-1,
+        -2.400_758_277_161_838,
+        -2.549_732_539_343_734,
+        4.374_664_141_464_968,
+        2.938_163_982_698_783,
+    ];
+    const D: [f64; 4] = [
+        7.784_695_709_041_462e-3,
+        3.224_671_290_700_398e-1,
+        2.445_134_137_142_996,
+        3.754_408_661_907_416,
+    ];
+    const P_LOW: f64 = 0.02425;
+
+    let tail = |q: f64| {
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5])
+            / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0)
+    };
+
+    let x = if p < P_LOW {
+        tail((-2.0 * p.ln()).sqrt())
+    } else if p <= 1.0 - P_LOW {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q
+            / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+    } else {
+        -tail((-2.0 * (1.0 - p).ln()).sqrt())
+    };
+
+    // One step of Halley's method brings the approximation to full double precision.
+    let e = standard_normal_cdf(x) - p;
+    let u = e * (2.0 * PI).sqrt() * (x * x / 2.0).exp();
+    x - u / (1.0 + x * u / 2.0)
+}
+
+/// Two-sided p-value for a statistic with an asymptotic standard normal distribution.
+pub fn two_sided_p_value(z: f64) -> f64 {
+    if z.is_nan() {
+        return f64::NAN;
+    }
+    (2.0 * standard_normal_cdf(-z.abs())).min(1.0)
+}
+
+/// The critical value z such that a two-sided interval at `confidence_level` is ±z.
+///
+/// For example, a `confidence_level` of 0.95 gives approximately 1.96.
+pub fn two_sided_critical_value(confidence_level: f64) -> f64 {
+    standard_normal_quantile(1.0 - (1.0 - confidence_level) / 2.0)
+}
+
+/// Natural logarithm of the gamma function, ln Γ(x), for `x > 0`.
+///
+/// Uses the Lanczos approximation (g = 7, n = 9), accurate to about 1e-15.
+pub fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+
+    if x < 0.5 {
+        // Reflection formula.
+        return (PI / (PI * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let series = COEFFICIENTS
+        .iter()
+        .enumerate()
+        .skip(1)
+        .fold(COEFFICIENTS[0], |acc, (i, &c)| acc + c / (x + i as f64));
+    0.5 * (2.0 * PI).ln() + (x + 0.5) * t.ln() - t + series.ln()
+}
+
+/// Regularized lower incomplete gamma function, P(a, x) = γ(a, x) / Γ(a).
+///
+/// Uses the series expansion for `x < a + 1` and a continued fraction otherwise.
+pub fn regularized_gamma_p(a: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x < a + 1.0 {
+        gamma_series(a, x)
+    } else {
+        1.0 - gamma_continued_fraction(a, x)
+    }
+}
+
+/// Regularized upper incomplete gamma function, Q(a, x) = 1 - P(a, x).
+pub fn regularized_gamma_q(a: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 1.0;
+    }
+    if x < a + 1.0 {
+        1.0 - gamma_series(a, x)
+    } else {
+        gamma_continued_fraction(a, x)
+    }
+}
+
+const MAX_ITERATIONS: usize = 500;
+const EPSILON: f64 = 1e-15;
+const TINY: f64 = 1e-300;
+
+fn gamma_series(a: f64, x: f64) -> f64 {
+    let mut term = 1.0 / a;
+    let mut sum = term;
+    let mut ap = a;
+    for _ in 0..MAX_ITERATIONS {
+        ap += 1.0;
+        term *= x / ap;
+        sum += term;
+        if term.abs() < sum.abs() * EPSILON {
+            break;
+        }
+    }
+    sum * (-x + a * x.ln() - ln_gamma(a)).exp()
+}
+
+fn gamma_continued_fraction(a: f64, x: f64) -> f64 {
+    // Modified Lentz's method.
+    let mut b = x + 1.0 - a;
+    let mut c = 1.0 / TINY;
+    let mut d = 1.0 / b;
+    let mut h = d;
+    for i in 1..MAX_ITERATIONS {
+        let an = -(i as f64) * (i as f64 - a);
+        b += 2.0;
+        d = an * d + b;
+        if d.abs() < TINY {
+            d = TINY;
+        }
+        c = b + an / c;
+        if c.abs() < TINY {
+            c = TINY;
+        }
+        d = 1.0 / d;
+        let delta = d * c;
+        h *= delta;
+        if (delta - 1.0).abs() < EPSILON {
+            break;
+        }
+    }
+    (-x + a * x.ln() - ln_gamma(a)).exp() * h
+}
+
+/// Survival function (upper tail probability) of the chi-squared distribution.
+///
+/// ## Parameters
+///
+/// * `x`: The observed statistic.
+/// * `degrees_of_freedom`: The degrees of freedom of the distribution.
+pub fn chi_squared_survival(x: f64, degrees_of_freedom: f64) -> f64 {
+    regularized_gamma_q(degrees_of_freedom / 2.0, x / 2.0)
+}
+
+/// Regularized incomplete beta function, I_x(a, b).
+///
+/// Evaluated with the continued fraction of Numerical Recipes (modified Lentz's method),
+/// using the symmetry I_x(a, b) = 1 - I_{1-x}(b, a) for fast convergence.
+pub fn regularized_beta(x: f64, a: f64, b: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x >= 1.0 {
+        return 1.0;
+    }
+    let front = (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln()).exp();
+    if x < (a + 1.0) / (a + b + 2.0) {
+        front * beta_continued_fraction(x, a, b) / a
+    } else {
+        1.0 - front * beta_continued_fraction(1.0 - x, b, a) / b
+    }
+}
+
+fn beta_continued_fraction(x: f64, a: f64, b: f64) -> f64 {
+    let clamp = |v: f64| if v.abs() < TINY { TINY } else { v };
+    let mut c = 1.0;
+    let mut d = 1.0 / clamp(1.0 - (a + b) * x / (a + 1.0));
+    let mut h = d;
+    for m in 1..MAX_ITERATIONS {
+        let m = m as f64;
+        let even = m * (b - m) * x / ((a + 2.0 * m - 1.0) * (a + 2.0 * m));
+        d = 1.0 / clamp(1.0 + even * d);
+        c = clamp(1.0 + even / c);
+        h *= d * c;
+        let odd = -(a + m) * (a + b + m) * x / ((a + 2.0 * m) * (a + 2.0 * m + 1.0));
+        d = 1.0 / clamp(1.0 + odd * d);
+        c = clamp(1.0 + odd / c);
+        let delta = d * c;
+        h *= delta;
+        if (delta - 1.0).abs() < EPSILON {
+            break;
+        }
+    }
+    h
+}
+
+/// Quantile function of the Beta(a, b) distribution.
+///
+/// Inverts `regularized_beta` by bisection, which is slow but never fails to converge.
+pub fn beta_quantile(p: f64, a: f64, b: f64) -> f64 {
+    if p <= 0.0 {
+        return 0.0;
+    }
+    if p >= 1.0 {
+        return 1.0;
+    }
+    let (mut low, mut high) = (0.0, 1.0);
+    for _ in 0..200 {
+        let mid = 0.5 * (low + high);
+        if regularized_beta(mid, a, b) < p {
+            low = mid;
+        } else {
+            high = mid;
+        }
+        if high - low < EPSILON * mid.max(TINY) {
+            break;
+        }
+    }
+    0.5 * (low + high)
+}
+
+/// Cumulative distribution function of the Binomial(n, p) distribution, P(X <= k).
+pub fn binomial_cdf(k: u32, n: u32, p: f64) -> f64 {
+    if k >= n {
+        return 1.0;
+    }
+    regularized_beta(1.0 - p, (n - k) as f64, k as f64 + 1.0)
+}
+
+/// Cumulative distribution function of the standard bivariate normal distribution with
+/// correlation ρ, P(X <= h, Y <= k).
+///
+/// ## Formula
+///
+/// Φ2(h, k; ρ) = Φ(h)Φ(k) + 1/(2π) * integral from 0 to asin(ρ) of exp(-(h² + k² - 2hk sin θ) / (2cos² θ)) dθ
+///
+/// The substitution r = sin θ in Plackett's identity removes the singularity of the bivariate
+/// density at |r| = 1, so the integrand is smooth for |ρ| < 1.
+pub fn bivariate_normal_cdf(h: f64, k: f64, rho: f64) -> f64 {
+    if h == f64::NEG_INFINITY || k == f64::NEG_INFINITY {
+        return 0.0;
+    }
+    if h == f64::INFINITY {
+        return standard_normal_cdf(k);
+    }
+    if k == f64::INFINITY {
+        return standard_normal_cdf(h);
+    }
+    let integrand = |theta: f64| {
+        let cos = theta.cos();
+        (-(h * h + k * k - 2.0 * h * k * theta.sin()) / (2.0 * cos * cos)).exp()
+    };
+    let quadrature = Quadrature::new(Method::ClenshawCurtis, 1e-12);
+    let correction = quadrature.integrate(integrand, 0.0, rho.asin()).integral / (2.0 * PI);
+    (standard_normal_cdf(h) * standard_normal_cdf(k) + correction).clamp(0.0, 1.0)
+}
+
+/// Draws a uniform random variate on the open interval (0, 1).
+pub fn sample_open_uniform<R: Rng + ?Sized>(rng: &mut R) -> f64 {
+    loop {
+        let u = rng.gen_range(0.0..1.0_f64);
+        if u > 0.0 {
+            return u;
+        }
+    }
+}
+
+/// Draws a standard exponential random variate, Exp(1), by inversion.
+pub fn sample_standard_exponential<R: Rng + ?Sized>(rng: &mut R) -> f64 {
+    // `gen_range` returns values in [0, 1), so 1 - u lies in (0, 1] and the logarithm is finite.
+    -(1.0 - rng.gen_range(0.0..1.0_f64)).ln()
+}
+
+/// Draws a standard normal random variate by the Box–Muller transform.
+pub fn sample_standard_normal<R: Rng + ?Sized>(rng: &mut R) -> f64 {
+    let u = sample_open_uniform(rng);
+    let v = sample_open_uniform(rng);
+    (-2.0 * u.ln()).sqrt() * (2.0 * PI * v).cos()
+}
+
+/// Draws a Gamma(shape, 1) random variate.
+///
+/// Uses the Marsaglia–Tsang squeeze method for `shape >= 1`. Smaller shapes are boosted with
+/// Gamma(a) = Gamma(a + 1) * U^(1/a).
+pub fn sample_gamma<R: Rng + ?Sized>(shape: f64, rng: &mut R) -> f64 {
+    if shape < 1.0 {
+        return sample_gamma(shape + 1.0, rng) * sample_open_uniform(rng).powf(1.0 / shape);
+    }
+    let d = shape - 1.0 / 3.0;
+    let c = 1.0 / (9.0 * d).sqrt();
+    loop {
+        let z = sample_standard_normal(rng);
+        let v = (1.0 + c * z).powi(3);
+        if v <= 0.0 {
+            continue;
+        }
+        let u = sample_open_uniform(rng);
+        if u.ln() < 0.5 * z * z + d - d * v + d * v.ln() {
+            return d * v;
+        }
+    }
+}
+
+/// Draws a positive stable random variate Z with Laplace transform E[exp(-sZ)] = exp(-s^α).
+///
+/// Uses Kanter's representation: with U ~ Uniform(0, π) and E ~ Exp(1),
+///
+/// Z = sin(αU) / sin(U)^(1/α) * (sin((1 - α)U) / E)^((1 - α)/α).
+///
+/// For `alpha = 1` the distribution is degenerate at 1.
+pub fn sample_positive_stable<R: Rng + ?Sized>(alpha: f64, rng: &mut R) -> f64 {
+    if alpha >= 1.0 {
+        return 1.0;
+    }
+    // U must lie strictly inside (0, π), where sin(U) > 0.
+    let u = PI * sample_open_uniform(rng);
+    let e = sample_standard_exponential(rng);
+    (alpha * u).sin() / u.sin().powf(1.0 / alpha) * (((1.0 - alpha) * u).sin() / e).powf((1.0 - alpha) / alpha)
+}
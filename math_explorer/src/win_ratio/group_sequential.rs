@@ -0,0 +1,422 @@
+//! # Group-Sequential Monitoring
+//!
+//! Interim analyses of the win ratio with boundaries from the Lan–DeMets alpha-spending
+//! approach, so looks need not be equally spaced or fixed in advance. The information at a
+//! look is measured by the number of non-tied pairs, which is the effective sample size of
+//! `calculate_significance_test_statistic`. Boundaries are computed for the standardized
+//! statistic Z_k by recursive numerical integration over the continuation regions
+//! (Armitage, McPherson and Rowe), and designs can be checked with the trial simulator.
+
+use rand::Rng;
+
+use crate::error::{self, Error, Result};
+
+use super::distributions::{standard_normal_cdf, standard_normal_pdf, standard_normal_quantile};
+use super::sample_win_ratio::{calculate_significance_test_statistic, count_matched_pairs, WinLossCounts};
+use super::simulation::{simulate_trial_lenient, CensoringDesign, JointSurvivalModel};
+
+/// Number of grid points used to integrate over a continuation region (odd, for Simpson's rule).
+const GRID_POINTS: usize = 201;
+/// Number of standard deviations below the mean at which an open continuation region is cut.
+const TAIL_WIDTH: f64 = 8.0;
+
+/// An error-spending function, giving the cumulative error spent at information fraction t.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpendingFunction {
+    /// O'Brien–Fleming-type spending, 2 - 2Φ(z_{1-α/2} / √t). Spends very little early.
+    OBrienFleming,
+    /// Pocock-type spending, α ln(1 + (e - 1)t). Spends evenly over the looks.
+    Pocock,
+    /// Power family (Kim–DeMets), α t^ρ. ρ = 3 is close to O'Brien–Fleming and ρ = 1 to Pocock.
+    Power(f64),
+}
+
+impl SpendingFunction {
+    /// Cumulative error spent at information fraction `t` when `total` is spent at t = 1.
+    pub fn spent(&self, total: f64, t: f64) -> f64 {
+        if t <= 0.0 {
+            return 0.0;
+        }
+        if t >= 1.0 {
+            return total;
+        }
+        match *self {
+            SpendingFunction::OBrienFleming => {
+                2.0 - 2.0 * standard_normal_cdf(standard_normal_quantile(1.0 - total / 2.0) / t.sqrt())
+            }
+            SpendingFunction::Pocock => total * (1.0 + (std::f64::consts::E - 1.0) * t).ln(),
+            SpendingFunction::Power(rho) => total * t.powf(rho),
+        }
+    }
+}
+
+/// Beta spending for non-binding futility boundaries.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FutilitySpending {
+    /// Type II error, 1 - power.
+    pub beta: f64,
+    /// How β is spent over the looks.
+    pub spending: SpendingFunction,
+    /// Expected value of Z at full information under the alternative hypothesis.
+    pub drift: f64,
+}
+
+/// A one-sided group-sequential design; large Z favours treatment.
+///
+/// For a two-sided test at level 2α, use the one-sided α.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GroupSequentialDesign {
+    /// One-sided type I error.
+    pub alpha: f64,
+    /// How α is spent over the looks.
+    pub efficacy_spending: SpendingFunction,
+    /// Optional futility boundaries.
+    pub futility: Option<FutilitySpending>,
+}
+
+impl GroupSequentialDesign {
+    /// Creates a design with efficacy boundaries only.
+    pub fn new(alpha: f64, efficacy_spending: SpendingFunction) -> Self {
+        Self { alpha, efficacy_spending, futility: None }
+    }
+
+    /// Adds non-binding futility boundaries from beta spending.
+    ///
+    /// ## Parameters
+    ///
+    /// * `beta`: Type II error, 1 - power.
+    /// * `spending`: How β is spent over the looks.
+    /// * `drift`: Expected value of Z at full information under the alternative, for
+    ///   example from `matched_pairs_drift`.
+    pub fn with_futility(mut self, beta: f64, spending: SpendingFunction, drift: f64) -> Self {
+        self.futility = Some(FutilitySpending { beta, spending, drift });
+        self
+    }
+}
+
+/// Expected value of the matched-pairs Z statistic at full information under the alternative.
+///
+/// ## Formula
+///
+/// With pw = WR / (1 + WR) and N non-tied pairs, E[Z] = (pw - 0.5) √N / √(pw (1 - pw)).
+pub fn matched_pairs_drift(win_ratio: f64, max_decided_pairs: f64) -> f64 {
+    let p_w = win_ratio / (1.0 + win_ratio);
+    (p_w - 0.5) * max_decided_pairs.sqrt() / (p_w * (1.0 - p_w)).sqrt()
+}
+
+/// Information fraction from the number of non-tied pairs, capped at one.
+pub fn information_fraction(counts: &WinLossCounts, max_decided_pairs: f64) -> f64 {
+    ((counts.n_wins() + counts.n_losses()) as f64 / max_decided_pairs).min(1.0)
+}
+
+/// Boundaries of a single look.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LookBoundaries {
+    /// Information fraction of the look.
+    pub information_fraction: f64,
+    /// Stop for efficacy if Z is at or above this value.
+    pub efficacy: f64,
+    /// Stop for futility if Z is at or below this value.
+    pub futility: Option<f64>,
+    /// Cumulative type I error spent up to and including this look.
+    pub cumulative_alpha: f64,
+}
+
+/// The density of Z on the continuation region of the previous look, as Simpson nodes.
+struct ContinuationDensity {
+    /// Information fraction of the look.
+    fraction: f64,
+    /// Nodes `(z, weight * density(z))`.
+    nodes: Vec<(f64, f64)>,
+}
+
+impl ContinuationDensity {
+    /// Probability of Z_k >= `bound` (or <= when `upper` is false) without stopping before.
+    fn crossing(previous: Option<&Self>, fraction: f64, drift: f64, bound: f64, upper: bool) -> f64 {
+        let tail = |x: f64| if upper { 1.0 - standard_normal_cdf(x) } else { standard_normal_cdf(x) };
+        match previous {
+            None => tail(bound - drift * fraction.sqrt()),
+            Some(prev) => {
+                let increment = fraction - prev.fraction;
+                prev.nodes
+                    .iter()
+                    .map(|&(z, weight)| {
+                        let mean = z * prev.fraction.sqrt() + drift * increment;
+                        weight * tail((bound * fraction.sqrt() - mean) / increment.sqrt())
+                    })
+                    .sum()
+            }
+        }
+    }
+
+    /// The density of Z_k restricted to `(lower, upper)`.
+    fn restrict(previous: Option<&Self>, fraction: f64, drift: f64, lower: f64, upper: f64) -> Self {
+        let lower = if lower.is_finite() { lower } else { drift * fraction.sqrt() - TAIL_WIDTH };
+        let mut nodes = Vec::with_capacity(GRID_POINTS);
+        if upper > lower {
+            let h = (upper - lower) / (GRID_POINTS - 1) as f64;
+            for i in 0..GRID_POINTS {
+                let z = lower + i as f64 * h;
+                let simpson = if i == 0 || i == GRID_POINTS - 1 {
+                    1.0
+                } else if i % 2 == 1 {
+                    4.0
+                } else {
+                    2.0
+                };
+                let density = match previous {
+                    None => standard_normal_pdf(z - drift * fraction.sqrt()),
+                    Some(prev) => {
+                        let increment = fraction - prev.fraction;
+                        let scale = (fraction / increment).sqrt();
+                        prev.nodes
+                            .iter()
+                            .map(|&(u, weight)| {
+                                let mean = u * prev.fraction.sqrt() + drift * increment;
+                                weight * scale * standard_normal_pdf((z * fraction.sqrt() - mean) / increment.sqrt())
+                            })
+                            .sum()
+                    }
+                };
+                nodes.push((z, h / 3.0 * simpson * density));
+            }
+        }
+        Self { fraction, nodes }
+    }
+}
+
+/// Finds the bound at which the crossing probability equals `target`, by bisection.
+fn solve_bound(previous: Option<&ContinuationDensity>, fraction: f64, drift: f64, target: f64, upper: bool) -> f64 {
+    let (mut low, mut high) = (-TAIL_WIDTH - drift.abs(), TAIL_WIDTH + drift.abs());
+    for _ in 0..60 {
+        let mid = 0.5 * (low + high);
+        let probability = ContinuationDensity::crossing(previous, fraction, drift, mid, upper);
+        // The upper crossing probability decreases in the bound; the lower one increases.
+        if (probability > target) == upper {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    0.5 * (low + high)
+}
+
+/// Computes the boundaries of a design at the given information fractions.
+///
+/// Efficacy boundaries spend α under the null hypothesis and ignore the futility boundaries,
+/// so futility stopping is non-binding. Futility boundaries spend β under the alternative
+/// given the efficacy boundaries; at the final look the futility boundary equals the
+/// efficacy boundary, so every trial ends with a decision.
+///
+/// ## Parameters
+///
+/// * `design`: The group-sequential design.
+/// * `information_fractions`: Increasing information fractions of the looks. The last look
+///   spends all remaining error, whatever its fraction.
+pub fn boundaries(design: &GroupSequentialDesign, information_fractions: &[f64]) -> Vec<LookBoundaries> {
+    let k = information_fractions.len();
+    let mut looks = Vec::with_capacity(k);
+    let mut null_density: Option<ContinuationDensity> = None;
+    let mut alternative_density: Option<ContinuationDensity> = None;
+    let (mut previous_alpha, mut previous_beta) = (0.0, 0.0);
+
+    for (index, &fraction) in information_fractions.iter().enumerate() {
+        let is_last = index + 1 == k;
+        let spending_fraction = if is_last { 1.0 } else { fraction };
+
+        let cumulative_alpha = design.efficacy_spending.spent(design.alpha, spending_fraction);
+        let efficacy = solve_bound(null_density.as_ref(), fraction, 0.0, cumulative_alpha - previous_alpha, true);
+        previous_alpha = cumulative_alpha;
+
+        let futility = design.futility.map(|futility| {
+            if is_last {
+                return efficacy;
+            }
+            let cumulative_beta = futility.spending.spent(futility.beta, spending_fraction);
+            let bound = solve_bound(
+                alternative_density.as_ref(),
+                fraction,
+                futility.drift,
+                cumulative_beta - previous_beta,
+                false,
+            );
+            previous_beta = cumulative_beta;
+            bound.min(efficacy)
+        });
+
+        if !is_last {
+            null_density = Some(ContinuationDensity::restrict(null_density.as_ref(), fraction, 0.0, f64::NEG_INFINITY, efficacy));
+            if let (Some(design_futility), Some(lower)) = (design.futility, futility) {
+                alternative_density = Some(ContinuationDensity::restrict(
+                    alternative_density.as_ref(),
+                    fraction,
+                    design_futility.drift,
+                    lower,
+                    efficacy,
+                ));
+            }
+        }
+        looks.push(LookBoundaries { information_fraction: fraction, efficacy, futility, cumulative_alpha });
+    }
+    looks
+}
+
+/// The decision at an interim or final look.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    /// Z crossed the efficacy boundary: reject the null hypothesis and stop.
+    StopForEfficacy,
+    /// Z crossed the futility boundary: stop without rejecting.
+    StopForFutility,
+    /// Continue to the next look.
+    Continue,
+    /// The final look was reached without crossing the efficacy boundary.
+    FailToReject,
+}
+
+/// The analysis at one look.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InterimAnalysis {
+    /// The boundaries of the look.
+    pub boundaries: LookBoundaries,
+    /// The matched-pairs Z statistic: +∞ if every decided pair is a win, -∞ if every one
+    /// is a loss and NaN if there are none, in which case neither boundary is crossed.
+    pub z_statistic: f64,
+    /// The decision.
+    pub decision: Decision,
+}
+
+/// Monitors a trial over its looks and stops at the first boundary crossing.
+///
+/// ## Parameters
+///
+/// * `design`: The group-sequential design.
+/// * `looks`: Win-loss counts at each look, cumulative over the trial.
+/// * `max_decided_pairs`: The planned number of non-tied pairs at full information.
+///
+/// ## Returns
+///
+/// The analyses up to and including the look at which the trial stopped.
+pub fn monitor(design: &GroupSequentialDesign, looks: &[WinLossCounts], max_decided_pairs: f64) -> Vec<InterimAnalysis> {
+    let fractions: Vec<f64> = looks.iter().map(|counts| information_fraction(counts, max_decided_pairs)).collect();
+    let bounds = boundaries(design, &fractions);
+
+    let mut analyses = Vec::with_capacity(looks.len());
+    for (index, (counts, boundaries)) in looks.iter().zip(bounds).enumerate() {
+        let z_statistic = look_statistic(counts);
+        let decision = if z_statistic >= boundaries.efficacy {
+            Decision::StopForEfficacy
+        } else if boundaries.futility.is_some_and(|futility| z_statistic <= futility) {
+            Decision::StopForFutility
+        } else if index + 1 == looks.len() {
+            Decision::FailToReject
+        } else {
+            Decision::Continue
+        };
+        analyses.push(InterimAnalysis { boundaries, z_statistic, decision });
+        if decision != Decision::Continue {
+            break;
+        }
+    }
+    analyses
+}
+
+/// The Z statistic of a look. Its variance estimate is zero when all decided pairs go one
+/// way, which is the strongest evidence for that arm, so the statistic is taken as ±∞.
+fn look_statistic(counts: &WinLossCounts) -> f64 {
+    let (n_w, n_l) = (counts.n_wins(), counts.n_losses());
+    match calculate_significance_test_statistic(n_w, n_l) {
+        Ok(z) => z,
+        Err(Error::ZeroVariance) if n_l == 0 => f64::INFINITY,
+        Err(Error::ZeroVariance) => f64::NEG_INFINITY,
+        Err(_) => f64::NAN,
+    }
+}
+
+/// Operating characteristics of a design estimated by simulation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OperatingCharacteristics {
+    /// Proportion of trials stopping for efficacy at each look.
+    pub efficacy_by_look: Vec<f64>,
+    /// Proportion of trials stopping for futility at each look.
+    pub futility_by_look: Vec<f64>,
+    /// Average number of pairs enrolled when the trial stopped.
+    pub expected_pairs: f64,
+}
+
+impl OperatingCharacteristics {
+    /// Overall probability of rejecting the null hypothesis.
+    pub fn rejection_probability(&self) -> f64 {
+        self.efficacy_by_look.iter().sum()
+    }
+}
+
+/// Simulates matched-pairs trials monitored with a group-sequential design.
+///
+/// At look k the first `pairs_at_look[k]` pairs are analysed. Because the simulated pairs
+/// are complete, this mimics a trial that is analysed after each cohort finishes follow-up.
+///
+/// ## Parameters
+///
+/// * `model`: The joint survival model.
+/// * `censoring`: The censoring mechanism of each pair.
+/// * `design`: The group-sequential design.
+/// * `pairs_at_look`: Cumulative number of pairs analysed at each look.
+/// * `max_decided_pairs`: The planned number of non-tied pairs at full information.
+/// * `n_replicates`: Number of simulated trials.
+/// * `rng`: The random number generator; seed it for reproducible results.
+///
+/// ## Returns
+///
+/// The estimated operating characteristics, `Error::EmptyData` if there are no looks, or
+/// `Error::InvalidParameter` if the pair counts are not positive and strictly increasing,
+/// `max_decided_pairs` is not positive, or the model or the censoring design is invalid.
+#[allow(clippy::too_many_arguments)]
+pub fn simulate_operating_characteristics<M: JointSurvivalModel, R: Rng + ?Sized>(
+    model: &M,
+    censoring: &CensoringDesign,
+    design: &GroupSequentialDesign,
+    pairs_at_look: &[usize],
+    max_decided_pairs: f64,
+    n_replicates: usize,
+    rng: &mut R,
+) -> Result<OperatingCharacteristics> {
+    model.validate()?;
+    censoring.validate()?;
+    error::positive("max_decided_pairs", max_decided_pairs)?;
+    let Some(&max_pairs) = pairs_at_look.iter().max() else {
+        return Err(Error::EmptyData);
+    };
+    let mut previous = 0;
+    for &pairs in pairs_at_look {
+        if pairs <= previous {
+            return Err(Error::InvalidParameter { name: "pairs_at_look", value: pairs as f64 });
+        }
+        previous = pairs;
+    }
+
+    let k = pairs_at_look.len();
+    let mut efficacy_by_look = vec![0.0; k];
+    let mut futility_by_look = vec![0.0; k];
+    let mut total_pairs = 0.0;
+
+    for _ in 0..n_replicates {
+        let pairs = simulate_trial_lenient(model, censoring, max_pairs, max_pairs, rng).matched_pairs();
+        let looks: Vec<WinLossCounts> = pairs_at_look.iter().map(|&n| count_matched_pairs(&pairs[..n]).counts).collect();
+        let analyses = monitor(design, &looks, max_decided_pairs);
+        let last = analyses.len() - 1;
+        match analyses[last].decision {
+            Decision::StopForEfficacy => efficacy_by_look[last] += 1.0,
+            Decision::StopForFutility => futility_by_look[last] += 1.0,
+            Decision::Continue | Decision::FailToReject => {}
+        }
+        total_pairs += pairs_at_look[last] as f64;
+    }
+
+    let n = n_replicates as f64;
+    Ok(OperatingCharacteristics {
+        efficacy_by_look: efficacy_by_look.into_iter().map(|c| c / n).collect(),
+        futility_by_look: futility_by_look.into_iter().map(|c| c / n).collect(),
+        expected_pairs: total_pairs / n,
+    })
+}
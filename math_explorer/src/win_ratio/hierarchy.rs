@@ -0,0 +1,351 @@
+//! # Outcome Hierarchies
+//!
+//! A generic, user-defined ordering of outcomes for win ratio comparisons. A hierarchy is a
+//! list of tiers in priority order; the first tier that separates a pair decides whether
+//! it is a win or a loss for the treatment patient. Tiers can compare any patient type,
+//! such as time to death, number of hospitalizations or a continuous score with a
+//! clinical margin.
+
+use crate::error::{Error, Result};
+
+use super::sample_win_ratio::WinLossCounts;
+
+/// The result of comparing a treatment patient with a control patient on a single tier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TierOutcome {
+    /// The treatment patient has the better outcome.
+    Win,
+    /// The control patient has the better outcome.
+    Loss,
+    /// The tier does not separate the pair.
+    Tie,
+}
+
+/// Whether larger or smaller values of an outcome are preferable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Larger values are better (e.g. a quality-of-life score).
+    HigherIsBetter,
+    /// Smaller values are better (e.g. the number of hospitalizations).
+    LowerIsBetter,
+}
+
+/// A single level of an outcome hierarchy.
+pub trait OutcomeTier<P> {
+    /// Name of the tier, used in reports.
+    fn name(&self) -> &str;
+
+    /// Compares a treatment patient with a control patient on this tier.
+    fn compare(&self, treatment: &P, control: &P) -> TierOutcome;
+}
+
+/// Closure extracting an optional event time from a patient.
+type EventTimeFn<P> = Box<dyn Fn(&P) -> Option<f64>>;
+
+/// A time-to-event tier: the patient who has the event first within the shared follow-up loses.
+pub struct TimeToEventTier<P> {
+    name: String,
+    event_time: EventTimeFn<P>,
+    follow_up: Box<dyn Fn(&P) -> f64>,
+}
+
+impl<P> TimeToEventTier<P> {
+    /// Creates a new `TimeToEventTier`.
+    ///
+    /// ## Parameters
+    ///
+    /// * `name`: Name of the tier.
+    /// * `event_time`: Closure returning the event time of a patient, or `None` if it was not observed.
+    /// * `follow_up`: Closure returning the end of a patient's follow-up for this event.
+    pub fn new(
+        name: &str,
+        event_time: impl Fn(&P) -> Option<f64> + 'static,
+        follow_up: impl Fn(&P) -> f64 + 'static,
+    ) -> Self {
+        Self { name: name.to_string(), event_time: Box::new(event_time), follow_up: Box::new(follow_up) }
+    }
+}
+
+impl<P> OutcomeTier<P> for TimeToEventTier<P> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn compare(&self, treatment: &P, control: &P) -> TierOutcome {
+        let window = (self.follow_up)(treatment).min((self.follow_up)(control));
+        let within = |p: &P| (self.event_time)(p).filter(|&t| t <= window);
+        match (within(treatment), within(control)) {
+            (Some(t1), Some(t0)) if t1 < t0 => TierOutcome::Loss,
+            (Some(t1), Some(t0)) if t0 < t1 => TierOutcome::Win,
+            (Some(_), None) => TierOutcome::Loss,
+            (None, Some(_)) => TierOutcome::Win,
+            _ => TierOutcome::Tie,
+        }
+    }
+}
+
+/// A count tier, e.g. the number of hospitalizations during follow-up.
+pub struct CountTier<P> {
+    name: String,
+    count: Box<dyn Fn(&P) -> u32>,
+    direction: Direction,
+}
+
+impl<P> CountTier<P> {
+    /// Creates a new `CountTier`.
+    ///
+    /// ## Parameters
+    ///
+    /// * `name`: Name of the tier.
+    /// * `count`: Closure returning the count for a patient.
+    /// * `direction`: Whether higher or lower counts are better.
+    pub fn new(name: &str, count: impl Fn(&P) -> u32 + 'static, direction: Direction) -> Self {
+        Self { name: name.to_string(), count: Box::new(count), direction }
+    }
+}
+
+impl<P> OutcomeTier<P> for CountTier<P> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn compare(&self, treatment: &P, control: &P) -> TierOutcome {
+        let difference = (self.count)(treatment) as f64 - (self.count)(control) as f64;
+        compare_with_margin(difference, 0.0, self.direction)
+    }
+}
+
+/// A continuous tier with a clinical margin, e.g. a quality-of-life score or a BMI change.
+///
+/// The pair is only separated if the values differ by more than the margin.
+pub struct ContinuousTier<P> {
+    name: String,
+    value: Box<dyn Fn(&P) -> f64>,
+    margin: f64,
+    direction: Direction,
+}
+
+impl<P> ContinuousTier<P> {
+    /// Creates a new `ContinuousTier`.
+    ///
+    /// ## Parameters
+    ///
+    /// * `name`: Name of the tier.
+    /// * `value`: Closure returning the outcome value for a patient.
+    /// * `margin`: Smallest difference regarded as clinically meaningful.
+    /// * `direction`: Whether higher or lower values are better.
+    pub fn new(name: &str, value: impl Fn(&P) -> f64 + 'static, margin: f64, direction: Direction) -> Self {
+        Self { name: name.to_string(), value: Box::new(value), margin, direction }
+    }
+}
+
+impl<P> OutcomeTier<P> for ContinuousTier<P> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn compare(&self, treatment: &P, control: &P) -> TierOutcome {
+        let difference = (self.value)(treatment) - (self.value)(control);
+        compare_with_margin(difference, self.margin, self.direction)
+    }
+}
+
+/// Decides a tier from the treatment-minus-control difference of an outcome.
+pub(crate) fn compare_with_margin(difference: f64, margin: f64, direction: Direction) -> TierOutcome {
+    let improvement = match direction {
+        Direction::HigherIsBetter => difference,
+        Direction::LowerIsBetter => -difference,
+    };
+    if improvement > margin {
+        TierOutcome::Win
+    } else if improvement < -margin {
+        TierOutcome::Loss
+    } else {
+        TierOutcome::Tie
+    }
+}
+
+/// The result of comparing a pair over the whole hierarchy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HierarchyOutcome {
+    /// The treatment patient wins on the tier with the given index.
+    Win { tier: usize },
+    /// The treatment patient loses on the tier with the given index.
+    Loss { tier: usize },
+    /// No tier separates the pair.
+    Tie,
+}
+
+/// Wins and losses decided on a single tier.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TierCounts {
+    /// Name of the tier.
+    pub name: String,
+    /// Number of pairs won by the treatment patient on this tier.
+    pub wins: u32,
+    /// Number of pairs lost by the treatment patient on this tier.
+    pub losses: u32,
+}
+
+/// Wins and losses broken down by tier, together with the number of tied pairs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HierarchyReport {
+    /// Counts for each tier, in priority order.
+    pub tiers: Vec<TierCounts>,
+    /// Number of pairs that no tier separated.
+    pub n_ties: u32,
+}
+
+impl HierarchyReport {
+    /// Total number of wins over all tiers.
+    pub fn n_wins(&self) -> u32 {
+        self.tiers.iter().map(|t| t.wins).sum()
+    }
+
+    /// Total number of losses over all tiers.
+    pub fn n_losses(&self) -> u32 {
+        self.tiers.iter().map(|t| t.losses).sum()
+    }
+
+    /// Total number of pairs compared, including ties.
+    pub fn n_pairs(&self) -> u32 {
+        self.n_wins() + self.n_losses() + self.n_ties
+    }
+
+    /// The win ratio, Nw / Nl.
+    ///
+    /// Returns `Error::EmptyData` if no pair was decided and `Error::NoLosses` if there are
+    /// wins but no losses.
+    pub fn win_ratio(&self) -> Result<f64> {
+        match (self.n_wins(), self.n_losses()) {
+            (0, 0) => Err(Error::EmptyData),
+            (_, 0) => Err(Error::NoLosses),
+            (n_w, n_l) => Ok(n_w as f64 / n_l as f64),
+        }
+    }
+
+    /// The win ratio, Nw / Nl, returning `f64::INFINITY` if there are no losses.
+    pub fn win_ratio_lenient(&self) -> f64 {
+        if self.n_losses() == 0 {
+            return f64::INFINITY;
+        }
+        self.n_wins() as f64 / self.n_losses() as f64
+    }
+
+    /// Converts a two-tier report (fatal, then non-fatal) into `WinLossCounts`.
+    ///
+    /// Returns `None` if the hierarchy does not have exactly two tiers.
+    pub fn to_win_loss_counts(&self) -> Option<WinLossCounts> {
+        match self.tiers.as_slice() {
+            [fatal, non_fatal] => Some(WinLossCounts::new(fatal.losses, fatal.wins, non_fatal.losses, non_fatal.wins)),
+            _ => None,
+        }
+    }
+
+    fn record(&mut self, outcome: HierarchyOutcome) {
+        match outcome {
+            HierarchyOutcome::Win { tier } => self.tiers[tier].wins += 1,
+            HierarchyOutcome::Loss { tier } => self.tiers[tier].losses += 1,
+            HierarchyOutcome::Tie => self.n_ties += 1,
+        }
+    }
+}
+
+/// An ordered list of outcome tiers.
+///
+/// ## Example
+///
+/// ```
+/// use math_explorer::win_ratio::hierarchy::{ContinuousTier, Direction, OutcomeHierarchy, TierOutcome};
+///
+/// struct Patient { score: f64 }
+///
+/// let hierarchy = OutcomeHierarchy::new()
+///     .with_tier(ContinuousTier::new("score", |p: &Patient| p.score, 5.0, Direction::HigherIsBetter));
+/// let report = hierarchy.compare_matched(&[(Patient { score: 70.0 }, Patient { score: 60.0 })]);
+/// assert_eq!(report.n_wins(), 1);
+/// ```
+pub struct OutcomeHierarchy<P> {
+    tiers: Vec<Box<dyn OutcomeTier<P>>>,
+}
+
+impl<P> Default for OutcomeHierarchy<P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P> OutcomeHierarchy<P> {
+    /// Creates an empty hierarchy.
+    pub fn new() -> Self {
+        Self { tiers: Vec::new() }
+    }
+
+    /// Appends a tier with lower priority than the existing tiers.
+    pub fn with_tier(mut self, tier: impl OutcomeTier<P> + 'static) -> Self {
+        self.tiers.push(Box::new(tier));
+        self
+    }
+
+    /// Number of tiers in the hierarchy.
+    pub fn len(&self) -> usize {
+        self.tiers.len()
+    }
+
+    /// Returns `true` if the hierarchy has no tiers.
+    pub fn is_empty(&self) -> bool {
+        self.tiers.is_empty()
+    }
+
+    /// Compares a treatment patient with a control patient, tier by tier.
+    pub fn compare(&self, treatment: &P, control: &P) -> HierarchyOutcome {
+        for (tier, level) in self.tiers.iter().enumerate() {
+            match level.compare(treatment, control) {
+                TierOutcome::Win => return HierarchyOutcome::Win { tier },
+                TierOutcome::Loss => return HierarchyOutcome::Loss { tier },
+                TierOutcome::Tie => {}
+            }
+        }
+        HierarchyOutcome::Tie
+    }
+
+    /// Win and loss scores of a pair, for use with `unmatched::pairwise_probabilities_with`.
+    pub fn scores(&self, treatment: &P, control: &P) -> (f64, f64) {
+        match self.compare(treatment, control) {
+            HierarchyOutcome::Win { .. } => (1.0, 0.0),
+            HierarchyOutcome::Loss { .. } => (0.0, 1.0),
+            HierarchyOutcome::Tie => (0.0, 0.0),
+        }
+    }
+
+    /// Compares matched `(treatment, control)` pairs.
+    pub fn compare_matched(&self, pairs: &[(P, P)]) -> HierarchyReport {
+        let mut report = self.empty_report();
+        for (treatment, control) in pairs {
+            report.record(self.compare(treatment, control));
+        }
+        report
+    }
+
+    /// Compares every treatment patient with every control patient.
+    pub fn compare_unmatched(&self, treatment: &[P], control: &[P]) -> HierarchyReport {
+        let mut report = self.empty_report();
+        for t in treatment {
+            for c in control {
+                report.record(self.compare(t, c));
+            }
+        }
+        report
+    }
+
+    fn empty_report(&self) -> HierarchyReport {
+        HierarchyReport {
+            tiers: self
+                .tiers
+                .iter()
+                .map(|t| TierCounts { name: t.name().to_string(), wins: 0, losses: 0 })
+                .collect(),
+            n_ties: 0,
+        }
+    }
+}
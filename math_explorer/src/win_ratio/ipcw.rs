@@ -0,0 +1,279 @@
+//! # Inverse-Probability-of-Censoring Weighted Win Ratio
+//!
+//! The sample win ratio compares each pair over its shared follow-up, so it estimates a
+//! quantity that depends on the censoring pattern, and it is biased when censoring differs
+//! between arms. The IPCW win ratio instead targets the win and loss probabilities of a
+//! comparison over a fixed horizon τ. Pairs whose comparison is observable are weighted by
+//! the inverse probability of both patients remaining uncensored, with the censoring
+//! distribution estimated by Kaplan–Meier separately in each arm.
+
+use crate::error::{self, Result};
+
+use super::nonparametric::KaplanMeier;
+use super::sample_win_ratio::PatientRecord;
+use super::unmatched::{
+    analyze_pairwise_probabilities, finkelstein_schoenfeld, finkelstein_schoenfeld_lenient, PairwiseProbabilities,
+    UnmatchedWinAnalysis,
+};
+
+/// Kaplan–Meier estimate of the censoring distribution G(t) = P(C > t) of one arm.
+///
+/// Censoring is the "event" and an observed death censors it. The follow-up of a patient
+/// ends at the earlier of death and censoring.
+pub fn censoring_distribution(patients: &[PatientRecord]) -> KaplanMeier {
+    let observations: Vec<(f64, bool)> = patients
+        .iter()
+        .map(|p| match p.fatal_time_within(p.censoring_time) {
+            Some(t) => (t, false),
+            None => (p.censoring_time, true),
+        })
+        .collect();
+    KaplanMeier::new(&observations)
+}
+
+/// IPCW win and loss scores of a pair over the horizon `tau`.
+///
+/// A fatal comparison decided at time D is observable when both patients are still under
+/// follow-up at D, which has probability G1(D-) G0(D-). A pair that reaches τ with neither
+/// death is compared on the first non-fatal event in [0, τ]; this requires both patients to
+/// be followed to τ, with probability G1(τ-) G0(τ-). Unobservable comparisons score zero.
+///
+/// Returns `(win, loss, time)`, where `time` is the time at which the weight was evaluated.
+fn ipcw_scores(
+    treatment: &PatientRecord,
+    control: &PatientRecord,
+    tau: f64,
+    treatment_censoring: &KaplanMeier,
+    control_censoring: &KaplanMeier,
+) -> (f64, f64, f64) {
+    let window = treatment.censoring_time.min(control.censoring_time).min(tau);
+    let weight = |time: f64| {
+        let probability = treatment_censoring.survival_before(time) * control_censoring.survival_before(time);
+        if probability > 0.0 { 1.0 / probability } else { 0.0 }
+    };
+
+    match (treatment.fatal_time_within(window), control.fatal_time_within(window)) {
+        (Some(t1), Some(t0)) if t1 < t0 => return (0.0, weight(t1), t1),
+        (Some(t1), Some(t0)) if t0 < t1 => return (weight(t0), 0.0, t0),
+        (Some(t1), None) => return (0.0, weight(t1), t1),
+        (None, Some(t0)) => return (weight(t0), 0.0, t0),
+        (Some(t1), Some(_)) => return (0.0, 0.0, t1),
+        (None, None) => {}
+    }
+
+    if window < tau {
+        return (0.0, 0.0, window);
+    }
+    let w = weight(tau);
+    match (treatment.non_fatal_time_within(tau), control.non_fatal_time_within(tau)) {
+        (Some(x1), Some(x0)) if x1 < x0 => (0.0, w, tau),
+        (Some(x1), Some(x0)) if x0 < x1 => (w, 0.0, tau),
+        (Some(_), None) => (0.0, w, tau),
+        (None, Some(_)) => (w, 0.0, tau),
+        _ => (0.0, 0.0, tau),
+    }
+}
+
+/// Result of an IPCW win ratio analysis, with the unweighted analysis for comparison.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IpcwWinRatio {
+    /// The horizon τ of the comparison.
+    pub horizon: f64,
+    /// The IPCW-adjusted win ratio, net benefit and win odds.
+    pub weighted: UnmatchedWinAnalysis,
+    /// The unweighted Finkelstein–Schoenfeld analysis over each pair's shared follow-up.
+    pub unweighted: UnmatchedWinAnalysis,
+}
+
+impl IpcwWinRatio {
+    /// Ratio of the weighted to the unweighted win ratio. Values far from one indicate that
+    /// the unweighted estimate is sensitive to the censoring pattern.
+    pub fn adjustment_ratio(&self) -> f64 {
+        self.weighted.win_ratio.estimate / self.unweighted.win_ratio.estimate
+    }
+}
+
+/// Calculates the IPCW-adjusted win ratio over the horizon `tau`.
+///
+/// ## Formula
+///
+/// W = 1 / (n1 n0) Σ_i Σ_j K_w(i, j),   L = 1 / (n1 n0) Σ_i Σ_j K_l(i, j)
+///
+/// where K_w and K_l are the win and loss indicators of a pair, divided by the estimated
+/// probability G1(D-) G0(D-) that the comparison at time D was observed. Under independent
+/// censoring within each arm, W and L estimate the win and loss probabilities over [0, τ]
+/// without censoring, as in `probability_win_ratio::model_probability_win_ratio`.
+///
+/// The variance is the sample variance of each patient's influence on (W, L). A patient's
+/// influence is its Hoeffding projection, the mean weighted score against the other arm,
+/// plus the effect of its censoring martingale on the Kaplan–Meier estimate of G in its arm
+/// (see `censoring_influence`), so the uncertainty in the estimated weights is included.
+///
+/// ## Parameters
+///
+/// * `treatment`: Patients in the treatment arm.
+/// * `control`: Patients in the control arm.
+/// * `tau`: The horizon; it should lie within the follow-up of both arms.
+/// * `confidence_level`: The confidence level of the intervals, e.g. `0.95`.
+///
+/// ## Returns
+///
+/// The weighted and unweighted analyses, or `Error::InvalidParameter` if `tau` is negative or
+/// not finite or `confidence_level` is outside [0, 1], `Error::EmptyData` if an arm is empty
+/// or no comparison is decided, `Error::NoLosses` if either win ratio is unbounded and
+/// `Error::NoWins` if either is zero.
+pub fn ipcw_win_ratio(
+    treatment: &[PatientRecord],
+    control: &[PatientRecord],
+    tau: f64,
+    confidence_level: f64,
+) -> Result<IpcwWinRatio> {
+    error::non_negative("tau", tau)?;
+    let unweighted = finkelstein_schoenfeld(treatment, control, confidence_level)?;
+    let probabilities = weighted_probabilities(treatment, control, tau);
+    probabilities.win_ratio()?;
+
+    Ok(IpcwWinRatio {
+        horizon: tau,
+        weighted: analyze_pairwise_probabilities(&probabilities, confidence_level),
+        unweighted,
+    })
+}
+
+/// Calculates the IPCW-adjusted win ratio, returning infinite or NaN estimates where the
+/// win ratio is unbounded or undefined.
+pub fn ipcw_win_ratio_lenient(
+    treatment: &[PatientRecord],
+    control: &[PatientRecord],
+    tau: f64,
+    confidence_level: f64,
+) -> IpcwWinRatio {
+    IpcwWinRatio {
+        horizon: tau,
+        weighted: analyze_pairwise_probabilities(&weighted_probabilities(treatment, control, tau), confidence_level),
+        unweighted: finkelstein_schoenfeld_lenient(treatment, control, confidence_level),
+    }
+}
+
+/// Influence of each patient on the weighted win and loss sums through the Kaplan–Meier
+/// estimate of the censoring distribution of its arm.
+///
+/// ## Formula
+///
+/// 1 / Ĝ(t-) - 1 / G(t-) ≈ (1 / G(t-)) Σ_k ∫_[0,t) dM_k(s) / Y(s)
+///
+/// where Y(s) is the number at risk, dN(s) the number censored at s, and
+/// dM_k(s) = dN_k(s) - 1{X_k >= s} dN(s) / Y(s) the censoring martingale of patient k with
+/// follow-up X_k. Summing over the decided pairs with A(s) = Σ_pairs K 1{D > s} gives
+///
+/// ψ_k = δ_k A(X_k) / Y(X_k) - Σ_{s <= X_k} A(s) dN(s) / Y(s)²
+///
+/// ## Parameters
+///
+/// * `patients`: Patients in one arm.
+/// * `decided`: The `(win, loss, time)` scores of every pair.
+fn censoring_influence(patients: &[PatientRecord], decided: &[(f64, f64, f64)]) -> Vec<(f64, f64)> {
+    let censoring = censoring_distribution(patients);
+
+    let mut sorted = decided.to_vec();
+    sorted.sort_by(|a, b| a.2.total_cmp(&b.2));
+    // suffix[i] holds the win and loss sums of the pairs from index i onwards.
+    let mut suffix = vec![(0.0, 0.0); sorted.len() + 1];
+    for i in (0..sorted.len()).rev() {
+        suffix[i] = (suffix[i + 1].0 + sorted[i].0, suffix[i + 1].1 + sorted[i].1);
+    }
+    let after = |s: f64| suffix[sorted.partition_point(|d| d.2 <= s)];
+
+    let mut compensator = Vec::with_capacity(censoring.times.len());
+    let mut total = (0.0, 0.0);
+    for ((&s, &y), &d) in censoring.times.iter().zip(&censoring.at_risk).zip(&censoring.events) {
+        let (win, loss) = after(s);
+        let scale = d as f64 / (y as f64 * y as f64);
+        total = (total.0 + win * scale, total.1 + loss * scale);
+        compensator.push(total);
+    }
+
+    patients
+        .iter()
+        .map(|p| {
+            let (follow_up, censored) = match p.fatal_time_within(p.censoring_time) {
+                Some(t) => (t, false),
+                None => (p.censoring_time, true),
+            };
+            let index = censoring.times.partition_point(|&s| s <= follow_up).checked_sub(1);
+            let (mut win, mut loss) = index.map_or((0.0, 0.0), |i| (-compensator[i].0, -compensator[i].1));
+            if let (true, Some(i)) = (censored, index) {
+                let (after_win, after_loss) = after(follow_up);
+                let y = censoring.at_risk[i] as f64;
+                win += after_win / y;
+                loss += after_loss / y;
+            }
+            (win, loss)
+        })
+        .collect()
+}
+
+/// The IPCW win and loss probabilities over the horizon `tau`, with the variance of the
+/// per-patient influence including the estimation of the censoring weights.
+fn weighted_probabilities(treatment: &[PatientRecord], control: &[PatientRecord], tau: f64) -> PairwiseProbabilities {
+    let treatment_censoring = censoring_distribution(treatment);
+    let control_censoring = censoring_distribution(control);
+    let n = treatment.len();
+    let m = control.len();
+
+    let mut treatment_influence = vec![(0.0, 0.0); n];
+    let mut control_influence = vec![(0.0, 0.0); m];
+    let mut decided = Vec::new();
+    for (i, t) in treatment.iter().enumerate() {
+        for (j, c) in control.iter().enumerate() {
+            let (win, loss, time) = ipcw_scores(t, c, tau, &treatment_censoring, &control_censoring);
+            if win == 0.0 && loss == 0.0 {
+                continue;
+            }
+            treatment_influence[i].0 += win / m as f64;
+            treatment_influence[i].1 += loss / m as f64;
+            control_influence[j].0 += win / n as f64;
+            control_influence[j].1 += loss / n as f64;
+            decided.push((win, loss, time));
+        }
+    }
+
+    let win_probability = treatment_influence.iter().map(|s| s.0).sum::<f64>() / n as f64;
+    let loss_probability = treatment_influence.iter().map(|s| s.1).sum::<f64>() / n as f64;
+
+    // The weights of a treatment patient's pairs are divided by n1 n0, so its censoring term
+    // enters its influence scaled by 1 / n0, and likewise 1 / n1 for a control patient.
+    for (influence, (win, loss)) in treatment_influence.iter_mut().zip(censoring_influence(treatment, &decided)) {
+        influence.0 += win / m as f64;
+        influence.1 += loss / m as f64;
+    }
+    for (influence, (win, loss)) in control_influence.iter_mut().zip(censoring_influence(control, &decided)) {
+        influence.0 += win / n as f64;
+        influence.1 += loss / n as f64;
+    }
+
+    // Sample covariance matrix of the per-patient influence, scaled by the arm size.
+    let arm_covariance = |influence: &[(f64, f64)]| {
+        let k = influence.len() as f64;
+        let denominator = (k - 1.0).max(1.0) * k;
+        let mean_win = influence.iter().map(|s| s.0).sum::<f64>() / k;
+        let mean_loss = influence.iter().map(|s| s.1).sum::<f64>() / k;
+        influence.iter().fold((0.0, 0.0, 0.0), |acc, &(w, l)| {
+            let dw = w - mean_win;
+            let dl = l - mean_loss;
+            (acc.0 + dw * dw / denominator, acc.1 + dl * dl / denominator, acc.2 + dw * dl / denominator)
+        })
+    };
+    let treatment_part = arm_covariance(&treatment_influence);
+    let control_part = arm_covariance(&control_influence);
+
+    PairwiseProbabilities {
+        n_treatment: n,
+        n_control: m,
+        win_probability,
+        loss_probability,
+        win_variance: treatment_part.0 + control_part.0,
+        loss_variance: treatment_part.1 + control_part.1,
+        covariance: treatment_part.2 + control_part.2,
+    }
+}
@@ -1,9 +1,28 @@
 //! # Win Ratio Analysis
 //!
-//! A collection of modules for performing win ratio analysis, including BMI calculation,
-//! sample win ratio, probability win ratio, and simulation studies.
+//! A collection of modules for performing win ratio analysis, including BMI and BMI-for-age,
+//! user-defined outcome hierarchies, sample win ratio, unmatched (all-pairs) and stratified
+//! win statistics, covariate-adjusted win ratio regression, recurrent events,
+//! inverse-probability-of-censoring weighting, nonparametric survival estimators,
+//! probability win ratio, time-dependent win ratio curves, group-sequential monitoring and
+//! simulation studies under a choice of copula models, with numerical checks of the model
+//! densities.
 
 pub mod bmi;
+pub mod copula;
+pub mod diagnostics;
+pub mod distributions;
+pub mod group_sequential;
+pub mod hierarchy;
+pub mod ipcw;
 pub mod sample_win_ratio;
+pub mod unmatched;
+pub mod stratified;
+pub mod resampling;
+pub mod nonparametric;
 pub mod probability_win_ratio;
+pub mod recurrent;
+pub mod regression;
+pub mod time_dependent;
 pub mod simulation;
+pub mod power;
@@ -0,0 +1,394 @@
+//! # Nonparametric Survival Estimators
+//!
+//! Kaplan–Meier and Nelson–Aalen estimators for right-censored data, and kernel-smoothed
+//! hazard and density estimates. The estimators return callable functions with the same
+//! shape as the analytic closures used by `probability_win_ratio`, so the probability win
+//! ratio can be estimated directly from patient records.
+
+use super::distributions::two_sided_critical_value;
+use crate::integration::{Method, Quadrature};
+
+use super::probability_win_ratio::{
+    calculate_loss_probability_with, calculate_probability_win_ratio_lenient, calculate_win_probability_with,
+    IntegratedProbabilityWinRatio, ProbabilityWinRatio,
+};
+use super::sample_win_ratio::PatientRecord;
+
+/// Number at risk and number of events at each distinct event time.
+fn risk_table(observations: &[(f64, bool)]) -> Vec<(f64, usize, usize)> {
+    let mut sorted = observations.to_vec();
+    sorted.sort_by(|a, b| a.0.partial_cmp(&b.0).expect("observation times must not be NaN"));
+
+    let mut table = Vec::new();
+    let mut at_risk = sorted.len();
+    let mut i = 0;
+    while i < sorted.len() {
+        let time = sorted[i].0;
+        let (mut events, mut removed) = (0, 0);
+        while i < sorted.len() && sorted[i].0 == time {
+            if sorted[i].1 {
+                events += 1;
+            }
+            removed += 1;
+            i += 1;
+        }
+        if events > 0 {
+            table.push((time, at_risk, events));
+        }
+        at_risk -= removed;
+    }
+    table
+}
+
+/// Index of the last event time at or before `t`, if any.
+fn step_index(times: &[f64], t: f64) -> Option<usize> {
+    times.partition_point(|&s| s <= t).checked_sub(1)
+}
+
+/// The Kaplan–Meier estimate of a survival function.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KaplanMeier {
+    /// Distinct event times, in increasing order.
+    pub times: Vec<f64>,
+    /// Number at risk just before each event time.
+    pub at_risk: Vec<usize>,
+    /// Number of events at each event time.
+    pub events: Vec<usize>,
+    /// Estimated survival just after each event time.
+    pub survival: Vec<f64>,
+    /// Greenwood variance of the estimate just after each event time.
+    pub variance: Vec<f64>,
+}
+
+impl KaplanMeier {
+    /// Fits the Kaplan–Meier estimator.
+    ///
+    /// ## Formula
+    ///
+    /// S(t) = Π_{t_i <= t} (1 - d_i / n_i)
+    ///
+    /// Var(S(t)) = S(t)² Σ_{t_i <= t} d_i / (n_i (n_i - d_i))   (Greenwood)
+    ///
+    /// ## Parameters
+    ///
+    /// * `observations`: `(time, event)` pairs, where `event` is `false` for a censored time.
+    pub fn new(observations: &[(f64, bool)]) -> Self {
+        let table = risk_table(observations);
+        let mut estimate = Self {
+            times: Vec::with_capacity(table.len()),
+            at_risk: Vec::with_capacity(table.len()),
+            events: Vec::with_capacity(table.len()),
+            survival: Vec::with_capacity(table.len()),
+            variance: Vec::with_capacity(table.len()),
+        };
+        let (mut survival, mut greenwood_sum) = (1.0, 0.0);
+        for (time, n, d) in table {
+            survival *= 1.0 - d as f64 / n as f64;
+            if n > d {
+                greenwood_sum += d as f64 / (n as f64 * (n - d) as f64);
+            }
+            estimate.times.push(time);
+            estimate.at_risk.push(n);
+            estimate.events.push(d);
+            estimate.survival.push(survival);
+            estimate.variance.push(survival * survival * greenwood_sum);
+        }
+        estimate
+    }
+
+    /// The estimated survival probability at time `t`, a right-continuous step function.
+    pub fn survival_at(&self, t: f64) -> f64 {
+        step_index(&self.times, t).map_or(1.0, |i| self.survival[i])
+    }
+
+    /// The left limit S(t-), the probability of surviving beyond all times before `t`.
+    pub fn survival_before(&self, t: f64) -> f64 {
+        self.times.partition_point(|&s| s < t).checked_sub(1).map_or(1.0, |i| self.survival[i])
+    }
+
+    /// The Greenwood variance of the estimate at time `t`.
+    pub fn variance_at(&self, t: f64) -> f64 {
+        step_index(&self.times, t).map_or(0.0, |i| self.variance[i])
+    }
+
+    /// Pointwise confidence interval for S(t) on the log(-log) scale, which stays inside [0, 1].
+    pub fn confidence_interval_at(&self, t: f64, confidence_level: f64) -> (f64, f64) {
+        let survival = self.survival_at(t);
+        if survival <= 0.0 || survival >= 1.0 {
+            return (survival, survival);
+        }
+        let log_survival = survival.ln();
+        let standard_error = self.variance_at(t).sqrt() / (survival * log_survival.abs());
+        let margin = two_sided_critical_value(confidence_level) * standard_error;
+        (survival.powf(margin.exp()), survival.powf((-margin).exp()))
+    }
+
+    /// The estimate as a closure, for use with `calculate_win_probability`.
+    pub fn survival_fn(&self) -> impl Fn(f64) -> f64 + '_ {
+        move |t| self.survival_at(t)
+    }
+}
+
+/// The Nelson–Aalen estimate of a cumulative hazard function.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NelsonAalen {
+    /// Distinct event times, in increasing order.
+    pub times: Vec<f64>,
+    /// Hazard increments d_i / n_i at each event time.
+    pub increments: Vec<f64>,
+    /// Estimated cumulative hazard just after each event time.
+    pub cumulative_hazard: Vec<f64>,
+    /// Variance of the estimate just after each event time, Σ d_i / n_i².
+    pub variance: Vec<f64>,
+}
+
+impl NelsonAalen {
+    /// Fits the Nelson–Aalen estimator, H(t) = Σ_{t_i <= t} d_i / n_i.
+    ///
+    /// ## Parameters
+    ///
+    /// * `observations`: `(time, event)` pairs, where `event` is `false` for a censored time.
+    pub fn new(observations: &[(f64, bool)]) -> Self {
+        let table = risk_table(observations);
+        let mut estimate = Self {
+            times: Vec::with_capacity(table.len()),
+            increments: Vec::with_capacity(table.len()),
+            cumulative_hazard: Vec::with_capacity(table.len()),
+            variance: Vec::with_capacity(table.len()),
+        };
+        let (mut hazard, mut variance) = (0.0, 0.0);
+        for (time, n, d) in table {
+            let increment = d as f64 / n as f64;
+            hazard += increment;
+            variance += d as f64 / (n as f64 * n as f64);
+            estimate.times.push(time);
+            estimate.increments.push(increment);
+            estimate.cumulative_hazard.push(hazard);
+            estimate.variance.push(variance);
+        }
+        estimate
+    }
+
+    /// The estimated cumulative hazard at time `t`.
+    pub fn cumulative_hazard_at(&self, t: f64) -> f64 {
+        step_index(&self.times, t).map_or(0.0, |i| self.cumulative_hazard[i])
+    }
+
+    /// The variance of the estimate at time `t`.
+    pub fn variance_at(&self, t: f64) -> f64 {
+        step_index(&self.times, t).map_or(0.0, |i| self.variance[i])
+    }
+
+    /// The Fleming–Harrington survival estimate, exp(-H(t)).
+    pub fn survival_at(&self, t: f64) -> f64 {
+        (-self.cumulative_hazard_at(t)).exp()
+    }
+
+    /// The cumulative hazard as a closure.
+    pub fn cumulative_hazard_fn(&self) -> impl Fn(f64) -> f64 + '_ {
+        move |t| self.cumulative_hazard_at(t)
+    }
+}
+
+/// The Epanechnikov kernel, K(u) = 3/4 (1 - u²) on [-1, 1].
+fn epanechnikov(u: f64) -> f64 {
+    if u.abs() <= 1.0 { 0.75 * (1.0 - u * u) } else { 0.0 }
+}
+
+/// A kernel-smoothed hazard estimate built from the Nelson–Aalen increments.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KernelHazard {
+    /// Distinct event times, in increasing order.
+    pub times: Vec<f64>,
+    /// Nelson–Aalen increments at each event time.
+    pub increments: Vec<f64>,
+    /// The kernel bandwidth b.
+    pub bandwidth: f64,
+}
+
+impl KernelHazard {
+    /// Smooths a Nelson–Aalen estimate.
+    ///
+    /// ## Formula
+    ///
+    /// h(t) = 1/b Σ K((t - t_i) / b) ΔH(t_i)
+    ///
+    /// where K is the Epanechnikov kernel. No boundary correction is applied, so the estimate
+    /// is biased downwards within one bandwidth of time zero.
+    pub fn new(nelson_aalen: &NelsonAalen, bandwidth: f64) -> Self {
+        Self { times: nelson_aalen.times.clone(), increments: nelson_aalen.increments.clone(), bandwidth }
+    }
+
+    /// The smoothed hazard at time `t`.
+    pub fn hazard_at(&self, t: f64) -> f64 {
+        let start = self.times.partition_point(|&s| s < t - self.bandwidth);
+        let end = self.times.partition_point(|&s| s <= t + self.bandwidth);
+        (start..end)
+            .map(|i| epanechnikov((t - self.times[i]) / self.bandwidth) * self.increments[i])
+            .sum::<f64>()
+            / self.bandwidth
+    }
+
+    /// The smoothed hazard as a closure.
+    pub fn hazard_fn(&self) -> impl Fn(f64) -> f64 + '_ {
+        move |t| self.hazard_at(t)
+    }
+}
+
+/// Kaplan–Meier survival together with a smoothed density, f(t) = h(t) S(t).
+#[derive(Debug, Clone, PartialEq)]
+pub struct SmoothedSurvival {
+    /// The Kaplan–Meier estimate of S(t).
+    pub kaplan_meier: KaplanMeier,
+    /// The kernel-smoothed hazard h(t).
+    pub hazard: KernelHazard,
+}
+
+impl SmoothedSurvival {
+    /// Fits the survival and smoothed density estimates.
+    ///
+    /// ## Parameters
+    ///
+    /// * `observations`: `(time, event)` pairs, where `event` is `false` for a censored time.
+    /// * `bandwidth`: The kernel bandwidth of the hazard estimate.
+    pub fn new(observations: &[(f64, bool)], bandwidth: f64) -> Self {
+        Self {
+            kaplan_meier: KaplanMeier::new(observations),
+            hazard: KernelHazard::new(&NelsonAalen::new(observations), bandwidth),
+        }
+    }
+
+    /// The Kaplan–Meier survival estimate at time `t`.
+    pub fn survival_at(&self, t: f64) -> f64 {
+        self.kaplan_meier.survival_at(t)
+    }
+
+    /// The smoothed density estimate at time `t`.
+    pub fn density_at(&self, t: f64) -> f64 {
+        self.hazard.hazard_at(t) * self.kaplan_meier.survival_at(t)
+    }
+
+    /// The survival estimate as a closure.
+    pub fn survival_fn(&self) -> impl Fn(f64) -> f64 + '_ {
+        move |t| self.survival_at(t)
+    }
+
+    /// The density estimate as a closure.
+    pub fn density_fn(&self) -> impl Fn(f64) -> f64 + '_ {
+        move |t| self.density_at(t)
+    }
+}
+
+/// Nonparametric estimates for one arm: T, and X given T > c.
+struct ArmEstimates {
+    fatal: SmoothedSurvival,
+    non_fatal_given_survival: SmoothedSurvival,
+}
+
+impl ArmEstimates {
+    fn new(patients: &[PatientRecord], c: f64, bandwidth: f64) -> Self {
+        let fatal: Vec<(f64, bool)> = patients
+            .iter()
+            .map(|p| match p.fatal_time_within(p.censoring_time) {
+                Some(t) => (t, true),
+                None => (p.censoring_time, false),
+            })
+            .collect();
+        // Patients known to be alive at c have X observed whenever X <= c.
+        let non_fatal: Vec<(f64, bool)> = patients
+            .iter()
+            .filter(|p| p.censoring_time >= c && p.fatal_time_within(c).is_none())
+            .map(|p| match p.non_fatal_time_within(c) {
+                Some(x) => (x, true),
+                None => (c, false),
+            })
+            .collect();
+        Self {
+            fatal: SmoothedSurvival::new(&fatal, bandwidth),
+            non_fatal_given_survival: SmoothedSurvival::new(&non_fatal, bandwidth),
+        }
+    }
+}
+
+/// Estimates the probability win ratio at follow-up time `c` from censored patient data.
+///
+/// The analytic closures of `calculate_win_probability` and `calculate_loss_probability` are
+/// replaced by nonparametric estimates: Kaplan–Meier for S0 and S1, kernel-smoothed densities
+/// for f0 and f1, and the same estimators for X among the patients known to be alive at c for
+/// G(x|c) and f(x|c). Censoring is assumed to be independent of the event times.
+///
+/// The Kaplan–Meier factors are step functions, so the integrands jump at every observed
+/// time. The integrals use adaptive Gauss–Kronrod quadrature, which subdivides around the
+/// jumps; use `empirical_probability_win_ratio_with` for its error estimates.
+///
+/// ## Parameters
+///
+/// * `treatment`: Patients in the treatment arm.
+/// * `control`: Patients in the control arm.
+/// * `c`: The follow-up time.
+/// * `bandwidth`: The kernel bandwidth of the density estimates.
+/// * `error_tolerance`: The desired error tolerance for numerical integration.
+pub fn empirical_probability_win_ratio(
+    treatment: &[PatientRecord],
+    control: &[PatientRecord],
+    c: f64,
+    bandwidth: f64,
+    error_tolerance: f64,
+) -> ProbabilityWinRatio {
+    let quadrature = Quadrature::new(Method::GaussKronrod, error_tolerance);
+    empirical_probability_win_ratio_with(treatment, control, c, bandwidth, &quadrature).probabilities
+}
+
+/// Estimates the probability win ratio from censored patient data with a chosen quadrature rule.
+///
+/// See `empirical_probability_win_ratio` for the estimator and parameters.
+///
+/// ## Returns
+///
+/// The estimated W(c), L(c) and PR(c), with the error estimates and evaluation counts of both
+/// integrals.
+pub fn empirical_probability_win_ratio_with(
+    treatment: &[PatientRecord],
+    control: &[PatientRecord],
+    c: f64,
+    bandwidth: f64,
+    quadrature: &Quadrature,
+) -> IntegratedProbabilityWinRatio {
+    let treatment_estimates = ArmEstimates::new(treatment, c, bandwidth);
+    let control_estimates = ArmEstimates::new(control, c, bandwidth);
+    let s0_at_c = control_estimates.fatal.survival_at(c);
+    let s1_at_c = treatment_estimates.fatal.survival_at(c);
+
+    let win_integration = calculate_win_probability_with(
+        treatment_estimates.fatal.survival_fn(),
+        control_estimates.fatal.density_fn(),
+        treatment_estimates.non_fatal_given_survival.survival_fn(),
+        control_estimates.non_fatal_given_survival.density_fn(),
+        s0_at_c,
+        s1_at_c,
+        c,
+        quadrature,
+    );
+    let loss_integration = calculate_loss_probability_with(
+        control_estimates.fatal.survival_fn(),
+        treatment_estimates.fatal.density_fn(),
+        control_estimates.non_fatal_given_survival.survival_fn(),
+        treatment_estimates.non_fatal_given_survival.density_fn(),
+        s0_at_c,
+        s1_at_c,
+        c,
+        quadrature,
+    );
+
+    let (win_probability, loss_probability) = (win_integration.integral, loss_integration.integral);
+    IntegratedProbabilityWinRatio {
+        probabilities: ProbabilityWinRatio {
+            follow_up: c,
+            win_probability,
+            loss_probability,
+            win_ratio: calculate_probability_win_ratio_lenient(win_probability, loss_probability),
+        },
+        win_integration,
+        loss_integration,
+    }
+}
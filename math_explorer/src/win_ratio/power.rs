@@ -0,0 +1,219 @@
+//! # Power and Sample Size
+//!
+//! Analytic sample-size formulas and simulation-based power curves for trials analysed
+//! with the win ratio. The analytic formulas take the win ratio and the probability of a
+//! tie, which can be computed from a `SimulationParams` model with the win-probability
+//! integrals; the simulated power uses the Monte Carlo trial simulator.
+
+use rand::Rng;
+
+use crate::error::Error;
+
+use super::distributions::{standard_normal_cdf, standard_normal_quantile};
+use super::probability_win_ratio::model_probability_win_ratio;
+use super::sample_win_ratio::{
+    calculate_p_value, calculate_significance_test_statistic, count_matched_pairs, ConfidenceIntervalMethod, WinLossCounts,
+};
+use super::simulation::{simulate_trial_lenient, CensoringDesign, SimulationParams};
+use super::unmatched::finkelstein_schoenfeld_lenient;
+
+/// The test used to analyse the trial.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WinRatioTest {
+    /// The matched-pairs significance test, `calculate_significance_test_statistic`, with the
+    /// i-th treatment patient paired to the i-th control patient. Requires equal allocation.
+    MatchedPairs,
+    /// The Finkelstein–Schoenfeld all-pairs test of log(WR) with U-statistic variance.
+    Unmatched,
+}
+
+/// Calculates the total sample size for the unmatched win ratio test (Yu and Ganju, 2022).
+///
+/// ## Formula
+///
+/// N = 4 * (1 + p_tie) * (z_{1-α/2} + z_{1-β})² / (3 * k * (1 - k) * (1 - p_tie) * (log WR)²)
+///
+/// ## Parameters
+///
+/// * `win_ratio`: The win ratio under the alternative hypothesis.
+/// * `tie_probability`: The probability that a treatment-control pair is tied.
+/// * `allocation`: The proportion k of patients randomised to treatment, e.g. `0.5`.
+/// * `alpha`: The two-sided significance level.
+/// * `power`: The target power, 1 - β.
+///
+/// ## Returns
+///
+/// The total number of patients in both arms, rounded up.
+pub fn unmatched_sample_size(win_ratio: f64, tie_probability: f64, allocation: f64, alpha: f64, power: f64) -> usize {
+    let z = standard_normal_quantile(1.0 - alpha / 2.0) + standard_normal_quantile(power);
+    let log_ratio = win_ratio.ln();
+    let n = 4.0 * (1.0 + tie_probability) * z * z
+        / (3.0 * allocation * (1.0 - allocation) * (1.0 - tie_probability) * log_ratio * log_ratio);
+    n.ceil() as usize
+}
+
+/// Calculates the power of the unmatched win ratio test for a total sample size.
+///
+/// This is the inverse of `unmatched_sample_size`.
+pub fn unmatched_power(win_ratio: f64, tie_probability: f64, allocation: f64, alpha: f64, total_sample_size: usize) -> f64 {
+    let information = 3.0 * allocation * (1.0 - allocation) * (1.0 - tie_probability) * total_sample_size as f64
+        / (4.0 * (1.0 + tie_probability));
+    standard_normal_cdf(win_ratio.ln().abs() * information.sqrt() - standard_normal_quantile(1.0 - alpha / 2.0))
+}
+
+/// Calculates the total sample size for the matched-pairs test.
+///
+/// ## Formula
+///
+/// With pw = WR / (1 + WR), the number of non-tied pairs needed is
+///
+/// D = (z_{1-α/2} + z_{1-β})² * pw * (1 - pw) / (pw - 0.5)²
+///
+/// and the number of pairs is D / (1 - p_tie). Each pair has two patients.
+///
+/// ## Parameters
+///
+/// * `win_ratio`: The win ratio under the alternative hypothesis.
+/// * `tie_probability`: The probability that a pair is tied.
+/// * `alpha`: The two-sided significance level.
+/// * `power`: The target power, 1 - β.
+///
+/// ## Returns
+///
+/// The total number of patients in both arms, rounded up to a whole number of pairs.
+pub fn matched_pairs_sample_size(win_ratio: f64, tie_probability: f64, alpha: f64, power: f64) -> usize {
+    let z = standard_normal_quantile(1.0 - alpha / 2.0) + standard_normal_quantile(power);
+    let p_w = win_ratio / (1.0 + win_ratio);
+    let decided_pairs = z * z * p_w * (1.0 - p_w) / ((p_w - 0.5) * (p_w - 0.5));
+    2 * (decided_pairs / (1.0 - tie_probability)).ceil() as usize
+}
+
+/// Calculates the power of the matched-pairs test for a total sample size.
+///
+/// This is the inverse of `matched_pairs_sample_size`.
+pub fn matched_pairs_power(win_ratio: f64, tie_probability: f64, alpha: f64, total_sample_size: usize) -> f64 {
+    let p_w = win_ratio / (1.0 + win_ratio);
+    let decided_pairs = (total_sample_size / 2) as f64 * (1.0 - tie_probability);
+    let shift = (p_w - 0.5).abs() * (decided_pairs / (p_w * (1.0 - p_w))).sqrt();
+    standard_normal_cdf(shift - standard_normal_quantile(1.0 - alpha / 2.0))
+}
+
+/// Calculates the total sample size for a trial under the simulation model.
+///
+/// The win ratio and tie probability are computed from the win and loss probabilities
+/// W(c) and L(c), with administrative censoring at the follow-up time `c`.
+///
+/// ## Parameters
+///
+/// * `params`: The joint survival model under the alternative hypothesis.
+/// * `c`: The follow-up time.
+/// * `test`: The test used to analyse the trial. Equal allocation is assumed.
+/// * `alpha`: The two-sided significance level.
+/// * `power`: The target power, 1 - β.
+pub fn sample_size_for_model(params: &SimulationParams, c: f64, test: WinRatioTest, alpha: f64, power: f64) -> usize {
+    let model = model_probability_win_ratio(params, c, 1e-8);
+    let tie = model.tie_probability();
+    match test {
+        WinRatioTest::MatchedPairs => matched_pairs_sample_size(model.win_ratio, tie, alpha, power),
+        WinRatioTest::Unmatched => unmatched_sample_size(model.win_ratio, tie, 0.5, alpha, power),
+    }
+}
+
+/// A point on a power curve.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PowerCurvePoint {
+    /// The treatment effect θ.
+    pub theta: f64,
+    /// The total number of patients in both arms.
+    pub total_sample_size: usize,
+    /// The model-based win ratio W(c) / L(c).
+    pub win_ratio: f64,
+    /// Power from the analytic formula for the chosen test.
+    pub analytic_power: f64,
+    /// Proportion of simulated trials in which the test rejected the null hypothesis.
+    pub simulated_power: f64,
+}
+
+/// Whether the matched-pairs test rejects the null hypothesis. When every decided pair goes
+/// the same way the Wald variance is zero, and the exact binomial test decides instead.
+fn matched_pairs_rejects(counts: &WinLossCounts, alpha: f64, critical_value: f64) -> bool {
+    let (n_w, n_l) = (counts.n_wins(), counts.n_losses());
+    match calculate_significance_test_statistic(n_w, n_l) {
+        Ok(z) => z.abs() > critical_value,
+        Err(Error::ZeroVariance) => {
+            calculate_p_value(n_w, n_l, ConfidenceIntervalMethod::ClopperPearson).is_ok_and(|p| p < alpha)
+        }
+        Err(_) => false,
+    }
+}
+
+/// Simulates a power curve across sample sizes and treatment effects.
+///
+/// For each θ and each total sample size, `n_replicates` trials with equal allocation are
+/// simulated from `params` with θ replaced, and analysed with the chosen test. A matched-pairs
+/// trial in which every decided pair is a win, or every one a loss, is analysed with the exact
+/// binomial test, as the Wald statistic is undefined.
+///
+/// ## Parameters
+///
+/// * `params`: The joint survival model; its θ is replaced by each value of `thetas`.
+/// * `thetas`: The treatment effects to evaluate.
+/// * `sample_sizes`: The total sample sizes to evaluate.
+/// * `design`: The censoring mechanism of the simulated trials.
+/// * `test`: The test used to analyse each trial.
+/// * `alpha`: The two-sided significance level.
+/// * `n_replicates`: Number of simulated trials per point.
+/// * `rng`: The random number generator; seed it for reproducible results.
+#[allow(clippy::too_many_arguments)]
+pub fn simulate_power_curve<R: Rng + ?Sized>(
+    params: &SimulationParams,
+    thetas: &[f64],
+    sample_sizes: &[usize],
+    design: &CensoringDesign,
+    test: WinRatioTest,
+    alpha: f64,
+    n_replicates: usize,
+    rng: &mut R,
+) -> Vec<PowerCurvePoint> {
+    let critical_value = standard_normal_quantile(1.0 - alpha / 2.0);
+    let mut curve = Vec::with_capacity(thetas.len() * sample_sizes.len());
+
+    for &theta in thetas {
+        let model = SimulationParams { theta, ..*params };
+        let probabilities = model_probability_win_ratio(&model, design.follow_up, 1e-8);
+        let win_ratio = probabilities.win_ratio;
+        let tie = probabilities.tie_probability();
+
+        for &total_sample_size in sample_sizes {
+            let n_per_arm = total_sample_size / 2;
+            let mut rejections = 0;
+            for _ in 0..n_replicates {
+                let trial = simulate_trial_lenient(&model, design, n_per_arm, n_per_arm, rng);
+                let rejected = match test {
+                    WinRatioTest::MatchedPairs => {
+                        matched_pairs_rejects(&count_matched_pairs(&trial.matched_pairs()).counts, alpha, critical_value)
+                    }
+                    WinRatioTest::Unmatched => {
+                        finkelstein_schoenfeld_lenient(&trial.treatment, &trial.control, 1.0 - alpha).win_ratio.p_value < alpha
+                    }
+                };
+                if rejected {
+                    rejections += 1;
+                }
+            }
+
+            let analytic_power = match test {
+                WinRatioTest::MatchedPairs => matched_pairs_power(win_ratio, tie, alpha, total_sample_size),
+                WinRatioTest::Unmatched => unmatched_power(win_ratio, tie, 0.5, alpha, total_sample_size),
+            };
+            curve.push(PowerCurvePoint {
+                theta,
+                total_sample_size,
+                win_ratio,
+                analytic_power,
+                simulated_power: rejections as f64 / n_replicates as f64,
+            });
+        }
+    }
+    curve
+}
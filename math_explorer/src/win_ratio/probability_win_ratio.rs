@@ -2,15 +2,22 @@
 //!
 //! Functions to calculate the probability win ratio, a parameter that extends the concept
 //! of the sample win ratio. This module uses numerical integration to calculate the
-//! win and loss probabilities over a specified time interval.
+//! win and loss probabilities over a specified time interval. The quadrature rule can be
+//! chosen per call with the `_with` variants, which also accept an infinite follow-up.
 
-use quadrature::clenshaw_curtis::integrate;
+use crate::error::{positive, probability, Error, Result};
+use crate::integration::{IntegrationResult, Method, Quadrature};
+
+use super::simulation::{win_ratio_parameter, Arm, JointSurvivalModel, SimulationParams};
 
 /// Calculates the win probability, W(c).
 ///
 /// ## Formula
 ///
-/// W(c) = integral from 0 to c of S1(t)*f0(t)dt - S1(c)*S0(c) * integral from 0 to c of G1(x|c)*f0(x|c)dx
+/// W(c) = integral from 0 to c of S1(t)*f0(t)dt + S1(c)*S0(c) * integral from 0 to c of G1(x|c)*f0(x|c)dx
+///
+/// This is the paper's W(c) = -∫S1 dS0 - S1(c)S0(c)∫G1 dG0 with the Stieltjes integrals written
+/// in terms of densities (dS0 = -f0 dt and dG0 = -f0(x|c) dx), so both terms are added.
 ///
 /// where:
 /// - S1(t): Marginal survival function for the treatment group.
@@ -29,6 +36,7 @@ use quadrature::clenshaw_curtis::integrate;
 /// * `s1_at_c`: The value of S1(c).
 /// * `c`: The time interval [0, c].
 /// * `error_tolerance`: The desired error tolerance for numerical integration.
+#[allow(clippy::too_many_arguments)]
 pub fn calculate_win_probability<FS1, FPDFT0, FG1, FPDFX0>(
     s1: FS1,
     pdf_t0: FPDFT0,
@@ -45,23 +53,52 @@ where
     FG1: Fn(f64) -> f64,
     FPDFX0: Fn(f64) -> f64,
 {
-    let integrand1 = |t: f64| s1(t) * pdf_t0(t);
-    let integral1 = integrate(integrand1, 0.0, c, error_tolerance).integral;
-
-    let integrand2 = |x: f64| g1_given_c(x) * pdf_x0_given_c(x);
-    let integral2 = integrate(integrand2, 0.0, c, error_tolerance).integral;
+    let quadrature = Quadrature::new(Method::ClenshawCurtis, error_tolerance);
+    calculate_win_probability_with(s1, pdf_t0, g1_given_c, pdf_x0_given_c, s0_at_c, s1_at_c, c, &quadrature).integral
+}
 
-    integral1 - s0_at_c * s1_at_c * integral2
+/// Calculates the win probability, W(c), with a chosen quadrature rule.
+///
+/// See `calculate_win_probability` for the formula and parameters. The follow-up `c` may be
+/// `f64::INFINITY`; the second term is skipped when S0(c) * S1(c) is zero.
+///
+/// ## Returns
+///
+/// An `IntegrationResult` whose error estimate and evaluation count combine both integrals.
+#[allow(clippy::too_many_arguments)]
+pub fn calculate_win_probability_with<FS1, FPDFT0, FG1, FPDFX0>(
+    s1: FS1,
+    pdf_t0: FPDFT0,
+    g1_given_c: FG1,
+    pdf_x0_given_c: FPDFX0,
+    s0_at_c: f64,
+    s1_at_c: f64,
+    c: f64,
+    quadrature: &Quadrature,
+) -> IntegrationResult
+where
+    FS1: Fn(f64) -> f64,
+    FPDFT0: Fn(f64) -> f64,
+    FG1: Fn(f64) -> f64,
+    FPDFX0: Fn(f64) -> f64,
+{
+    combine(
+        quadrature,
+        quadrature.integrate(|t: f64| s1(t) * pdf_t0(t), 0.0, c),
+        s0_at_c * s1_at_c,
+        |q| q.integrate(|x: f64| g1_given_c(x) * pdf_x0_given_c(x), 0.0, c),
+    )
 }
 
 /// Calculates the loss probability, L(c).
 ///
 /// ## Formula
 ///
-/// L(c) = integral from 0 to c of S0(t)*f1(t)dt - S1(c)*S0(c) * integral from 0 to c of G0(x|c)*f1(x|c)dx
+/// L(c) = integral from 0 to c of S0(t)*f1(t)dt + S1(c)*S0(c) * integral from 0 to c of G0(x|c)*f1(x|c)dx
 ///
 /// ## Parameters
 /// (See `calculate_win_probability` for details, with roles of group 0 and 1 swapped)
+#[allow(clippy::too_many_arguments)]
 pub fn calculate_loss_probability<FS0, FPDFT1, FG0, FPDFX1>(
     s0: FS0,
     pdf_t1: FPDFT1,
@@ -78,13 +115,56 @@ where
     FG0: Fn(f64) -> f64,
     FPDFX1: Fn(f64) -> f64,
 {
-    let integrand1 = |t: f64| s0(t) * pdf_t1(t);
-    let integral1 = integrate(integrand1, 0.0, c, error_tolerance).integral;
+    let quadrature = Quadrature::new(Method::ClenshawCurtis, error_tolerance);
+    calculate_loss_probability_with(s0, pdf_t1, g0_given_c, pdf_x1_given_c, s0_at_c, s1_at_c, c, &quadrature).integral
+}
 
-    let integrand2 = |x: f64| g0_given_c(x) * pdf_x1_given_c(x);
-    let integral2 = integrate(integrand2, 0.0, c, error_tolerance).integral;
+/// Calculates the loss probability, L(c), with a chosen quadrature rule.
+///
+/// See `calculate_win_probability_with`, with the roles of group 0 and 1 swapped.
+#[allow(clippy::too_many_arguments)]
+pub fn calculate_loss_probability_with<FS0, FPDFT1, FG0, FPDFX1>(
+    s0: FS0,
+    pdf_t1: FPDFT1,
+    g0_given_c: FG0,
+    pdf_x1_given_c: FPDFX1,
+    s0_at_c: f64,
+    s1_at_c: f64,
+    c: f64,
+    quadrature: &Quadrature,
+) -> IntegrationResult
+where
+    FS0: Fn(f64) -> f64,
+    FPDFT1: Fn(f64) -> f64,
+    FG0: Fn(f64) -> f64,
+    FPDFX1: Fn(f64) -> f64,
+{
+    combine(
+        quadrature,
+        quadrature.integrate(|t: f64| s0(t) * pdf_t1(t), 0.0, c),
+        s0_at_c * s1_at_c,
+        |q| q.integrate(|x: f64| g0_given_c(x) * pdf_x1_given_c(x), 0.0, c),
+    )
+}
 
-    integral1 - s0_at_c * s1_at_c * integral2
+/// Adds `weight` times the second integral to the first, skipping it when the weight is zero.
+fn combine(
+    quadrature: &Quadrature,
+    first: IntegrationResult,
+    weight: f64,
+    second: impl FnOnce(&Quadrature) -> IntegrationResult,
+) -> IntegrationResult {
+    if weight == 0.0 {
+        return first;
+    }
+    let second = second(quadrature);
+    let error_estimate = first.error_estimate + weight * second.error_estimate;
+    IntegrationResult {
+        integral: first.integral + weight * second.integral,
+        error_estimate,
+        evaluations: first.evaluations + second.evaluations,
+        converged: first.converged && second.converged,
+    }
 }
 
 /// Calculates the probability win ratio, PR(c).
@@ -95,10 +175,181 @@ where
 ///
 /// ## Returns
 ///
-/// The probability win ratio as a `f64`. Returns `f64::INFINITY` if the loss probability is zero.
-pub fn calculate_probability_win_ratio(win_probability: f64, loss_probability: f64) -> f64 {
+/// The probability win ratio as a `f64`. Returns `Error::InvalidParameter` if either
+/// probability is outside [0, 1], and `Error::NoLosses` if the loss probability is zero.
+pub fn calculate_probability_win_ratio(win_probability: f64, loss_probability: f64) -> Result<f64> {
+    let win_probability = probability("win_probability", win_probability)?;
+    let loss_probability = probability("loss_probability", loss_probability)?;
+    if loss_probability == 0.0 {
+        return Err(Error::NoLosses);
+    }
+    Ok(win_probability / loss_probability)
+}
+
+/// Calculates the probability win ratio, returning `f64::INFINITY` if the loss probability
+/// is zero.
+///
+/// See `calculate_probability_win_ratio` for the formula.
+pub fn calculate_probability_win_ratio_lenient(win_probability: f64, loss_probability: f64) -> f64 {
     if loss_probability == 0.0 {
         return f64::INFINITY;
     }
     win_probability / loss_probability
 }
+
+/// Win and loss probabilities at a follow-up time, with the resulting probability win ratio.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProbabilityWinRatio {
+    /// The follow-up time c.
+    pub follow_up: f64,
+    /// The win probability, W(c).
+    pub win_probability: f64,
+    /// The loss probability, L(c).
+    pub loss_probability: f64,
+    /// The probability win ratio, PR(c) = W(c) / L(c).
+    pub win_ratio: f64,
+}
+
+impl ProbabilityWinRatio {
+    /// The probability that a pair is tied, 1 - W(c) - L(c).
+    pub fn tie_probability(&self) -> f64 {
+        1.0 - self.win_probability - self.loss_probability
+    }
+}
+
+/// Calculates the probability win ratio at follow-up time `c` under a joint survival model.
+///
+/// The survival functions and densities of the model are wired into
+/// `calculate_win_probability` and `calculate_loss_probability`, so the caller only
+/// supplies the model.
+///
+/// As c grows, the tie probability S0(c) * S1(c) vanishes. For `SimulationParams`, PR(c)
+/// then approaches the uncensored win ratio parameter, `simulation::win_ratio_parameter`.
+///
+/// ## Parameters
+///
+/// * `model`: The joint survival model, e.g. `SimulationParams` or a `copula::CopulaModel`.
+/// * `c`: The follow-up time.
+/// * `error_tolerance`: The desired error tolerance for numerical integration.
+///
+/// ## Returns
+///
+/// A `ProbabilityWinRatio` with W(c), L(c) and PR(c).
+pub fn model_probability_win_ratio<M: JointSurvivalModel>(model: &M, c: f64, error_tolerance: f64) -> ProbabilityWinRatio {
+    model_probability_win_ratio_with(model, c, &Quadrature::new(Method::ClenshawCurtis, error_tolerance)).probabilities
+}
+
+/// A probability win ratio with the quadrature results of W(c) and L(c), from a model or
+/// from data.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IntegratedProbabilityWinRatio {
+    /// W(c), L(c) and PR(c).
+    pub probabilities: ProbabilityWinRatio,
+    /// The integration of the win probability.
+    pub win_integration: IntegrationResult,
+    /// The integration of the loss probability.
+    pub loss_integration: IntegrationResult,
+}
+
+impl IntegratedProbabilityWinRatio {
+    /// The sum of the error estimates of W(c) and L(c).
+    pub fn error_estimate(&self) -> f64 {
+        self.win_integration.error_estimate + self.loss_integration.error_estimate
+    }
+
+    /// The total number of integrand evaluations.
+    pub fn evaluations(&self) -> usize {
+        self.win_integration.evaluations + self.loss_integration.evaluations
+    }
+
+    /// Whether both integrals met the tolerance.
+    pub fn converged(&self) -> bool {
+        self.win_integration.converged && self.loss_integration.converged
+    }
+}
+
+/// Calculates the probability win ratio under a joint survival model with a chosen quadrature rule.
+///
+/// See `model_probability_win_ratio`. With `c = f64::INFINITY` the tie probability is zero
+/// and only the fatal event decides, so PR(∞) is the uncensored win ratio on T. Tanh-sinh
+/// quadrature suits the t^(α-1) singularity of the Weibull densities at t = 0 when α < 1.
+///
+/// ## Parameters
+///
+/// * `model`: The joint survival model.
+/// * `c`: The follow-up time, possibly infinite.
+/// * `quadrature`: The quadrature rule and tolerance.
+///
+/// ## Returns
+///
+/// The `ProbabilityWinRatio` with the error estimates and evaluation counts of both integrals.
+pub fn model_probability_win_ratio_with<M: JointSurvivalModel>(
+    model: &M,
+    c: f64,
+    quadrature: &Quadrature,
+) -> IntegratedProbabilityWinRatio {
+    let s0_at_c = model.marginal_survival_t(Arm::Control, c);
+    let s1_at_c = model.marginal_survival_t(Arm::Treatment, c);
+
+    let win_integration = calculate_win_probability_with(
+        |t| model.marginal_survival_t(Arm::Treatment, t),
+        |t| model.pdf_t(Arm::Control, t),
+        |x| model.conditional_survival_x_given_t(Arm::Treatment, x, c),
+        |x| model.pdf_x_given_t(Arm::Control, x, c),
+        s0_at_c,
+        s1_at_c,
+        c,
+        quadrature,
+    );
+    let loss_integration = calculate_loss_probability_with(
+        |t| model.marginal_survival_t(Arm::Control, t),
+        |t| model.pdf_t(Arm::Treatment, t),
+        |x| model.conditional_survival_x_given_t(Arm::Control, x, c),
+        |x| model.pdf_x_given_t(Arm::Treatment, x, c),
+        s0_at_c,
+        s1_at_c,
+        c,
+        quadrature,
+    );
+
+    let (win_probability, loss_probability) = (win_integration.integral, loss_integration.integral);
+
+    IntegratedProbabilityWinRatio {
+        probabilities: ProbabilityWinRatio {
+            follow_up: c,
+            win_probability,
+            loss_probability,
+            win_ratio: calculate_probability_win_ratio_lenient(win_probability, loss_probability),
+        },
+        win_integration,
+        loss_integration,
+    }
+}
+
+/// Checks that the probability win ratio reaches the win ratio parameter without censoring.
+///
+/// ## Formula
+///
+/// Without censoring only the fatal event decides a pair, so PR(∞) = W(∞) / L(∞) must equal
+/// the closed form PR_W = 1 / θ^α of `simulation::win_ratio_parameter`. The integrals run to
+/// infinity with tanh-sinh quadrature, which handles the t^(α-1) singularity when α < 1.
+///
+/// ## Parameters
+///
+/// * `params`: The joint survival model.
+/// * `tolerance`: The error tolerance of the quadrature.
+///
+/// ## Returns
+///
+/// The absolute discrepancy |PR(∞) - PR_W|, `Error::InvalidParameter` if the model or the
+/// tolerance is invalid, or `Error::NoConvergence` with the number of integrand evaluations if
+/// either integral did not meet the tolerance.
+pub fn no_censoring_consistency(params: &SimulationParams, tolerance: f64) -> Result<f64> {
+    params.validate()?;
+    positive("tolerance", tolerance)?;
+    let result = model_probability_win_ratio_with(params, f64::INFINITY, &Quadrature::new(Method::TanhSinh, tolerance));
+    if !result.converged() {
+        return Err(Error::NoConvergence { iterations: result.evaluations() });
+    }
+    Ok((result.probabilities.win_ratio - win_ratio_parameter(params)).abs())
+}
@@ -0,0 +1,275 @@
+//! # Recurrent Events
+//!
+//! Win ratio comparisons when the non-fatal outcome can occur repeatedly, such as
+//! heart-failure hospitalizations. A pair is compared first on death within the shared
+//! follow-up and then on the number of recurrent events in that window, where fewer events
+//! is better. The module also provides a simulator that extends `SimulationParams` with
+//! gamma-frailty recurrent event processes.
+
+use rand::Rng;
+
+use crate::error::{self, Error, Result};
+
+use super::distributions::{sample_gamma, sample_standard_exponential};
+use super::hierarchy::{compare_with_margin, Direction, OutcomeHierarchy, OutcomeTier, TierOutcome, TimeToEventTier};
+use super::simulation::{Arm, CensoringDesign, JointSurvivalModel, SimulationParams};
+use super::unmatched::{
+    analyze_pairwise_probabilities, pairwise_probabilities_with, pairwise_probabilities_with_lenient, PairwiseProbabilities,
+    UnmatchedWinAnalysis,
+};
+
+/// Follow-up of a patient with a fatal event and a recurrent non-fatal event.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecurrentEventHistory {
+    /// Time of death, or `None` if the patient was censored alive.
+    pub death_time: Option<f64>,
+    /// Times of the recurrent non-fatal events, in increasing order.
+    pub event_times: Vec<f64>,
+    /// End of follow-up: the earlier of death and censoring, so a patient who died is not at
+    /// risk of further events.
+    pub censoring_time: f64,
+}
+
+impl RecurrentEventHistory {
+    /// Creates a new `RecurrentEventHistory`. The event times are sorted.
+    ///
+    /// Returns `Error::InvalidParameter` if an event time is NaN.
+    pub fn new(death_time: Option<f64>, mut event_times: Vec<f64>, censoring_time: f64) -> Result<Self> {
+        if let Some(&value) = event_times.iter().find(|e| e.is_nan()) {
+            return Err(Error::InvalidParameter { name: "event_times", value });
+        }
+        event_times.sort_by(f64::total_cmp);
+        Ok(Self { death_time, event_times, censoring_time })
+    }
+
+    /// Time of death if it was observed within `[0, window]`.
+    pub fn death_time_within(&self, window: f64) -> Option<f64> {
+        self.death_time.filter(|&t| t <= window && t <= self.censoring_time)
+    }
+
+    /// Number of recurrent events observed within `[0, window]`.
+    pub fn events_within(&self, window: f64) -> usize {
+        let end = window.min(self.censoring_time);
+        self.event_times.iter().take_while(|&&e| e <= end).count()
+    }
+}
+
+/// A tier comparing the number of recurrent events within the shared follow-up.
+///
+/// The window is the shorter of the two follow-up times, so both patients are counted over the
+/// same period. The tier does not look at `death_time`: each patient's `censoring_time` must
+/// already be the end of follow-up including death, so that events are only counted while
+/// both patients are alive and observed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RecurrentEventTier;
+
+impl OutcomeTier<RecurrentEventHistory> for RecurrentEventTier {
+    fn name(&self) -> &str {
+        "recurrent events"
+    }
+
+    fn compare(&self, treatment: &RecurrentEventHistory, control: &RecurrentEventHistory) -> TierOutcome {
+        let window = treatment.censoring_time.min(control.censoring_time);
+        let difference = treatment.events_within(window) as f64 - control.events_within(window) as f64;
+        compare_with_margin(difference, 0.0, Direction::LowerIsBetter)
+    }
+}
+
+/// The two-tier hierarchy for recurrent events: death, then the number of recurrent events.
+pub fn recurrent_hierarchy() -> OutcomeHierarchy<RecurrentEventHistory> {
+    OutcomeHierarchy::new()
+        .with_tier(TimeToEventTier::new(
+            "death",
+            |p: &RecurrentEventHistory| p.death_time,
+            |p: &RecurrentEventHistory| p.censoring_time,
+        ))
+        .with_tier(RecurrentEventTier)
+}
+
+/// Estimates the win and loss probabilities over all pairs with the recurrent event hierarchy.
+///
+/// Returns `Error::EmptyData` if an arm is empty.
+pub fn recurrent_pairwise_probabilities(
+    treatment: &[RecurrentEventHistory],
+    control: &[RecurrentEventHistory],
+) -> Result<PairwiseProbabilities> {
+    let hierarchy = recurrent_hierarchy();
+    pairwise_probabilities_with(treatment, control, |t, c| hierarchy.scores(t, c))
+}
+
+/// Estimates the win and loss probabilities with the recurrent event hierarchy, without
+/// checking that both arms are non-empty.
+pub fn recurrent_pairwise_probabilities_lenient(
+    treatment: &[RecurrentEventHistory],
+    control: &[RecurrentEventHistory],
+) -> PairwiseProbabilities {
+    let hierarchy = recurrent_hierarchy();
+    pairwise_probabilities_with_lenient(treatment, control, |t, c| hierarchy.scores(t, c))
+}
+
+/// Performs the Finkelstein–Schoenfeld all-pairs analysis with recurrent events.
+///
+/// ## Parameters
+///
+/// * `treatment`: Patients in the treatment arm.
+/// * `control`: Patients in the control arm.
+/// * `confidence_level`: The confidence level of the intervals, e.g. `0.95`.
+///
+/// ## Returns
+///
+/// An `UnmatchedWinAnalysis` with the win ratio, net benefit and win odds, or the errors of
+/// `unmatched::finkelstein_schoenfeld`.
+pub fn finkelstein_schoenfeld_recurrent(
+    treatment: &[RecurrentEventHistory],
+    control: &[RecurrentEventHistory],
+    confidence_level: f64,
+) -> Result<UnmatchedWinAnalysis> {
+    error::probability("confidence_level", confidence_level)?;
+    let probabilities = recurrent_pairwise_probabilities(treatment, control)?;
+    probabilities.win_ratio()?;
+    Ok(analyze_pairwise_probabilities(&probabilities, confidence_level))
+}
+
+/// Performs the all-pairs analysis with recurrent events, returning an infinite win ratio if
+/// there are no losses, a zero win ratio if there are no wins, and NaN estimates if an arm is
+/// empty or every pair is tied.
+pub fn finkelstein_schoenfeld_recurrent_lenient(
+    treatment: &[RecurrentEventHistory],
+    control: &[RecurrentEventHistory],
+    confidence_level: f64,
+) -> UnmatchedWinAnalysis {
+    analyze_pairwise_probabilities(&recurrent_pairwise_probabilities_lenient(treatment, control), confidence_level)
+}
+
+/// Parameters for simulating recurrent non-fatal events.
+///
+/// The fatal event time follows the marginal distribution of T in `params`,
+/// S(t) = exp[-(θλ1 t)^α] in the treatment arm. Each patient has a gamma frailty W with mean 1
+/// and variance σ²; given W, the recurrent events form a Poisson process with rate θλ2W until
+/// death or censoring. The frailty makes a patient's events positively correlated, so the
+/// counts are overdispersed with variance μ + σ²μ².
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RecurrentSimulationParams {
+    /// The survival model; λ2 is used as the recurrent event rate in the control arm.
+    pub params: SimulationParams,
+    /// Variance σ² of the gamma frailty. Zero gives a Poisson process without frailty.
+    pub frailty_variance: f64,
+}
+
+impl RecurrentSimulationParams {
+    /// Creates a new `RecurrentSimulationParams`.
+    pub fn new(params: SimulationParams, frailty_variance: f64) -> Self {
+        Self { params, frailty_variance }
+    }
+
+    /// Checks the survival model, and that the frailty variance is finite and non-negative.
+    pub fn validate(&self) -> Result<()> {
+        self.params.validate()?;
+        error::non_negative("frailty_variance", self.frailty_variance)?;
+        Ok(())
+    }
+
+    /// Simulates the observed follow-up of one patient.
+    ///
+    /// Returns `Error::InvalidParameter` if the model or the censoring design is invalid.
+    pub fn sample_history<R: Rng + ?Sized>(
+        &self,
+        arm: Arm,
+        design: &CensoringDesign,
+        rng: &mut R,
+    ) -> Result<RecurrentEventHistory> {
+        self.validate()?;
+        design.validate()?;
+        Ok(self.sample_history_lenient(arm, design, rng))
+    }
+
+    /// Simulates the observed follow-up of one patient without checking the model or the design.
+    pub fn sample_history_lenient<R: Rng + ?Sized>(
+        &self,
+        arm: Arm,
+        design: &CensoringDesign,
+        rng: &mut R,
+    ) -> RecurrentEventHistory {
+        let multiplier = self.params.rate_multiplier(arm);
+        let death = sample_standard_exponential(rng).powf(1.0 / self.params.alpha) / (multiplier * self.params.lambda1);
+        let mut censoring_time = design.follow_up;
+        if design.dropout_rate > 0.0 {
+            censoring_time = censoring_time.min(sample_standard_exponential(rng) / design.dropout_rate);
+        }
+
+        let frailty = if self.frailty_variance > 0.0 {
+            let shape = 1.0 / self.frailty_variance;
+            sample_gamma(shape, rng) / shape
+        } else {
+            1.0
+        };
+        let rate = multiplier * self.params.lambda2 * frailty;
+        let end = death.min(censoring_time);
+        let mut event_times = Vec::new();
+        let mut time = sample_standard_exponential(rng) / rate;
+        while time < end {
+            event_times.push(time);
+            time += sample_standard_exponential(rng) / rate;
+        }
+
+        // The simulated event times are increasing and finite, so they need no sorting or checks.
+        let death_time = Some(death).filter(|&t| t <= censoring_time);
+        RecurrentEventHistory { death_time, event_times, censoring_time: end }
+    }
+}
+
+impl SimulationParams {
+    /// Extends the model with gamma-frailty recurrent non-fatal events.
+    pub fn with_recurrent_events(&self, frailty_variance: f64) -> RecurrentSimulationParams {
+        RecurrentSimulationParams::new(*self, frailty_variance)
+    }
+}
+
+/// Patient-level data of a simulated two-arm trial with recurrent events.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecurrentTrial {
+    /// Patients in the treatment arm.
+    pub treatment: Vec<RecurrentEventHistory>,
+    /// Patients in the control arm.
+    pub control: Vec<RecurrentEventHistory>,
+}
+
+/// Simulates a two-arm trial with recurrent events.
+///
+/// ## Parameters
+///
+/// * `params`: The recurrent event model.
+/// * `design`: The censoring mechanism.
+/// * `n_treatment`: Number of patients in the treatment arm.
+/// * `n_control`: Number of patients in the control arm.
+/// * `rng`: The random number generator; seed it for reproducible results.
+///
+/// ## Returns
+///
+/// The simulated patients, or `Error::InvalidParameter` if the model or the censoring design
+/// is invalid.
+pub fn simulate_recurrent_trial<R: Rng + ?Sized>(
+    params: &RecurrentSimulationParams,
+    design: &CensoringDesign,
+    n_treatment: usize,
+    n_control: usize,
+    rng: &mut R,
+) -> Result<RecurrentTrial> {
+    params.validate()?;
+    design.validate()?;
+    Ok(simulate_recurrent_trial_lenient(params, design, n_treatment, n_control, rng))
+}
+
+/// Simulates a two-arm trial with recurrent events without checking the model or the design.
+pub fn simulate_recurrent_trial_lenient<R: Rng + ?Sized>(
+    params: &RecurrentSimulationParams,
+    design: &CensoringDesign,
+    n_treatment: usize,
+    n_control: usize,
+    rng: &mut R,
+) -> RecurrentTrial {
+    RecurrentTrial {
+        treatment: (0..n_treatment).map(|_| params.sample_history_lenient(Arm::Treatment, design, rng)).collect(),
+        control: (0..n_control).map(|_| params.sample_history_lenient(Arm::Control, design, rng)).collect(),
+    }
+}
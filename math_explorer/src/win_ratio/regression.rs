@@ -0,0 +1,266 @@
+//! # Win Ratio Regression
+//!
+//! Covariate-adjusted win ratio following Mao and Wang (2021). Every pair of subjects is
+//! compared, regardless of arm, and the win ratio of subject i over subject j is modelled as
+//!
+//! P(i wins) / P(j wins) = exp(β'(Z_i - Z_j))
+//!
+//! so exp(β_k) is the win ratio for a unit increase in covariate k with the others held fixed.
+//! With a treatment indicator as the only covariate, exp(β) is the all-pairs win ratio.
+
+use nalgebra::{DMatrix, DVector};
+
+use crate::error::{self, Error, Result};
+
+use super::distributions::{chi_squared_survival, two_sided_critical_value, two_sided_p_value};
+use super::sample_win_ratio::PatientRecord;
+use super::unmatched::hierarchical_scores;
+
+const MAX_NEWTON_ITERATIONS: usize = 100;
+const NEWTON_TOLERANCE: f64 = 1e-10;
+/// Fitted probabilities this close to zero or one count as a perfectly predicted outcome.
+const SEPARATION_TOLERANCE: f64 = 1e-8;
+
+/// An estimated regression coefficient.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegressionCoefficient {
+    /// Name of the covariate.
+    pub name: String,
+    /// The log win ratio per unit of the covariate, β.
+    pub estimate: f64,
+    /// Robust (sandwich) standard error of β.
+    pub standard_error: f64,
+    /// Wald statistic, β / SE(β).
+    pub z_statistic: f64,
+    /// Two-sided p-value of the Wald test of β = 0.
+    pub p_value: f64,
+    /// The win ratio per unit of the covariate, exp(β).
+    pub win_ratio: f64,
+    /// Confidence interval of the win ratio.
+    pub confidence_interval: (f64, f64),
+}
+
+/// A Wald test of several coefficients being zero.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WaldTest {
+    /// The statistic β' V⁻¹ β over the tested coefficients.
+    pub statistic: f64,
+    /// Number of coefficients tested.
+    pub degrees_of_freedom: usize,
+    /// Upper tail probability of the chi-squared distribution.
+    pub p_value: f64,
+}
+
+/// A fitted win ratio regression.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WinRatioRegression {
+    /// The coefficients, in the order of the covariate columns.
+    pub coefficients: Vec<RegressionCoefficient>,
+    /// Robust (sandwich) covariance matrix of the estimates.
+    pub covariance: DMatrix<f64>,
+    /// Number of subjects.
+    pub n_subjects: usize,
+    /// Number of pairs decided by the comparison (pairs that are not tied).
+    pub n_decided_pairs: usize,
+    /// Number of Newton–Raphson iterations used.
+    pub iterations: usize,
+}
+
+impl WinRatioRegression {
+    /// The vector of estimates β.
+    pub fn estimates(&self) -> DVector<f64> {
+        DVector::from_iterator(self.coefficients.len(), self.coefficients.iter().map(|c| c.estimate))
+    }
+
+    /// Joint Wald test that the coefficients with the given indices are all zero.
+    ///
+    /// Returns `None` if the covariance of the tested coefficients is singular.
+    pub fn wald_test(&self, indices: &[usize]) -> Option<WaldTest> {
+        let k = indices.len();
+        let beta = DVector::from_iterator(k, indices.iter().map(|&i| self.coefficients[i].estimate));
+        let covariance = DMatrix::from_fn(k, k, |r, c| self.covariance[(indices[r], indices[c])]);
+        let statistic = (beta.transpose() * covariance.try_inverse()? * &beta)[(0, 0)];
+        Some(WaldTest { statistic, degrees_of_freedom: k, p_value: chi_squared_survival(statistic, k as f64) })
+    }
+}
+
+/// A pair of subjects that was not tied.
+struct DecidedPair {
+    first: usize,
+    second: usize,
+    difference: DVector<f64>,
+    win: f64,
+    loss: f64,
+}
+
+fn logistic(x: f64) -> f64 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+/// Fits a win ratio regression with a user-supplied pairwise comparison.
+///
+/// ## Formula
+///
+/// β solves the estimating equation
+///
+/// U(β) = Σ_{i<j} (Z_i - Z_j) [δ_ij - p_ij (δ_ij + δ_ji)] = 0,   p_ij = exp(β'(Z_i - Z_j)) / (1 + exp(β'(Z_i - Z_j)))
+///
+/// where δ_ij is the win score of i over j. This is a logistic regression without intercept
+/// on the covariate differences of the decided pairs, solved by Newton–Raphson. The robust
+/// covariance is A⁻¹ B A⁻¹, where A is the information matrix and B is the Hoeffding
+/// variance of U, estimated from the per-subject means of the pairwise scores.
+///
+/// ## Parameters
+///
+/// * `subjects`: All subjects, from both arms.
+/// * `covariates`: One row per subject and one column per covariate, e.g. a treatment
+///   indicator, age and baseline BMI. No intercept column should be included.
+/// * `names`: Names of the covariate columns.
+/// * `kernel`: Closure returning the `(win, loss)` scores of subject i against subject j.
+/// * `confidence_level`: The confidence level of the intervals, e.g. `0.95`.
+///
+/// ## Returns
+///
+/// The fitted regression, or `Error::InvalidParameter` if `confidence_level` is outside
+/// [0, 1], `Error::EmptyData` if every pair is tied, `Error::ZeroVariance` if the information
+/// matrix is singular (for example when the covariates are collinear), `Error::NoWins` or
+/// `Error::NoLosses` if the covariates separate the wins from the losses, so that the
+/// dominant exp(β_k) tends to zero or infinity, and `Error::NoConvergence` if Newton–Raphson
+/// does not converge otherwise.
+///
+/// ## Panics
+///
+/// Panics if the number of rows of `covariates` differs from the number of subjects, or the
+/// number of names differs from the number of columns.
+pub fn win_ratio_regression_with<P, F>(
+    subjects: &[P],
+    covariates: &DMatrix<f64>,
+    names: &[&str],
+    kernel: F,
+    confidence_level: f64,
+) -> Result<WinRatioRegression>
+where
+    F: Fn(&P, &P) -> (f64, f64),
+{
+    let n = subjects.len();
+    let p = covariates.ncols();
+    assert_eq!(covariates.nrows(), n, "one row of covariates is needed per subject");
+    assert_eq!(names.len(), p, "one name is needed per covariate");
+    error::probability("confidence_level", confidence_level)?;
+
+    let mut pairs = Vec::new();
+    for i in 0..n {
+        for j in (i + 1)..n {
+            let (win, loss) = kernel(&subjects[i], &subjects[j]);
+            if win + loss > 0.0 {
+                let difference = (covariates.row(i) - covariates.row(j)).transpose();
+                pairs.push(DecidedPair { first: i, second: j, difference, win, loss });
+            }
+        }
+    }
+
+    if pairs.is_empty() {
+        return Err(Error::EmptyData);
+    }
+
+    let mut beta = DVector::zeros(p);
+    let mut information = DMatrix::zeros(p, p);
+    let mut iterations = 0;
+    let mut converged = false;
+    let mut singular = false;
+    while iterations < MAX_NEWTON_ITERATIONS {
+        iterations += 1;
+        let mut score = DVector::zeros(p);
+        information.fill(0.0);
+        for pair in &pairs {
+            // p and 1 - p are both evaluated directly, avoiding the cancellation in 1 - p as p
+            // approaches one.
+            let linear_predictor = beta.dot(&pair.difference);
+            let (probability, complement) = (logistic(linear_predictor), logistic(-linear_predictor));
+            score += &pair.difference * (pair.win * complement - pair.loss * probability);
+            information += &pair.difference * pair.difference.transpose() * (probability * complement * (pair.win + pair.loss));
+        }
+        let Some(inverse) = information.clone().try_inverse() else {
+            singular = true;
+            break;
+        };
+        let step = inverse * score;
+        beta += &step;
+        if step.amax() < NEWTON_TOLERANCE {
+            converged = true;
+            break;
+        }
+    }
+    if !converged {
+        // Covariates that separate the wins from the losses drive β to infinity, and with it
+        // the fitted probability of every decided outcome to one. Pairs with equal covariates
+        // carry no information about β and are left out.
+        let separated = pairs.iter().filter(|pair| pair.difference.amax() > 0.0).all(|pair| {
+            let probability = logistic(beta.dot(&pair.difference));
+            (pair.loss == 0.0 && probability > 1.0 - SEPARATION_TOLERANCE)
+                || (pair.win == 0.0 && probability < SEPARATION_TOLERANCE)
+        });
+        if separated {
+            let dominant = beta.iter().copied().fold(0.0, |a: f64, b| if b.abs() > a.abs() { b } else { a });
+            return Err(if dominant < 0.0 { Error::NoWins } else { Error::NoLosses });
+        }
+        return Err(if singular { Error::ZeroVariance } else { Error::NoConvergence { iterations } });
+    }
+
+    // Per-subject means of the symmetric pairwise scores u_ij, the Hoeffding projections of U.
+    let mut projections = vec![DVector::zeros(p); n];
+    for pair in &pairs {
+        let probability = logistic(beta.dot(&pair.difference));
+        let u = &pair.difference * (pair.win - probability * (pair.win + pair.loss));
+        projections[pair.first] += &u;
+        projections[pair.second] += &u;
+    }
+    let n_f = n as f64;
+    let pair_count = n_f * (n_f - 1.0) / 2.0;
+    let mut meat = DMatrix::zeros(p, p);
+    for projection in &projections {
+        let h = projection / (n_f - 1.0);
+        meat += &h * h.transpose();
+    }
+    // Var(U / N) ≈ 4 / n * Var(h), with U / N the average over the N = n(n - 1)/2 pairs.
+    let meat = meat * (4.0 / (n_f * n_f));
+    let bread = (information / pair_count).try_inverse().ok_or(Error::ZeroVariance)?;
+    let covariance = &bread * meat * &bread;
+
+    let critical_value = two_sided_critical_value(confidence_level);
+    let coefficients = names
+        .iter()
+        .enumerate()
+        .map(|(k, name)| {
+            let estimate = beta[k];
+            let standard_error = covariance[(k, k)].sqrt();
+            let z_statistic = estimate / standard_error;
+            RegressionCoefficient {
+                name: name.to_string(),
+                estimate,
+                standard_error,
+                z_statistic,
+                p_value: two_sided_p_value(z_statistic),
+                win_ratio: estimate.exp(),
+                confidence_interval: (
+                    (estimate - critical_value * standard_error).exp(),
+                    (estimate + critical_value * standard_error).exp(),
+                ),
+            }
+        })
+        .collect();
+
+    Ok(WinRatioRegression { coefficients, covariance, n_subjects: n, n_decided_pairs: pairs.len(), iterations })
+}
+
+/// Fits a win ratio regression on patient records compared with the Pocock hierarchy.
+///
+/// See `win_ratio_regression_with` for the model and parameters.
+pub fn win_ratio_regression(
+    patients: &[PatientRecord],
+    covariates: &DMatrix<f64>,
+    names: &[&str],
+    confidence_level: f64,
+) -> Result<WinRatioRegression> {
+    win_ratio_regression_with(patients, covariates, names, hierarchical_scores, confidence_level)
+}
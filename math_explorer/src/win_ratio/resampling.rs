@@ -0,0 +1,473 @@
+//! # Resampling Inference for Win Statistics
+//!
+//! Bootstrap confidence intervals (percentile and BCa) and permutation tests for the
+//! win ratio, net benefit and win odds of two unmatched arms. These do not rely on
+//! asymptotic normality, so they are better suited to small samples. All randomness
+//! comes from the caller's random number generator, so results are reproducible from
+//! its seed.
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::error::{self, Error, Result};
+
+use super::distributions::{standard_normal_cdf, standard_normal_quantile};
+use super::sample_win_ratio::PatientRecord;
+use super::unmatched::hierarchical_scores;
+
+/// Percentile and bias-corrected and accelerated (BCa) bootstrap intervals for one statistic.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BootstrapInterval {
+    /// The statistic on the original data.
+    pub estimate: f64,
+    /// Percentile interval as `(lower_bound, upper_bound)`.
+    pub percentile: (f64, f64),
+    /// BCa interval as `(lower_bound, upper_bound)`.
+    pub bca: (f64, f64),
+}
+
+/// Bootstrap intervals for the win ratio, net benefit and win odds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BootstrapResult {
+    /// Number of bootstrap resamples.
+    pub n_resamples: usize,
+    /// Win ratio, W / L.
+    pub win_ratio: BootstrapInterval,
+    /// Net benefit, W - L.
+    pub net_benefit: BootstrapInterval,
+    /// Win odds, (1 + NB) / (1 - NB).
+    pub win_odds: BootstrapInterval,
+}
+
+/// Result of a permutation test of the net benefit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PermutationTest {
+    /// Net benefit of the observed treatment assignment.
+    pub observed_net_benefit: f64,
+    /// Two-sided p-value: the proportion of assignments with |NB| at least as large as observed.
+    pub p_value: f64,
+    /// Number of assignments evaluated.
+    pub n_permutations: usize,
+    /// `true` if all assignments were enumerated, `false` for a Monte Carlo approximation.
+    pub exact: bool,
+}
+
+/// Win and loss score matrices of every treatment-control pair.
+struct ScoreMatrix {
+    wins: Vec<Vec<f64>>,
+    losses: Vec<Vec<f64>>,
+}
+
+impl ScoreMatrix {
+    fn new<P, F>(rows: &[P], columns: &[P], kernel: &F) -> Self
+    where
+        F: Fn(&P, &P) -> (f64, f64),
+    {
+        let mut wins = vec![vec![0.0; columns.len()]; rows.len()];
+        let mut losses = vec![vec![0.0; columns.len()]; rows.len()];
+        for (i, r) in rows.iter().enumerate() {
+            for (j, c) in columns.iter().enumerate() {
+                let (win, loss) = kernel(r, c);
+                wins[i][j] = win;
+                losses[i][j] = loss;
+            }
+        }
+        Self { wins, losses }
+    }
+
+    /// Win and loss probabilities with each row and column weighted by its multiplicity.
+    fn weighted_probabilities(&self, row_weights: &[f64], column_weights: &[f64]) -> (f64, f64) {
+        let (mut win, mut loss) = (0.0, 0.0);
+        for (i, &ri) in row_weights.iter().enumerate().filter(|(_, w)| **w > 0.0) {
+            for (j, &cj) in column_weights.iter().enumerate().filter(|(_, w)| **w > 0.0) {
+                win += ri * cj * self.wins[i][j];
+                loss += ri * cj * self.losses[i][j];
+            }
+        }
+        let total = row_weights.iter().sum::<f64>() * column_weights.iter().sum::<f64>();
+        (win / total, loss / total)
+    }
+}
+
+fn win_ratio((win, loss): (f64, f64)) -> f64 {
+    win / loss
+}
+
+fn net_benefit((win, loss): (f64, f64)) -> f64 {
+    win - loss
+}
+
+fn win_odds(probabilities: (f64, f64)) -> f64 {
+    let nb = net_benefit(probabilities);
+    (1.0 + nb) / (1.0 - nb)
+}
+
+/// Empirical quantile with linear interpolation between order statistics, NaN if `sorted` is empty.
+fn quantile(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return f64::NAN;
+    }
+    let position = p.clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+    let below = position.floor() as usize;
+    let above = position.ceil() as usize;
+    let fraction = position - below as f64;
+    if fraction == 0.0 || sorted[below] == sorted[above] {
+        sorted[below]
+    } else if sorted[above].is_infinite() {
+        sorted[above]
+    } else {
+        sorted[below] + fraction * (sorted[above] - sorted[below])
+    }
+}
+
+fn bootstrap_interval(
+    estimate: f64,
+    replicates: &mut Vec<f64>,
+    jackknife: &[f64],
+    confidence_level: f64,
+) -> BootstrapInterval {
+    // Resamples without any wins or losses leave the ratios undefined; they are dropped.
+    replicates.retain(|r| !r.is_nan());
+    if replicates.is_empty() {
+        return BootstrapInterval { estimate, percentile: (f64::NAN, f64::NAN), bca: (f64::NAN, f64::NAN) };
+    }
+    replicates.sort_by(f64::total_cmp);
+    let alpha = 1.0 - confidence_level;
+    let percentile = (quantile(replicates, alpha / 2.0), quantile(replicates, 1.0 - alpha / 2.0));
+
+    // Bias correction from the proportion of replicates below the estimate.
+    let below = replicates.iter().filter(|&&r| r < estimate).count() as f64;
+    let z0 = standard_normal_quantile(below / replicates.len() as f64);
+
+    // Acceleration from the skewness of the jackknife estimates.
+    let finite: Vec<f64> = jackknife.iter().copied().filter(|v| v.is_finite()).collect();
+    let mean = finite.iter().sum::<f64>() / finite.len() as f64;
+    let (squares, cubes) = finite.iter().fold((0.0, 0.0), |(s, c), &v| {
+        let d = mean - v;
+        (s + d * d, c + d * d * d)
+    });
+    let acceleration = if squares > 0.0 { cubes / (6.0 * squares.powf(1.5)) } else { 0.0 };
+
+    let adjusted = |p: f64| {
+        let z = standard_normal_quantile(p);
+        standard_normal_cdf(z0 + (z0 + z) / (1.0 - acceleration * (z0 + z)))
+    };
+    let bca = if z0.is_finite() {
+        (quantile(replicates, adjusted(alpha / 2.0)), quantile(replicates, adjusted(1.0 - alpha / 2.0)))
+    } else {
+        percentile
+    };
+
+    BootstrapInterval { estimate, percentile, bca }
+}
+
+/// Bootstrap intervals for the win statistics using a user-supplied pairwise kernel.
+///
+/// Each resample draws patients with replacement within each arm, keeping the arm sizes fixed.
+/// Resamples in which a statistic is undefined (no wins and no losses) are left out of its
+/// interval; an infinite win ratio (no losses) is kept. If a statistic is undefined in every
+/// resample, `bootstrap_with_lenient` reports NaN bounds. The BCa acceleration is estimated
+/// by the leave-one-patient-out jackknife.
+///
+/// ## Parameters
+///
+/// * `treatment`: Patients in the treatment arm.
+/// * `control`: Patients in the control arm.
+/// * `kernel`: Closure returning the `(win, loss)` scores of a `(treatment, control)` pair.
+/// * `n_resamples`: Number of bootstrap resamples.
+/// * `confidence_level`: The confidence level of the intervals, e.g. `0.95`.
+/// * `rng`: The random number generator; seed it for reproducible results.
+///
+/// ## Returns
+///
+/// The intervals of the three statistics, or `Error::InvalidParameter` if `confidence_level`
+/// is outside [0, 1], `Error::EmptyData` if an arm is empty or every pair is tied, and
+/// `Error::NoLosses` or `Error::NoWins` if the observed win ratio is unbounded or zero.
+pub fn bootstrap_with<P, F, R>(
+    treatment: &[P],
+    control: &[P],
+    kernel: F,
+    n_resamples: usize,
+    confidence_level: f64,
+    rng: &mut R,
+) -> Result<BootstrapResult>
+where
+    F: Fn(&P, &P) -> (f64, f64),
+    R: Rng + ?Sized,
+{
+    error::probability("confidence_level", confidence_level)?;
+    if treatment.is_empty() || control.is_empty() {
+        return Err(Error::EmptyData);
+    }
+    let result = bootstrap_with_lenient(treatment, control, kernel, n_resamples, confidence_level, rng);
+    let estimate = result.win_ratio.estimate;
+    if estimate.is_nan() {
+        Err(Error::EmptyData)
+    } else if estimate.is_infinite() {
+        Err(Error::NoLosses)
+    } else if estimate == 0.0 {
+        Err(Error::NoWins)
+    } else {
+        Ok(result)
+    }
+}
+
+/// Bootstrap intervals using a user-supplied pairwise kernel, with NaN estimates and bounds
+/// if an arm is empty, and an infinite or zero win ratio if there are no losses or no wins.
+pub fn bootstrap_with_lenient<P, F, R>(
+    treatment: &[P],
+    control: &[P],
+    kernel: F,
+    n_resamples: usize,
+    confidence_level: f64,
+    rng: &mut R,
+) -> BootstrapResult
+where
+    F: Fn(&P, &P) -> (f64, f64),
+    R: Rng + ?Sized,
+{
+    let n = treatment.len();
+    let m = control.len();
+    let scores = ScoreMatrix::new(treatment, control, &kernel);
+    let observed = scores.weighted_probabilities(&vec![1.0; n], &vec![1.0; m]);
+
+    let mut replicates = [
+        Vec::with_capacity(n_resamples),
+        Vec::with_capacity(n_resamples),
+        Vec::with_capacity(n_resamples),
+    ];
+    let mut row_weights = vec![0.0; n];
+    let mut column_weights = vec![0.0; m];
+    for _ in 0..n_resamples {
+        row_weights.iter_mut().for_each(|w| *w = 0.0);
+        column_weights.iter_mut().for_each(|w| *w = 0.0);
+        for _ in 0..n {
+            row_weights[rng.gen_range(0..n)] += 1.0;
+        }
+        for _ in 0..m {
+            column_weights[rng.gen_range(0..m)] += 1.0;
+        }
+        let probabilities = scores.weighted_probabilities(&row_weights, &column_weights);
+        replicates[0].push(win_ratio(probabilities));
+        replicates[1].push(net_benefit(probabilities));
+        replicates[2].push(win_odds(probabilities));
+    }
+
+    // Leave-one-out estimates from the row and column sums of the score matrices.
+    let total_win: f64 = scores.wins.iter().flatten().sum();
+    let total_loss: f64 = scores.losses.iter().flatten().sum();
+    let mut jackknife = [Vec::new(), Vec::new(), Vec::new()];
+    let mut push_jackknife = |probabilities: (f64, f64)| {
+        jackknife[0].push(win_ratio(probabilities));
+        jackknife[1].push(net_benefit(probabilities));
+        jackknife[2].push(win_odds(probabilities));
+    };
+    if n > 1 {
+        for i in 0..n {
+            let pairs = ((n - 1) * m) as f64;
+            let row_win: f64 = scores.wins[i].iter().sum();
+            let row_loss: f64 = scores.losses[i].iter().sum();
+            push_jackknife(((total_win - row_win) / pairs, (total_loss - row_loss) / pairs));
+        }
+    }
+    if m > 1 {
+        for j in 0..m {
+            let pairs = (n * (m - 1)) as f64;
+            let column_win: f64 = scores.wins.iter().map(|row| row[j]).sum();
+            let column_loss: f64 = scores.losses.iter().map(|row| row[j]).sum();
+            push_jackknife(((total_win - column_win) / pairs, (total_loss - column_loss) / pairs));
+        }
+    }
+
+    let [wr, nb, wo] = &mut replicates;
+    BootstrapResult {
+        n_resamples,
+        win_ratio: bootstrap_interval(win_ratio(observed), wr, &jackknife[0], confidence_level),
+        net_benefit: bootstrap_interval(net_benefit(observed), nb, &jackknife[1], confidence_level),
+        win_odds: bootstrap_interval(win_odds(observed), wo, &jackknife[2], confidence_level),
+    }
+}
+
+/// Bootstrap intervals for the win statistics using the Pocock hierarchy on patient records.
+///
+/// Returns the errors of `bootstrap_with`, which also describes the intervals.
+pub fn bootstrap<R: Rng + ?Sized>(
+    treatment: &[PatientRecord],
+    control: &[PatientRecord],
+    n_resamples: usize,
+    confidence_level: f64,
+    rng: &mut R,
+) -> Result<BootstrapResult> {
+    bootstrap_with(treatment, control, hierarchical_scores, n_resamples, confidence_level, rng)
+}
+
+/// Bootstrap intervals using the Pocock hierarchy, with NaN estimates and bounds if an arm is empty.
+pub fn bootstrap_lenient<R: Rng + ?Sized>(
+    treatment: &[PatientRecord],
+    control: &[PatientRecord],
+    n_resamples: usize,
+    confidence_level: f64,
+    rng: &mut R,
+) -> BootstrapResult {
+    bootstrap_with_lenient(treatment, control, hierarchical_scores, n_resamples, confidence_level, rng)
+}
+
+/// Number of ways to choose `k` of `n` items, saturating at `usize::MAX`.
+fn binomial_coefficient(n: usize, k: usize) -> usize {
+    let k = k.min(n - k);
+    let mut result: u128 = 1;
+    for i in 0..k {
+        result = result * (n - i) as u128 / (i + 1) as u128;
+        if result > usize::MAX as u128 {
+            return usize::MAX;
+        }
+    }
+    result as usize
+}
+
+/// Permutation test of the net benefit using a user-supplied pairwise kernel.
+///
+/// Under the null hypothesis the treatment labels are exchangeable. If the number of possible
+/// assignments is at most `max_exact_permutations`, they are all enumerated and the p-value is
+/// exact. Otherwise `n_permutations` random assignments are drawn and the Monte Carlo p-value
+/// (count + 1) / (n_permutations + 1) is reported.
+///
+/// ## Parameters
+///
+/// * `treatment`: Patients in the treatment arm.
+/// * `control`: Patients in the control arm.
+/// * `kernel`: Closure returning the `(win, loss)` scores of a `(first, second)` pair of patients.
+/// * `max_exact_permutations`: Largest number of assignments to enumerate exactly.
+/// * `n_permutations`: Number of random assignments for the Monte Carlo test.
+/// * `rng`: The random number generator; seed it for reproducible results.
+///
+/// ## Returns
+///
+/// The observed net benefit and its p-value, or `Error::EmptyData` if an arm is empty.
+pub fn permutation_test_with<P, F, R>(
+    treatment: &[P],
+    control: &[P],
+    kernel: F,
+    max_exact_permutations: usize,
+    n_permutations: usize,
+    rng: &mut R,
+) -> Result<PermutationTest>
+where
+    F: Fn(&P, &P) -> (f64, f64),
+    R: Rng + ?Sized,
+{
+    if treatment.is_empty() || control.is_empty() {
+        return Err(Error::EmptyData);
+    }
+    Ok(permutation_test_with_lenient(treatment, control, kernel, max_exact_permutations, n_permutations, rng))
+}
+
+/// Permutation test using a user-supplied pairwise kernel, with a NaN net benefit if an arm
+/// is empty.
+pub fn permutation_test_with_lenient<P, F, R>(
+    treatment: &[P],
+    control: &[P],
+    kernel: F,
+    max_exact_permutations: usize,
+    n_permutations: usize,
+    rng: &mut R,
+) -> PermutationTest
+where
+    F: Fn(&P, &P) -> (f64, f64),
+    R: Rng + ?Sized,
+{
+    let n = treatment.len();
+    let pooled: Vec<&P> = treatment.iter().chain(control.iter()).collect();
+    let total = pooled.len();
+
+    // Net score of every ordered pair of pooled patients.
+    let mut net_scores = vec![vec![0.0; total]; total];
+    for (a, first) in pooled.iter().enumerate() {
+        for (b, second) in pooled.iter().enumerate() {
+            if a != b {
+                let (win, loss) = kernel(first, second);
+                net_scores[a][b] = win - loss;
+            }
+        }
+    }
+    let n_pairs = (n * (total - n)) as f64;
+    let statistic = |in_treatment: &[bool]| {
+        let mut sum = 0.0;
+        for a in (0..total).filter(|&a| in_treatment[a]) {
+            for b in (0..total).filter(|&b| !in_treatment[b]) {
+                sum += net_scores[a][b];
+            }
+        }
+        sum / n_pairs
+    };
+
+    let mut assignment: Vec<bool> = (0..total).map(|i| i < n).collect();
+    let observed = statistic(&assignment);
+    let threshold = observed.abs() - 1e-12;
+
+    let n_assignments = binomial_coefficient(total, n);
+    if n_assignments <= max_exact_permutations {
+        let mut extreme = 0;
+        let mut indices: Vec<usize> = (0..n).collect();
+        loop {
+            assignment.iter_mut().for_each(|a| *a = false);
+            indices.iter().for_each(|&i| assignment[i] = true);
+            if statistic(&assignment).abs() >= threshold {
+                extreme += 1;
+            }
+
+            // Advance to the next combination in lexicographic order.
+            let Some(position) = (0..n).rev().find(|&k| indices[k] < total - n + k) else {
+                break;
+            };
+            indices[position] += 1;
+            for k in position + 1..n {
+                indices[k] = indices[k - 1] + 1;
+            }
+        }
+        PermutationTest {
+            observed_net_benefit: observed,
+            p_value: extreme as f64 / n_assignments as f64,
+            n_permutations: n_assignments,
+            exact: true,
+        }
+    } else {
+        let mut extreme = 0;
+        for _ in 0..n_permutations {
+            assignment.shuffle(rng);
+            if statistic(&assignment).abs() >= threshold {
+                extreme += 1;
+            }
+        }
+        PermutationTest {
+            observed_net_benefit: observed,
+            p_value: (extreme + 1) as f64 / (n_permutations + 1) as f64,
+            n_permutations,
+            exact: false,
+        }
+    }
+}
+
+/// Permutation test of the net benefit using the Pocock hierarchy on patient records.
+///
+/// Returns `Error::EmptyData` if an arm is empty. See `permutation_test_with` for the test.
+pub fn permutation_test<R: Rng + ?Sized>(
+    treatment: &[PatientRecord],
+    control: &[PatientRecord],
+    max_exact_permutations: usize,
+    n_permutations: usize,
+    rng: &mut R,
+) -> Result<PermutationTest> {
+    permutation_test_with(treatment, control, hierarchical_scores, max_exact_permutations, n_permutations, rng)
+}
+
+/// Permutation test using the Pocock hierarchy, with a NaN net benefit if an arm is empty.
+pub fn permutation_test_lenient<R: Rng + ?Sized>(
+    treatment: &[PatientRecord],
+    control: &[PatientRecord],
+    max_exact_permutations: usize,
+    n_permutations: usize,
+    rng: &mut R,
+) -> PermutationTest {
+    permutation_test_with_lenient(treatment, control, hierarchical_scores, max_exact_permutations, n_permutations, rng)
+}
@@ -1,9 +1,15 @@
 //! # Sample Win Ratio (Matched-Pairs Approach)
 //!
 //! Functions to calculate the sample win ratio, confidence interval, and significance test.
+//! Win-loss counts can be entered directly or derived from patient-level data with the
+//! Pocock hierarchical comparison (death first, then the non-fatal event).
+
+use crate::error::{Error, Result};
+
+use super::distributions::{beta_quantile, binomial_cdf, two_sided_critical_value, two_sided_p_value};
 
 /// Represents the number of pairs in each category for win-loss analysis.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub struct WinLossCounts {
     /// Na: The patient in the treatment group dies first (a loss).
     pub n_a: u32,
@@ -32,6 +38,177 @@ impl WinLossCounts {
     }
 }
 
+/// Follow-up data for a single patient with a fatal and a non-fatal event.
+///
+/// An event only counts if it occurs on or before `censoring_time`; later times are treated
+/// as unobserved.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PatientRecord {
+    /// Time of the fatal event (e.g. death), or `None` if it was not observed.
+    pub fatal_time: Option<f64>,
+    /// Time of the first non-fatal event (e.g. hospitalization), or `None` if it was not observed.
+    pub non_fatal_time: Option<f64>,
+    /// End of the patient's follow-up.
+    pub censoring_time: f64,
+}
+
+impl PatientRecord {
+    /// Creates a new `PatientRecord`.
+    pub fn new(fatal_time: Option<f64>, non_fatal_time: Option<f64>, censoring_time: f64) -> Self {
+        Self { fatal_time, non_fatal_time, censoring_time }
+    }
+
+    /// Time of the fatal event if it was observed within `[0, window]`.
+    pub fn fatal_time_within(&self, window: f64) -> Option<f64> {
+        self.fatal_time.filter(|&t| t <= window && t <= self.censoring_time)
+    }
+
+    /// Time of the non-fatal event if it was observed within `[0, window]`.
+    pub fn non_fatal_time_within(&self, window: f64) -> Option<f64> {
+        self.non_fatal_time.filter(|&x| x <= window && x <= self.censoring_time)
+    }
+
+    /// The record as it would have been observed with follow-up ending at time `c`.
+    pub fn truncated(&self, c: f64) -> Self {
+        Self {
+            fatal_time: self.fatal_time_within(c),
+            non_fatal_time: self.non_fatal_time_within(c),
+            censoring_time: self.censoring_time.min(c),
+        }
+    }
+}
+
+/// The category a treatment-control pair falls into under the Pocock hierarchical comparison.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PairOutcome {
+    /// Category a: the treatment patient dies first (a loss).
+    TreatmentDiesFirst,
+    /// Category b: the control patient dies first (a win).
+    ControlDiesFirst,
+    /// Category c: death is not comparable and the treatment patient has the non-fatal event first (a loss).
+    TreatmentEventFirst,
+    /// Category d: death is not comparable and the control patient has the non-fatal event first (a win).
+    ControlEventFirst,
+    /// Neither outcome separates the pair.
+    Tie,
+}
+
+impl PairOutcome {
+    /// Returns `true` if the pair is a win for the treatment patient.
+    pub fn is_win(&self) -> bool {
+        matches!(self, PairOutcome::ControlDiesFirst | PairOutcome::ControlEventFirst)
+    }
+
+    /// Returns `true` if the pair is a loss for the treatment patient.
+    pub fn is_loss(&self) -> bool {
+        matches!(self, PairOutcome::TreatmentDiesFirst | PairOutcome::TreatmentEventFirst)
+    }
+}
+
+/// Win-loss counts from patient-level comparisons, together with the number of tied pairs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HierarchicalCounts {
+    /// Counts of the pairs in categories a, b, c and d.
+    pub counts: WinLossCounts,
+    /// Number of pairs that could not be separated on either outcome.
+    pub n_ties: u32,
+}
+
+impl HierarchicalCounts {
+    /// Adds a single pair outcome to the counts.
+    pub fn record(&mut self, outcome: PairOutcome) {
+        match outcome {
+            PairOutcome::TreatmentDiesFirst => self.counts.n_a += 1,
+            PairOutcome::ControlDiesFirst => self.counts.n_b += 1,
+            PairOutcome::TreatmentEventFirst => self.counts.n_c += 1,
+            PairOutcome::ControlEventFirst => self.counts.n_d += 1,
+            PairOutcome::Tie => self.n_ties += 1,
+        }
+    }
+
+    /// Total number of pairs compared, including ties.
+    pub fn n_pairs(&self) -> u32 {
+        self.counts.n_wins() + self.counts.n_losses() + self.n_ties
+    }
+}
+
+/// Compares a treatment patient with a control patient using the Pocock hierarchy.
+///
+/// Both patients are compared over their shared follow-up window, `[0, min(C1, C0)]`.
+/// The fatal event is compared first; whoever has it first within the window loses.
+/// If neither patient has the fatal event in the window (or both have it at the same time),
+/// the non-fatal event is compared in the same way. Otherwise the pair is a tie.
+///
+/// ## Parameters
+///
+/// * `treatment`: The patient from the treatment group.
+/// * `control`: The patient from the control group.
+///
+/// ## Returns
+///
+/// The `PairOutcome` describing which category the pair falls into.
+pub fn compare_patients(treatment: &PatientRecord, control: &PatientRecord) -> PairOutcome {
+    let mut window = treatment.censoring_time.min(control.censoring_time);
+
+    match (treatment.fatal_time_within(window), control.fatal_time_within(window)) {
+        (Some(t1), Some(t0)) if t1 < t0 => return PairOutcome::TreatmentDiesFirst,
+        (Some(t1), Some(t0)) if t0 < t1 => return PairOutcome::ControlDiesFirst,
+        // Simultaneous deaths: the non-fatal event can only separate the pair before that time.
+        (Some(t1), Some(_)) => window = t1,
+        (Some(_), None) => return PairOutcome::TreatmentDiesFirst,
+        (None, Some(_)) => return PairOutcome::ControlDiesFirst,
+        (None, None) => {}
+    }
+
+    match (treatment.non_fatal_time_within(window), control.non_fatal_time_within(window)) {
+        (Some(x1), Some(x0)) if x1 < x0 => PairOutcome::TreatmentEventFirst,
+        (Some(x1), Some(x0)) if x0 < x1 => PairOutcome::ControlEventFirst,
+        (Some(_), None) => PairOutcome::TreatmentEventFirst,
+        (None, Some(_)) => PairOutcome::ControlEventFirst,
+        _ => PairOutcome::Tie,
+    }
+}
+
+/// Counts wins, losses and ties over matched treatment-control pairs.
+///
+/// ## Parameters
+///
+/// * `pairs`: Matched pairs as `(treatment, control)` tuples.
+///
+/// ## Returns
+///
+/// A `HierarchicalCounts` whose `counts` can be passed to `calculate_sample_win_ratio`.
+pub fn count_matched_pairs(pairs: &[(PatientRecord, PatientRecord)]) -> HierarchicalCounts {
+    let mut result = HierarchicalCounts::default();
+    for (treatment, control) in pairs {
+        result.record(compare_patients(treatment, control));
+    }
+    result
+}
+
+/// Counts wins, losses and ties over all treatment-control pairs of two unmatched arms.
+///
+/// Every treatment patient is compared with every control patient, giving
+/// `treatment.len() * control.len()` comparisons.
+///
+/// ## Parameters
+///
+/// * `treatment`: Patients in the treatment group.
+/// * `control`: Patients in the control group.
+///
+/// ## Returns
+///
+/// A `HierarchicalCounts` whose `counts` can be passed to `calculate_sample_win_ratio`.
+pub fn count_unmatched_pairs(treatment: &[PatientRecord], control: &[PatientRecord]) -> HierarchicalCounts {
+    let mut result = HierarchicalCounts::default();
+    for t in treatment {
+        for c in control {
+            result.record(compare_patients(t, c));
+        }
+    }
+    result
+}
+
 /// Calculates the sample win ratio (R).
 ///
 /// The win ratio is the ratio of the total number of "wins" to the total number of "losses".
@@ -46,8 +223,20 @@ impl WinLossCounts {
 ///
 /// ## Returns
 ///
-/// The sample win ratio as a `f64`. Returns `f64::INFINITY` if the number of losses is zero.
-pub fn calculate_sample_win_ratio(counts: &WinLossCounts) -> f64 {
+/// The sample win ratio as a `f64`, `Error::EmptyData` if there are no wins or losses, or
+/// `Error::NoLosses` if there are wins but no losses.
+pub fn calculate_sample_win_ratio(counts: &WinLossCounts) -> Result<f64> {
+    match (counts.n_wins(), counts.n_losses()) {
+        (0, 0) => Err(Error::EmptyData),
+        (_, 0) => Err(Error::NoLosses),
+        (n_w, n_l) => Ok(n_w as f64 / n_l as f64),
+    }
+}
+
+/// Calculates the sample win ratio, returning `f64::INFINITY` if the number of losses is zero.
+///
+/// See `calculate_sample_win_ratio` for the formula.
+pub fn calculate_sample_win_ratio_lenient(counts: &WinLossCounts) -> f64 {
     let n_w = counts.n_wins() as f64;
     let n_l = counts.n_losses() as f64;
 
@@ -70,8 +259,18 @@ pub fn calculate_sample_win_ratio(counts: &WinLossCounts) -> f64 {
 ///
 /// ## Returns
 ///
-/// The proportion of wins as a `f64`.
-pub fn calculate_win_proportion(n_w: u32, n_l: u32) -> f64 {
+/// The proportion of wins as a `f64`, or `Error::EmptyData` if there are no wins or losses.
+pub fn calculate_win_proportion(n_w: u32, n_l: u32) -> Result<f64> {
+    if n_w + n_l == 0 {
+        return Err(Error::EmptyData);
+    }
+    Ok(calculate_win_proportion_lenient(n_w, n_l))
+}
+
+/// Calculates the proportion of wins, returning 0.0 if there are no wins or losses.
+///
+/// See `calculate_win_proportion` for the formula.
+pub fn calculate_win_proportion_lenient(n_w: u32, n_l: u32) -> f64 {
     let n_w = n_w as f64;
     let n_l = n_l as f64;
     if n_w + n_l == 0.0 {
@@ -95,13 +294,25 @@ pub fn calculate_win_proportion(n_w: u32, n_l: u32) -> f64 {
 ///
 /// ## Returns
 ///
-/// A tuple `(lower_bound, upper_bound)` for the confidence interval.
-pub fn calculate_confidence_interval(n_w: u32, n_l: u32) -> (f64, f64) {
+/// A tuple `(lower_bound, upper_bound)` for the confidence interval, or `Error::EmptyData`
+/// if there are no wins or losses.
+pub fn calculate_confidence_interval(n_w: u32, n_l: u32) -> Result<(f64, f64)> {
+    if n_w + n_l == 0 {
+        return Err(Error::EmptyData);
+    }
+    Ok(calculate_confidence_interval_lenient(n_w, n_l))
+}
+
+/// Calculates the 95% confidence interval for the win ratio, returning `(0, 0)` if there are
+/// no wins or losses.
+///
+/// See `calculate_confidence_interval` for the formula.
+pub fn calculate_confidence_interval_lenient(n_w: u32, n_l: u32) -> (f64, f64) {
     let n_total = (n_w + n_l) as f64;
     if n_total == 0.0 {
         return (0.0, 0.0);
     }
-    let p_w = calculate_win_proportion(n_w, n_l);
+    let p_w = calculate_win_proportion_lenient(n_w, n_l);
 
     let standard_error = (p_w * (1.0 - p_w) / n_total).sqrt();
     let margin_of_error = 1.96 * standard_error;
@@ -115,6 +326,172 @@ pub fn calculate_confidence_interval(n_w: u32, n_l: u32) -> (f64, f64) {
     (lower_bound.max(0.0), upper_bound.max(0.0))
 }
 
+/// Methods for the confidence interval of the win ratio and the matching p-value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfidenceIntervalMethod {
+    /// Wald interval on the win proportion, transformed to the win ratio scale.
+    Wald,
+    /// Wilson score interval on the win proportion, transformed to the win ratio scale.
+    Wilson,
+    /// Clopper–Pearson exact binomial interval on the win proportion, transformed to the win ratio scale.
+    ClopperPearson,
+    /// Delta-method interval on log(R), with Var(log R) = 1/Nw + 1/Nl.
+    LogDelta,
+}
+
+/// Calculates a confidence interval for the win ratio at any confidence level.
+///
+/// ## Formula
+///
+/// The proportion-based methods find an interval [pL, pU] for the win proportion pw and
+/// map it to the win ratio as [pL / (1 - pL), pU / (1 - pU)]:
+///
+/// - Wald: pw ± z * sqrt(pw * (1 - pw) / n), with the bounds kept inside [0, 1].
+/// - Wilson: (pw + z²/2n ± z * sqrt(pw * (1 - pw) / n + z²/4n²)) / (1 + z²/n).
+/// - Clopper–Pearson: pL = B(α/2; Nw, Nl + 1), pU = B(1 - α/2; Nw + 1, Nl), where B is the beta quantile.
+///
+/// The log-delta method uses exp(log R ± z * sqrt(1/Nw + 1/Nl)) directly.
+///
+/// ## Parameters
+///
+/// * `n_w`: Total number of wins.
+/// * `n_l`: Total number of losses.
+/// * `confidence_level`: The confidence level, e.g. `0.95`.
+/// * `method`: The interval method.
+///
+/// ## Returns
+///
+/// A tuple `(lower_bound, upper_bound)`. An upper bound of `f64::INFINITY` means the interval
+/// is unbounded above. Returns `Error::InvalidParameter` if the confidence level is not in
+/// (0, 1), `Error::EmptyData` without any wins or losses, and `Error::NoWins` or
+/// `Error::NoLosses` when the log-delta method meets a zero count.
+pub fn calculate_confidence_interval_with_method(
+    n_w: u32,
+    n_l: u32,
+    confidence_level: f64,
+    method: ConfidenceIntervalMethod,
+) -> Result<(f64, f64)> {
+    if !(confidence_level > 0.0 && confidence_level < 1.0) {
+        return Err(Error::InvalidParameter { name: "confidence_level", value: confidence_level });
+    }
+    match (n_w, n_l, method) {
+        (0, 0, _) => Err(Error::EmptyData),
+        (0, _, ConfidenceIntervalMethod::LogDelta) => Err(Error::NoWins),
+        (_, 0, ConfidenceIntervalMethod::LogDelta) => Err(Error::NoLosses),
+        _ => Ok(calculate_confidence_interval_with_method_lenient(n_w, n_l, confidence_level, method)),
+    }
+}
+
+/// Calculates a confidence interval for the win ratio, returning the uninformative interval
+/// `(0, f64::INFINITY)` without any wins or losses, or when the log-delta method meets a zero
+/// count.
+///
+/// See `calculate_confidence_interval_with_method` for the methods.
+pub fn calculate_confidence_interval_with_method_lenient(
+    n_w: u32,
+    n_l: u32,
+    confidence_level: f64,
+    method: ConfidenceIntervalMethod,
+) -> (f64, f64) {
+    let n_total = (n_w + n_l) as f64;
+    if n_total == 0.0 {
+        return (0.0, f64::INFINITY);
+    }
+    let z = two_sided_critical_value(confidence_level);
+    let alpha = 1.0 - confidence_level;
+    let p_w = calculate_win_proportion_lenient(n_w, n_l);
+    let to_ratio = |p: f64| if p >= 1.0 { f64::INFINITY } else { p / (1.0 - p) };
+
+    let (p_l, p_u) = match method {
+        ConfidenceIntervalMethod::Wald => {
+            let margin_of_error = z * (p_w * (1.0 - p_w) / n_total).sqrt();
+            ((p_w - margin_of_error).max(0.0), (p_w + margin_of_error).min(1.0))
+        }
+        ConfidenceIntervalMethod::Wilson => {
+            let z2 = z * z;
+            let denominator = 1.0 + z2 / n_total;
+            let center = (p_w + z2 / (2.0 * n_total)) / denominator;
+            let half_width = z * (p_w * (1.0 - p_w) / n_total + z2 / (4.0 * n_total * n_total)).sqrt() / denominator;
+            ((center - half_width).max(0.0), (center + half_width).min(1.0))
+        }
+        ConfidenceIntervalMethod::ClopperPearson => {
+            let lower = if n_w == 0 { 0.0 } else { beta_quantile(alpha / 2.0, n_w as f64, n_l as f64 + 1.0) };
+            let upper = if n_l == 0 { 1.0 } else { beta_quantile(1.0 - alpha / 2.0, n_w as f64 + 1.0, n_l as f64) };
+            (lower, upper)
+        }
+        ConfidenceIntervalMethod::LogDelta => {
+            if n_w == 0 || n_l == 0 {
+                return (0.0, f64::INFINITY);
+            }
+            let log_ratio = (n_w as f64 / n_l as f64).ln();
+            let standard_error = (1.0 / n_w as f64 + 1.0 / n_l as f64).sqrt();
+            return ((log_ratio - z * standard_error).exp(), (log_ratio + z * standard_error).exp());
+        }
+    };
+
+    (to_ratio(p_l), to_ratio(p_u))
+}
+
+/// Calculates the two-sided p-value for the null hypothesis R = 1 (equivalently pw = 0.5).
+///
+/// ## Formula
+///
+/// - Wald: the `calculate_significance_test_statistic` z-statistic.
+/// - Wilson: the score statistic (pw - 0.5) / sqrt(0.25 / (Nw + Nl)).
+/// - Clopper–Pearson: the exact binomial test, 2 * P(X <= min(Nw, Nl)) for X ~ Bin(Nw + Nl, 0.5).
+/// - LogDelta: log(R) / sqrt(1/Nw + 1/Nl).
+///
+/// ## Parameters
+///
+/// * `n_w`: Total number of wins.
+/// * `n_l`: Total number of losses.
+/// * `method`: The method whose test matches the interval.
+///
+/// ## Returns
+///
+/// The p-value as a `f64`. Returns `Error::EmptyData` when there are no wins or losses,
+/// `Error::ZeroVariance` for the Wald method when either count is zero, because its variance
+/// estimate vanishes, and `Error::NoWins` or `Error::NoLosses` for the log-delta method when
+/// either count is zero.
+pub fn calculate_p_value(n_w: u32, n_l: u32, method: ConfidenceIntervalMethod) -> Result<f64> {
+    match (n_w, n_l, method) {
+        (0, 0, _) => Err(Error::EmptyData),
+        (0, _, ConfidenceIntervalMethod::Wald) | (_, 0, ConfidenceIntervalMethod::Wald) => Err(Error::ZeroVariance),
+        (0, _, ConfidenceIntervalMethod::LogDelta) => Err(Error::NoWins),
+        (_, 0, ConfidenceIntervalMethod::LogDelta) => Err(Error::NoLosses),
+        _ => Ok(calculate_p_value_lenient(n_w, n_l, method)),
+    }
+}
+
+/// Calculates the two-sided p-value for R = 1, returning 1.0 when there are no wins or
+/// losses and `f64::NAN` for the log-delta method when either count is zero.
+///
+/// For the Wald method all wins or all losses give a zero statistic and so a p-value of 1.0,
+/// although the data are as extreme as possible; use another method for such counts.
+///
+/// See `calculate_p_value` for the tests.
+pub fn calculate_p_value_lenient(n_w: u32, n_l: u32, method: ConfidenceIntervalMethod) -> f64 {
+    let n_total = n_w + n_l;
+    if n_total == 0 {
+        return 1.0;
+    }
+    match method {
+        ConfidenceIntervalMethod::Wald => two_sided_p_value(calculate_significance_test_statistic_lenient(n_w, n_l)),
+        ConfidenceIntervalMethod::Wilson => {
+            let p_w = calculate_win_proportion_lenient(n_w, n_l);
+            two_sided_p_value((p_w - 0.5) / (0.25 / n_total as f64).sqrt())
+        }
+        ConfidenceIntervalMethod::ClopperPearson => (2.0 * binomial_cdf(n_w.min(n_l), n_total, 0.5)).min(1.0),
+        ConfidenceIntervalMethod::LogDelta => {
+            if n_w == 0 || n_l == 0 {
+                return f64::NAN;
+            }
+            let log_ratio = (n_w as f64 / n_l as f64).ln();
+            two_sided_p_value(log_ratio / (1.0 / n_w as f64 + 1.0 / n_l as f64).sqrt())
+        }
+    }
+}
+
 /// Calculates the significance test statistic.
 ///
 /// This statistic has an asymptotic standardized normal distribution under the null hypothesis.
@@ -130,13 +507,25 @@ pub fn calculate_confidence_interval(n_w: u32, n_l: u32) -> (f64, f64) {
 ///
 /// ## Returns
 ///
-/// The significance test statistic as a `f64`.
-pub fn calculate_significance_test_statistic(n_w: u32, n_l: u32) -> f64 {
+/// The significance test statistic as a `f64`. Returns `Error::EmptyData` if there are no
+/// wins or losses, and `Error::ZeroVariance` if all decided pairs are wins or all are losses.
+pub fn calculate_significance_test_statistic(n_w: u32, n_l: u32) -> Result<f64> {
+    match (n_w, n_l) {
+        (0, 0) => Err(Error::EmptyData),
+        (0, _) | (_, 0) => Err(Error::ZeroVariance),
+        _ => Ok(calculate_significance_test_statistic_lenient(n_w, n_l)),
+    }
+}
+
+/// Calculates the significance test statistic, returning 0.0 when it is undefined.
+///
+/// See `calculate_significance_test_statistic` for the formula.
+pub fn calculate_significance_test_statistic_lenient(n_w: u32, n_l: u32) -> f64 {
     let n_total = (n_w + n_l) as f64;
     if n_total == 0.0 {
         return 0.0;
     }
-    let p_w = calculate_win_proportion(n_w, n_l);
+    let p_w = calculate_win_proportion_lenient(n_w, n_l);
 
     let denominator = (p_w * (1.0 - p_w) / n_total).sqrt();
     if denominator == 0.0 {
@@ -2,10 +2,20 @@
 //!
 //! This module implements the formulas used in the paper's simulation study.
 //! It includes joint survival functions, the win ratio parameter, and derived
-//! marginal and conditional survival functions and their PDFs.
+//! marginal and conditional survival functions and their PDFs. It also provides a
+//! Monte Carlo sampler that generates trial data from the same model.
+
+use rand::Rng;
+
+use crate::error::{non_negative, positive, Error, Result};
+
+use super::distributions::{sample_positive_stable, sample_standard_exponential};
+use super::sample_win_ratio::{
+    calculate_sample_win_ratio_lenient, count_matched_pairs, count_unmatched_pairs, PatientRecord,
+};
 
 /// Parameters for the simulation study.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct SimulationParams {
     /// Lambda1 parameter.
     pub lambda1: f64,
@@ -56,7 +66,14 @@ pub fn marginal_survival_t_treatment(t: f64, params: &SimulationParams) -> f64 {
 }
 
 /// PDF for T in the control group, f0(t).
-pub fn pdf_t_control(t: f64, params: &SimulationParams) -> f64 {
+///
+/// Returns `Error::InvalidParameter` if `t` is negative or not finite.
+pub fn pdf_t_control(t: f64, params: &SimulationParams) -> Result<f64> {
+    Ok(pdf_t_control_lenient(non_negative("t", t)?, params))
+}
+
+/// PDF for T in the control group, returning 0.0 for negative times.
+pub fn pdf_t_control_lenient(t: f64, params: &SimulationParams) -> f64 {
     if t < 0.0 { return 0.0; }
     if t == 0.0 { return 0.0; } // Handle t=0 case for t.powf(alpha - 1.0)
     let l1_alpha = params.lambda1.powf(params.alpha);
@@ -64,7 +81,14 @@ pub fn pdf_t_control(t: f64, params: &SimulationParams) -> f64 {
 }
 
 /// PDF for T in the treatment group, f1(t).
-pub fn pdf_t_treatment(t: f64, params: &SimulationParams) -> f64 {
+///
+/// Returns `Error::InvalidParameter` if `t` is negative or not finite.
+pub fn pdf_t_treatment(t: f64, params: &SimulationParams) -> Result<f64> {
+    Ok(pdf_t_treatment_lenient(non_negative("t", t)?, params))
+}
+
+/// PDF for T in the treatment group, returning 0.0 for negative times.
+pub fn pdf_t_treatment_lenient(t: f64, params: &SimulationParams) -> f64 {
     if t < 0.0 { return 0.0; }
     if t == 0.0 { return 0.0; }
     let th_l1_alpha = (params.theta * params.lambda1).powf(params.alpha);
@@ -89,7 +113,14 @@ pub fn conditional_survival_x_given_t_treatment(x: f64, c: f64, params: &Simulat
 }
 
 /// Conditional PDF for X in the control group, f0(x|c).
-pub fn pdf_x_given_t_control(x: f64, c: f64, params: &SimulationParams) -> f64 {
+///
+/// Returns `Error::InvalidParameter` if `x` or `c` is negative or not finite.
+pub fn pdf_x_given_t_control(x: f64, c: f64, params: &SimulationParams) -> Result<f64> {
+    Ok(pdf_x_given_t_control_lenient(non_negative("x", x)?, non_negative("c", c)?, params))
+}
+
+/// Conditional PDF for X in the control group, returning 0.0 for negative times.
+pub fn pdf_x_given_t_control_lenient(x: f64, c: f64, params: &SimulationParams) -> f64 {
     if x < 0.0 { return 0.0; }
     let term = params.lambda1 * c + params.lambda2 * x;
     if term < 0.0 { return 0.0; }
@@ -98,10 +129,328 @@ pub fn pdf_x_given_t_control(x: f64, c: f64, params: &SimulationParams) -> f64 {
 }
 
 /// Conditional PDF for X in the treatment group, f1(x|c).
-pub fn pdf_x_given_t_treatment(x: f64, c: f64, params: &SimulationParams) -> f64 {
+///
+/// Returns `Error::InvalidParameter` if `x` or `c` is negative or not finite.
+pub fn pdf_x_given_t_treatment(x: f64, c: f64, params: &SimulationParams) -> Result<f64> {
+    Ok(pdf_x_given_t_treatment_lenient(non_negative("x", x)?, non_negative("c", c)?, params))
+}
+
+/// Conditional PDF for X in the treatment group, returning 0.0 for negative times.
+pub fn pdf_x_given_t_treatment_lenient(x: f64, c: f64, params: &SimulationParams) -> f64 {
     if x < 0.0 { return 0.0; }
     let term = params.theta * params.lambda1 * c + params.theta * params.lambda2 * x;
     if term < 0.0 { return 0.0; }
     let g1 = conditional_survival_x_given_t_treatment(x, c, params);
     params.alpha * params.theta * params.lambda2 * term.powf(params.alpha - 1.0) * g1
 }
+
+
+// --- Monte Carlo Sampling ---
+
+/// Study arm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arm {
+    /// The control group (group 0).
+    Control,
+    /// The treatment group (group 1).
+    Treatment,
+}
+
+impl SimulationParams {
+    /// The hazard multiplier of an arm: 1 for control and θ for treatment.
+    pub fn rate_multiplier(&self, arm: Arm) -> f64 {
+        match arm {
+            Arm::Control => 1.0,
+            Arm::Treatment => self.theta,
+        }
+    }
+}
+
+/// Draws latent event times (T, X) for one patient from the joint survival function of `arm`.
+///
+/// The Gumbel–Hougaard form exp[-(λ1 t + λ2 x)^α] is the Laplace transform of a positive
+/// stable frailty. Given Z with E[exp(-sZ)] = exp(-s^α), T and X are independent exponentials
+/// with rates θλ1Z and θλ2Z, so P(T > t, X > x) = E[exp(-Zθ(λ1 t + λ2 x))] = S(t, x).
+///
+/// ## Returns
+///
+/// A tuple `(t, x)` of the fatal and non-fatal event times, before any censoring, or
+/// `Error::InvalidParameter` if the parameters are invalid (see `JointSurvivalModel::validate`).
+pub fn sample_event_times<R: Rng + ?Sized>(params: &SimulationParams, arm: Arm, rng: &mut R) -> Result<(f64, f64)> {
+    params.validate()?;
+    Ok(sample_event_times_lenient(params, arm, rng))
+}
+
+/// Draws latent event times (T, X) for one patient without checking the parameters.
+pub fn sample_event_times_lenient<R: Rng + ?Sized>(params: &SimulationParams, arm: Arm, rng: &mut R) -> (f64, f64) {
+    let frailty = sample_positive_stable(params.alpha, rng);
+    let scale = params.rate_multiplier(arm) * frailty;
+    let t = sample_standard_exponential(rng) / (params.lambda1 * scale);
+    let x = sample_standard_exponential(rng) / (params.lambda2 * scale);
+    (t, x)
+}
+
+/// A joint model of the fatal event time T and the non-fatal event time X in each arm.
+///
+/// `SimulationParams` implements the Gumbel–Hougaard model of the paper; the `copula` module
+/// provides other dependence structures. Everything that needs the model, such as
+/// `probability_win_ratio::model_probability_win_ratio` and `simulate_trial`, is generic
+/// over this trait.
+pub trait JointSurvivalModel {
+    /// Joint survival function of `arm`, S(t, x) = P(T > t, X > x).
+    fn joint_survival(&self, arm: Arm, t: f64, x: f64) -> f64;
+
+    /// PDF of the fatal event time T in `arm`.
+    fn pdf_t(&self, arm: Arm, t: f64) -> f64;
+
+    /// Conditional PDF of X given T > c in `arm`, f(x|c) = -d/dx G(x|c).
+    fn pdf_x_given_t(&self, arm: Arm, x: f64, c: f64) -> f64;
+
+    /// Draws latent event times `(t, x)` for one patient of `arm`, before any censoring.
+    fn sample_event_times<R: Rng + ?Sized>(&self, arm: Arm, rng: &mut R) -> (f64, f64);
+
+    /// Checks the parameters of the model before it is simulated from or integrated.
+    ///
+    /// The default implementation accepts every model.
+    fn validate(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Marginal survival function of T in `arm`, S(t) = S(t, 0).
+    fn marginal_survival_t(&self, arm: Arm, t: f64) -> f64 {
+        self.joint_survival(arm, t, 0.0)
+    }
+
+    /// Conditional survival function of X given T > c in `arm`, G(x|c) = S(c, x) / S(c, 0).
+    fn conditional_survival_x_given_t(&self, arm: Arm, x: f64, c: f64) -> f64 {
+        let s_c_0 = self.marginal_survival_t(arm, c);
+        if s_c_0 == 0.0 { 0.0 } else { self.joint_survival(arm, c, x) / s_c_0 }
+    }
+}
+
+impl JointSurvivalModel for SimulationParams {
+    fn joint_survival(&self, arm: Arm, t: f64, x: f64) -> f64 {
+        match arm {
+            Arm::Control => joint_survival_function_control(t, x, self),
+            Arm::Treatment => joint_survival_function_treatment(t, x, self),
+        }
+    }
+
+    fn pdf_t(&self, arm: Arm, t: f64) -> f64 {
+        match arm {
+            Arm::Control => pdf_t_control_lenient(t, self),
+            Arm::Treatment => pdf_t_treatment_lenient(t, self),
+        }
+    }
+
+    fn pdf_x_given_t(&self, arm: Arm, x: f64, c: f64) -> f64 {
+        match arm {
+            Arm::Control => pdf_x_given_t_control_lenient(x, c, self),
+            Arm::Treatment => pdf_x_given_t_treatment_lenient(x, c, self),
+        }
+    }
+
+    fn sample_event_times<R: Rng + ?Sized>(&self, arm: Arm, rng: &mut R) -> (f64, f64) {
+        sample_event_times_lenient(self, arm, rng)
+    }
+
+    /// Requires positive, finite λ1, λ2 and θ, and α in (0, 1].
+    fn validate(&self) -> Result<()> {
+        positive("lambda1", self.lambda1)?;
+        positive("lambda2", self.lambda2)?;
+        positive("theta", self.theta)?;
+        if self.alpha > 0.0 && self.alpha <= 1.0 {
+            Ok(())
+        } else {
+            Err(Error::InvalidParameter { name: "alpha", value: self.alpha })
+        }
+    }
+}
+
+/// Censoring mechanism of a simulated trial.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CensoringDesign {
+    /// Administrative censoring time c: the end of the study.
+    pub follow_up: f64,
+    /// Rate of exponential random censoring (loss to follow-up). Zero disables it.
+    pub dropout_rate: f64,
+}
+
+impl CensoringDesign {
+    /// Creates a new `CensoringDesign`.
+    pub fn new(follow_up: f64, dropout_rate: f64) -> Self {
+        Self { follow_up, dropout_rate }
+    }
+
+    /// Administrative censoring only, at time `follow_up`.
+    pub fn administrative(follow_up: f64) -> Self {
+        Self::new(follow_up, 0.0)
+    }
+
+    /// Checks that the follow-up time and the dropout rate are finite and non-negative.
+    pub fn validate(&self) -> Result<()> {
+        non_negative("follow_up", self.follow_up)?;
+        non_negative("dropout_rate", self.dropout_rate)?;
+        Ok(())
+    }
+}
+
+/// Simulates the observed follow-up of one patient.
+///
+/// The censoring time is the earlier of the administrative time and an exponential dropout
+/// time. The fatal event is observed if it occurs before censoring; the non-fatal event is
+/// observed if it occurs before both censoring and the fatal event.
+///
+/// Returns `Error::InvalidParameter` if the model or the censoring design is invalid.
+pub fn sample_patient<M: JointSurvivalModel, R: Rng + ?Sized>(
+    model: &M,
+    arm: Arm,
+    design: &CensoringDesign,
+    rng: &mut R,
+) -> Result<PatientRecord> {
+    model.validate()?;
+    design.validate()?;
+    Ok(sample_patient_lenient(model, arm, design, rng))
+}
+
+/// Simulates the observed follow-up of one patient without checking the model or the design.
+pub fn sample_patient_lenient<M: JointSurvivalModel, R: Rng + ?Sized>(
+    model: &M,
+    arm: Arm,
+    design: &CensoringDesign,
+    rng: &mut R,
+) -> PatientRecord {
+    let (t, x) = model.sample_event_times(arm, rng);
+    let mut censoring_time = design.follow_up;
+    if design.dropout_rate > 0.0 {
+        censoring_time = censoring_time.min(sample_standard_exponential(rng) / design.dropout_rate);
+    }
+    PatientRecord::new(
+        Some(t).filter(|&t| t <= censoring_time),
+        Some(x).filter(|&x| x <= censoring_time && x < t),
+        censoring_time,
+    )
+}
+
+/// Patient-level data of a simulated two-arm trial.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulatedTrial {
+    /// Patients in the treatment arm.
+    pub treatment: Vec<PatientRecord>,
+    /// Patients in the control arm.
+    pub control: Vec<PatientRecord>,
+}
+
+impl SimulatedTrial {
+    /// Pairs the i-th treatment patient with the i-th control patient.
+    pub fn matched_pairs(&self) -> Vec<(PatientRecord, PatientRecord)> {
+        self.treatment.iter().copied().zip(self.control.iter().copied()).collect()
+    }
+}
+
+/// Simulates a two-arm trial.
+///
+/// ## Parameters
+///
+/// * `model`: The joint survival model, e.g. `SimulationParams` or a `copula::CopulaModel`.
+/// * `design`: The censoring mechanism.
+/// * `n_treatment`: Number of patients in the treatment arm.
+/// * `n_control`: Number of patients in the control arm.
+/// * `rng`: The random number generator; seed it for reproducible results.
+///
+/// ## Returns
+///
+/// The simulated patients, or `Error::InvalidParameter` if the model or the censoring design
+/// is invalid.
+pub fn simulate_trial<M: JointSurvivalModel, R: Rng + ?Sized>(
+    model: &M,
+    design: &CensoringDesign,
+    n_treatment: usize,
+    n_control: usize,
+    rng: &mut R,
+) -> Result<SimulatedTrial> {
+    model.validate()?;
+    design.validate()?;
+    Ok(simulate_trial_lenient(model, design, n_treatment, n_control, rng))
+}
+
+/// Simulates a two-arm trial without checking the model or the censoring design.
+pub fn simulate_trial_lenient<M: JointSurvivalModel, R: Rng + ?Sized>(
+    model: &M,
+    design: &CensoringDesign,
+    n_treatment: usize,
+    n_control: usize,
+    rng: &mut R,
+) -> SimulatedTrial {
+    SimulatedTrial {
+        treatment: (0..n_treatment).map(|_| sample_patient_lenient(model, Arm::Treatment, design, rng)).collect(),
+        control: (0..n_control).map(|_| sample_patient_lenient(model, Arm::Control, design, rng)).collect(),
+    }
+}
+
+/// Summary of a Monte Carlo simulation study.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulationStudySummary {
+    /// Sample win ratio of each replicate from the matched-pairs approach.
+    pub matched_win_ratios: Vec<f64>,
+    /// Sample win ratio of each replicate from all treatment-control pairs.
+    pub unmatched_win_ratios: Vec<f64>,
+    /// The win ratio parameter in the absence of censoring, PR_W = 1 / θ^α.
+    pub theoretical_win_ratio: f64,
+}
+
+impl SimulationStudySummary {
+    /// Geometric mean of the finite win ratios, exp(mean(log R)).
+    pub fn geometric_mean(win_ratios: &[f64]) -> f64 {
+        let logs: Vec<f64> = win_ratios.iter().map(|r| r.ln()).filter(|l| l.is_finite()).collect();
+        (logs.iter().sum::<f64>() / logs.len() as f64).exp()
+    }
+}
+
+/// Runs the simulation study: repeatedly simulates trials and computes the sample win ratio.
+///
+/// ## Parameters
+///
+/// * `params`: The joint survival model.
+/// * `design`: The censoring mechanism.
+/// * `n_per_arm`: Number of patients in each arm (and of matched pairs).
+/// * `n_replicates`: Number of simulated trials.
+/// * `rng`: The random number generator; seed it for reproducible results.
+///
+/// ## Returns
+///
+/// The win ratios of every replicate, or `Error::InvalidParameter` if the model or the
+/// censoring design is invalid.
+pub fn run_simulation_study<R: Rng + ?Sized>(
+    params: &SimulationParams,
+    design: &CensoringDesign,
+    n_per_arm: usize,
+    n_replicates: usize,
+    rng: &mut R,
+) -> Result<SimulationStudySummary> {
+    params.validate()?;
+    design.validate()?;
+    Ok(run_simulation_study_lenient(params, design, n_per_arm, n_replicates, rng))
+}
+
+/// Runs the simulation study without checking the model or the censoring design.
+pub fn run_simulation_study_lenient<R: Rng + ?Sized>(
+    params: &SimulationParams,
+    design: &CensoringDesign,
+    n_per_arm: usize,
+    n_replicates: usize,
+    rng: &mut R,
+) -> SimulationStudySummary {
+    let mut matched_win_ratios = Vec::with_capacity(n_replicates);
+    let mut unmatched_win_ratios = Vec::with_capacity(n_replicates);
+    for _ in 0..n_replicates {
+        let trial = simulate_trial_lenient(params, design, n_per_arm, n_per_arm, rng);
+        matched_win_ratios.push(calculate_sample_win_ratio_lenient(&count_matched_pairs(&trial.matched_pairs()).counts));
+        unmatched_win_ratios.push(calculate_sample_win_ratio_lenient(&count_unmatched_pairs(&trial.treatment, &trial.control).counts));
+    }
+    SimulationStudySummary {
+        matched_win_ratios,
+        unmatched_win_ratios,
+        theoretical_win_ratio: win_ratio_parameter(params),
+    }
+}
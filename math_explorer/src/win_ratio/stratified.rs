@@ -0,0 +1,208 @@
+//! # Stratified Win Ratio
+//!
+//! Combines per-stratum win-loss comparisons into a single win ratio. Patients are only
+//! compared with patients from the same stratum (e.g. site or baseline risk group), and
+//! the strata are pooled with Mantel–Haenszel-type or inverse-variance weights.
+
+use crate::error::{self, Error, Result};
+
+use super::distributions::chi_squared_survival;
+use super::sample_win_ratio::{count_unmatched_pairs, HierarchicalCounts, PatientRecord};
+use super::unmatched::{pairwise_probabilities_lenient, PairwiseProbabilities, WinStatistic};
+
+/// The patients of a single stratum.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Stratum {
+    /// Patients in the treatment arm.
+    pub treatment: Vec<PatientRecord>,
+    /// Patients in the control arm.
+    pub control: Vec<PatientRecord>,
+}
+
+impl Stratum {
+    /// Creates a new `Stratum`.
+    pub fn new(treatment: Vec<PatientRecord>, control: Vec<PatientRecord>) -> Self {
+        Self { treatment, control }
+    }
+}
+
+/// How strata are weighted when they are combined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StratumWeighting {
+    /// Mantel–Haenszel-type weights, n1 * n0 / (n1 + n0), applied to the win and loss
+    /// probabilities before taking their ratio.
+    MantelHaenszel,
+    /// Inverse-variance weights applied to the per-stratum log win ratios.
+    InverseVariance,
+}
+
+/// Per-stratum results.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StratumResult {
+    /// Win-loss counts over all treatment-control pairs in the stratum.
+    pub counts: HierarchicalCounts,
+    /// Win and loss probabilities with their U-statistic covariance.
+    pub probabilities: PairwiseProbabilities,
+    /// Normalized weight of the stratum in the pooled estimate. Strata that cannot
+    /// contribute (no pairs, or an undefined log win ratio for inverse-variance weighting)
+    /// have weight zero.
+    pub weight: f64,
+}
+
+/// Cochran's Q test for homogeneity of the log win ratio across strata.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HomogeneityTest {
+    /// Cochran's Q statistic, Σ (log R_k - log R)² / Var(log R_k).
+    pub statistic: f64,
+    /// Number of strata with a defined log win ratio, minus one.
+    pub degrees_of_freedom: usize,
+    /// Upper tail probability of the chi-squared distribution.
+    pub p_value: f64,
+}
+
+/// Result of a stratified win ratio analysis.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StratifiedWinRatio {
+    /// Per-stratum results, in the order the strata were given.
+    pub strata: Vec<StratumResult>,
+    /// The pooled win ratio with its confidence interval.
+    pub win_ratio: WinStatistic,
+    /// Test of homogeneity of the win ratio across strata.
+    pub homogeneity: HomogeneityTest,
+}
+
+/// Calculates the stratified win ratio.
+///
+/// ## Formula
+///
+/// With Mantel–Haenszel-type weights w_k = n1k * n0k / (n1k + n0k):
+///
+/// R = Σ w_k W_k / Σ w_k L_k
+///
+/// where W_k and L_k are the win and loss probabilities in stratum k. The variance of log R
+/// follows from the delta method using the per-stratum U-statistic covariances.
+///
+/// With inverse-variance weights, log R = Σ v_k log R_k / Σ v_k where v_k = 1 / Var(log R_k),
+/// and Var(log R) = 1 / Σ v_k.
+///
+/// ## Parameters
+///
+/// * `strata`: The patients of each stratum.
+/// * `weighting`: How the strata are combined.
+/// * `confidence_level`: The confidence level of the interval, e.g. `0.95`.
+///
+/// ## Returns
+///
+/// A `StratifiedWinRatio` with the per-stratum results, pooled estimate and homogeneity test,
+/// or `Error::InvalidParameter` if `confidence_level` is outside [0, 1], `Error::EmptyData`
+/// if no pair in any stratum is decided, or with inverse-variance weights if no stratum has
+/// a finite log win ratio with positive variance, `Error::NoLosses` if the pooled win ratio
+/// is unbounded and `Error::NoWins` if it is zero.
+pub fn stratified_win_ratio(
+    strata: &[Stratum],
+    weighting: StratumWeighting,
+    confidence_level: f64,
+) -> Result<StratifiedWinRatio> {
+    error::probability("confidence_level", confidence_level)?;
+    let result = stratified_win_ratio_lenient(strata, weighting, confidence_level);
+    let estimate = result.win_ratio.estimate;
+    if estimate.is_nan() {
+        Err(Error::EmptyData)
+    } else if estimate.is_infinite() {
+        Err(Error::NoLosses)
+    } else if estimate == 0.0 {
+        Err(Error::NoWins)
+    } else {
+        Ok(result)
+    }
+}
+
+/// Calculates the stratified win ratio, returning a NaN pooled estimate where
+/// `stratified_win_ratio` returns `Error::EmptyData`, an infinite one if the pooled loss
+/// probability is zero and zero if the pooled win probability is zero.
+pub fn stratified_win_ratio_lenient(
+    strata: &[Stratum],
+    weighting: StratumWeighting,
+    confidence_level: f64,
+) -> StratifiedWinRatio {
+    let mut results: Vec<StratumResult> = strata
+        .iter()
+        .map(|stratum| StratumResult {
+            counts: count_unmatched_pairs(&stratum.treatment, &stratum.control),
+            probabilities: pairwise_probabilities_lenient(&stratum.treatment, &stratum.control),
+            weight: 0.0,
+        })
+        .collect();
+
+    let has_pairs = |r: &StratumResult| r.probabilities.n_treatment > 0 && r.probabilities.n_control > 0;
+    let has_log_ratio = |r: &StratumResult| {
+        has_pairs(r) && r.probabilities.win_ratio_lenient().ln().is_finite() && r.probabilities.log_win_ratio_variance() > 0.0
+    };
+
+    let win_ratio = match weighting {
+        StratumWeighting::MantelHaenszel => {
+            let mut total_weight = 0.0;
+            for result in results.iter_mut().filter(|r| has_pairs(r)) {
+                let n1 = result.probabilities.n_treatment as f64;
+                let n0 = result.probabilities.n_control as f64;
+                result.weight = n1 * n0 / (n1 + n0);
+                total_weight += result.weight;
+            }
+
+            let (mut wins, mut losses) = (0.0, 0.0);
+            let (mut win_variance, mut loss_variance, mut covariance) = (0.0, 0.0, 0.0);
+            for result in results.iter_mut().filter(|r| r.weight > 0.0) {
+                result.weight /= total_weight;
+                let w = result.weight;
+                let p = &result.probabilities;
+                wins += w * p.win_probability;
+                losses += w * p.loss_probability;
+                win_variance += w * w * p.win_variance;
+                loss_variance += w * w * p.loss_variance;
+                covariance += w * w * p.covariance;
+            }
+
+            let log_variance = win_variance / (wins * wins) + loss_variance / (losses * losses)
+                - 2.0 * covariance / (wins * losses);
+            WinStatistic::from_log_scale(wins / losses, log_variance, confidence_level)
+        }
+        StratumWeighting::InverseVariance => {
+            let mut total_weight = 0.0;
+            for result in results.iter_mut().filter(|r| has_log_ratio(r)) {
+                result.weight = 1.0 / result.probabilities.log_win_ratio_variance();
+                total_weight += result.weight;
+            }
+
+            if total_weight > 0.0 {
+                let mut log_ratio = 0.0;
+                for result in results.iter_mut().filter(|r| r.weight > 0.0) {
+                    log_ratio += result.weight * result.probabilities.win_ratio_lenient().ln() / total_weight;
+                    result.weight /= total_weight;
+                }
+                WinStatistic::from_log_scale(log_ratio.exp(), 1.0 / total_weight, confidence_level)
+            } else {
+                // No stratum has a defined log win ratio, so there is nothing to pool.
+                WinStatistic::from_log_scale(f64::NAN, f64::NAN, confidence_level)
+            }
+        }
+    };
+
+    let pooled_log_ratio = win_ratio.estimate.ln();
+    let defined: Vec<&StratumResult> = results.iter().filter(|r| has_log_ratio(r)).collect();
+    let statistic: f64 = defined
+        .iter()
+        .map(|r| (r.probabilities.win_ratio_lenient().ln() - pooled_log_ratio).powi(2) / r.probabilities.log_win_ratio_variance())
+        .sum();
+    let degrees_of_freedom = defined.len().saturating_sub(1);
+    let p_value = if degrees_of_freedom == 0 {
+        1.0
+    } else {
+        chi_squared_survival(statistic, degrees_of_freedom as f64)
+    };
+
+    StratifiedWinRatio {
+        strata: results,
+        win_ratio,
+        homogeneity: HomogeneityTest { statistic, degrees_of_freedom, p_value },
+    }
+}
@@ -0,0 +1,193 @@
+//! # Time-Dependent Win Ratio
+//!
+//! Win and loss probabilities evaluated over a grid of follow-up times c, so the evolution
+//! of the win ratio over the course of a trial can be inspected. Curves can be computed
+//! from the simulation model or empirically from patient data, where each point is the
+//! all-pairs analysis of the data truncated at c with a pointwise confidence band.
+
+use std::fmt::Write;
+
+use crate::error::{non_negative, Result};
+
+use super::probability_win_ratio::{calculate_probability_win_ratio, model_probability_win_ratio};
+use super::sample_win_ratio::PatientRecord;
+use super::simulation::JointSurvivalModel;
+use super::unmatched::{finkelstein_schoenfeld, finkelstein_schoenfeld_lenient};
+
+/// The win and loss probabilities at a single follow-up time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WinRatioCurvePoint {
+    /// The follow-up time c.
+    pub follow_up: f64,
+    /// The win probability at c.
+    pub win_probability: f64,
+    /// The loss probability at c.
+    pub loss_probability: f64,
+    /// The win ratio at c.
+    pub win_ratio: f64,
+    /// Pointwise confidence interval of the win ratio, if it was estimated from data.
+    pub confidence_interval: Option<(f64, f64)>,
+}
+
+impl WinRatioCurvePoint {
+    /// The probability that a pair is tied at c.
+    pub fn tie_probability(&self) -> f64 {
+        1.0 - self.win_probability - self.loss_probability
+    }
+}
+
+/// The win ratio as a function of follow-up time.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct WinRatioCurve {
+    /// The points of the curve, in the order of the follow-up times given.
+    pub points: Vec<WinRatioCurvePoint>,
+}
+
+impl WinRatioCurve {
+    /// Exports the curve as CSV with a header row.
+    ///
+    /// The columns are `follow_up`, `win_probability`, `loss_probability`,
+    /// `tie_probability`, `win_ratio`, `lower` and `upper`. The band columns are empty
+    /// for model-based curves.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("follow_up,win_probability,loss_probability,tie_probability,win_ratio,lower,upper\n");
+        for point in &self.points {
+            let (lower, upper) = match point.confidence_interval {
+                Some((lower, upper)) => (lower.to_string(), upper.to_string()),
+                None => (String::new(), String::new()),
+            };
+            writeln!(
+                csv,
+                "{},{},{},{},{},{},{}",
+                point.follow_up,
+                point.win_probability,
+                point.loss_probability,
+                point.tie_probability(),
+                point.win_ratio,
+                lower,
+                upper
+            )
+            .expect("writing to a String cannot fail");
+        }
+        csv
+    }
+}
+
+/// Evaluates the probability win ratio of a joint survival model on a grid of follow-up times.
+///
+/// ## Parameters
+///
+/// * `model`: The joint survival model, e.g. `SimulationParams` or a `copula::CopulaModel`.
+/// * `follow_up_times`: The follow-up times c at which to evaluate W(c) and L(c).
+/// * `error_tolerance`: The desired error tolerance for numerical integration.
+///
+/// ## Returns
+///
+/// The curve, or `Error::InvalidParameter` if the model is invalid or a follow-up time is
+/// negative or not finite, and `Error::NoLosses` if the loss probability is zero at some
+/// follow-up time, such as c = 0.
+pub fn model_curve<M: JointSurvivalModel>(model: &M, follow_up_times: &[f64], error_tolerance: f64) -> Result<WinRatioCurve> {
+    model.validate()?;
+    let points = follow_up_times
+        .iter()
+        .map(|&c| {
+            let probabilities = model_probability_win_ratio(model, non_negative("follow_up_time", c)?, error_tolerance);
+            Ok(WinRatioCurvePoint {
+                follow_up: c,
+                win_probability: probabilities.win_probability,
+                loss_probability: probabilities.loss_probability,
+                win_ratio: calculate_probability_win_ratio(probabilities.win_probability, probabilities.loss_probability)?,
+                confidence_interval: None,
+            })
+        })
+        .collect::<Result<_>>()?;
+    Ok(WinRatioCurve { points })
+}
+
+/// Evaluates the probability win ratio of a joint survival model on a grid of follow-up
+/// times, with an infinite or NaN win ratio where the loss probability is zero.
+pub fn model_curve_lenient<M: JointSurvivalModel>(model: &M, follow_up_times: &[f64], error_tolerance: f64) -> WinRatioCurve {
+    let points = follow_up_times
+        .iter()
+        .map(|&c| {
+            let probabilities = model_probability_win_ratio(model, c, error_tolerance);
+            WinRatioCurvePoint {
+                follow_up: c,
+                win_probability: probabilities.win_probability,
+                loss_probability: probabilities.loss_probability,
+                win_ratio: probabilities.win_ratio,
+                confidence_interval: None,
+            }
+        })
+        .collect();
+    WinRatioCurve { points }
+}
+
+/// Estimates the win ratio curve from patient data.
+///
+/// At each follow-up time c, every record is truncated at c and the two arms are compared
+/// with the Finkelstein–Schoenfeld all-pairs analysis. The band is the pointwise confidence
+/// interval of the win ratio, based on the U-statistic variance of log(WR).
+///
+/// ## Parameters
+///
+/// * `treatment`: Patients in the treatment arm.
+/// * `control`: Patients in the control arm.
+/// * `follow_up_times`: The follow-up times c at which to evaluate the win ratio.
+/// * `confidence_level`: The confidence level of the pointwise intervals, e.g. `0.95`.
+///
+/// ## Returns
+///
+/// The curve, or `Error::InvalidParameter` if a follow-up time is negative or not finite,
+/// and the errors of `unmatched::finkelstein_schoenfeld` if the win ratio is undefined or
+/// unbounded at some follow-up time.
+pub fn empirical_curve(
+    treatment: &[PatientRecord],
+    control: &[PatientRecord],
+    follow_up_times: &[f64],
+    confidence_level: f64,
+) -> Result<WinRatioCurve> {
+    let points = follow_up_times
+        .iter()
+        .map(|&c| {
+            let c = non_negative("follow_up_time", c)?;
+            let truncated_treatment: Vec<PatientRecord> = treatment.iter().map(|p| p.truncated(c)).collect();
+            let truncated_control: Vec<PatientRecord> = control.iter().map(|p| p.truncated(c)).collect();
+            let analysis = finkelstein_schoenfeld(&truncated_treatment, &truncated_control, confidence_level)?;
+            Ok(WinRatioCurvePoint {
+                follow_up: c,
+                win_probability: analysis.probabilities.win_probability,
+                loss_probability: analysis.probabilities.loss_probability,
+                win_ratio: analysis.win_ratio.estimate,
+                confidence_interval: Some(analysis.win_ratio.confidence_interval),
+            })
+        })
+        .collect::<Result<_>>()?;
+    Ok(WinRatioCurve { points })
+}
+
+/// Estimates the win ratio curve from patient data, with infinite or NaN estimates at the
+/// follow-up times where the win ratio is unbounded or undefined.
+pub fn empirical_curve_lenient(
+    treatment: &[PatientRecord],
+    control: &[PatientRecord],
+    follow_up_times: &[f64],
+    confidence_level: f64,
+) -> WinRatioCurve {
+    let points = follow_up_times
+        .iter()
+        .map(|&c| {
+            let truncated_treatment: Vec<PatientRecord> = treatment.iter().map(|p| p.truncated(c)).collect();
+            let truncated_control: Vec<PatientRecord> = control.iter().map(|p| p.truncated(c)).collect();
+            let analysis = finkelstein_schoenfeld_lenient(&truncated_treatment, &truncated_control, confidence_level);
+            WinRatioCurvePoint {
+                follow_up: c,
+                win_probability: analysis.probabilities.win_probability,
+                loss_probability: analysis.probabilities.loss_probability,
+                win_ratio: analysis.win_ratio.estimate,
+                confidence_interval: Some(analysis.win_ratio.confidence_interval),
+            }
+        })
+        .collect();
+    WinRatioCurve { points }
+}
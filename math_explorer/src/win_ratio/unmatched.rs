@@ -0,0 +1,310 @@
+//! # Unmatched Win Ratio (Finkelstein–Schoenfeld Approach)
+//!
+//! Generalized pairwise comparisons between two unmatched arms. Every treatment patient is
+//! compared with every control patient using the same hierarchical ordering as the
+//! matched-pairs approach. The win ratio, net benefit and win odds are reported together
+//! with U-statistic variance estimates and confidence intervals.
+
+use crate::error::{self, Error, Result};
+
+use super::distributions::{two_sided_critical_value, two_sided_p_value};
+use super::sample_win_ratio::{compare_patients, PatientRecord};
+
+/// Win and loss probabilities estimated over all treatment-control pairs.
+///
+/// The estimates are two-sample U-statistics. Their variances and covariance come from the
+/// Hoeffding decomposition: the variance of the per-patient mean scores in each arm,
+/// divided by the arm size, summed over both arms.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PairwiseProbabilities {
+    /// Number of patients in the treatment arm.
+    pub n_treatment: usize,
+    /// Number of patients in the control arm.
+    pub n_control: usize,
+    /// Estimated probability that a treatment patient wins against a control patient.
+    pub win_probability: f64,
+    /// Estimated probability that a treatment patient loses against a control patient.
+    pub loss_probability: f64,
+    /// Variance of the win probability estimate.
+    pub win_variance: f64,
+    /// Variance of the loss probability estimate.
+    pub loss_variance: f64,
+    /// Covariance between the win and loss probability estimates.
+    pub covariance: f64,
+}
+
+impl PairwiseProbabilities {
+    /// Probability that a pair is tied.
+    pub fn tie_probability(&self) -> f64 {
+        1.0 - self.win_probability - self.loss_probability
+    }
+
+    /// Win ratio, W / L.
+    ///
+    /// Returns `Error::EmptyData` if an arm is empty or every pair is tied, `Error::NoLosses`
+    /// if there are wins but no losses, and `Error::NoWins` if there are losses but no wins,
+    /// as then log(WR) and its interval are unbounded.
+    pub fn win_ratio(&self) -> Result<f64> {
+        if self.n_treatment == 0 || self.n_control == 0 || self.win_probability + self.loss_probability <= 0.0 {
+            return Err(Error::EmptyData);
+        }
+        if self.loss_probability <= 0.0 {
+            return Err(Error::NoLosses);
+        }
+        if self.win_probability <= 0.0 {
+            return Err(Error::NoWins);
+        }
+        Ok(self.win_ratio_lenient())
+    }
+
+    /// Win ratio, W / L, returning `f64::INFINITY` if there are no losses and `f64::NAN` if
+    /// there are no decided pairs.
+    pub fn win_ratio_lenient(&self) -> f64 {
+        self.win_probability / self.loss_probability
+    }
+
+    /// Net benefit, W - L.
+    pub fn net_benefit(&self) -> f64 {
+        self.win_probability - self.loss_probability
+    }
+
+    /// Win odds, (W + T/2) / (L + T/2) = (1 + NB) / (1 - NB).
+    pub fn win_odds(&self) -> f64 {
+        let net_benefit = self.net_benefit();
+        (1.0 + net_benefit) / (1.0 - net_benefit)
+    }
+
+    /// Variance of log(WR) by the delta method.
+    pub fn log_win_ratio_variance(&self) -> f64 {
+        let w = self.win_probability;
+        let l = self.loss_probability;
+        self.win_variance / (w * w) + self.loss_variance / (l * l) - 2.0 * self.covariance / (w * l)
+    }
+
+    /// Variance of the net benefit.
+    pub fn net_benefit_variance(&self) -> f64 {
+        self.win_variance + self.loss_variance - 2.0 * self.covariance
+    }
+
+    /// Variance of log(WO) by the delta method.
+    pub fn log_win_odds_variance(&self) -> f64 {
+        let net_benefit = self.net_benefit();
+        let derivative = 2.0 / (1.0 - net_benefit * net_benefit);
+        derivative * derivative * self.net_benefit_variance()
+    }
+}
+
+/// A win statistic together with its standard error, confidence interval and p-value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WinStatistic {
+    /// Point estimate.
+    pub estimate: f64,
+    /// Standard error on the scale of the interval (log scale for the win ratio and win odds).
+    pub standard_error: f64,
+    /// Confidence interval as `(lower_bound, upper_bound)`.
+    pub confidence_interval: (f64, f64),
+    /// Two-sided p-value for the null hypothesis of no treatment effect.
+    pub p_value: f64,
+}
+
+impl WinStatistic {
+    /// Builds a statistic whose interval is symmetric on the log scale.
+    pub fn from_log_scale(estimate: f64, log_variance: f64, confidence_level: f64) -> Self {
+        let standard_error = log_variance.sqrt();
+        let z = two_sided_critical_value(confidence_level);
+        let log_estimate = estimate.ln();
+        Self {
+            estimate,
+            standard_error,
+            confidence_interval: (
+                (log_estimate - z * standard_error).exp(),
+                (log_estimate + z * standard_error).exp(),
+            ),
+            p_value: two_sided_p_value(log_estimate / standard_error),
+        }
+    }
+
+    /// Builds a statistic whose interval is symmetric on the natural scale, testing against zero.
+    pub fn from_linear_scale(estimate: f64, variance: f64, confidence_level: f64) -> Self {
+        let standard_error = variance.sqrt();
+        let z = two_sided_critical_value(confidence_level);
+        Self {
+            estimate,
+            standard_error,
+            confidence_interval: (estimate - z * standard_error, estimate + z * standard_error),
+            p_value: two_sided_p_value(estimate / standard_error),
+        }
+    }
+}
+
+/// Result of an unmatched win analysis.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UnmatchedWinAnalysis {
+    /// Estimated win and loss probabilities with their covariance.
+    pub probabilities: PairwiseProbabilities,
+    /// Win ratio, W / L.
+    pub win_ratio: WinStatistic,
+    /// Net benefit, W - L.
+    pub net_benefit: WinStatistic,
+    /// Win odds, (W + T/2) / (L + T/2).
+    pub win_odds: WinStatistic,
+}
+
+/// Win and loss scores of a treatment-control pair under the Pocock hierarchy.
+pub(crate) fn hierarchical_scores(treatment: &PatientRecord, control: &PatientRecord) -> (f64, f64) {
+    let outcome = compare_patients(treatment, control);
+    (
+        if outcome.is_win() { 1.0 } else { 0.0 },
+        if outcome.is_loss() { 1.0 } else { 0.0 },
+    )
+}
+
+/// Estimates the win and loss probabilities from a user-supplied pairwise kernel.
+///
+/// ## Parameters
+///
+/// * `treatment`: Patients in the treatment arm.
+/// * `control`: Patients in the control arm.
+/// * `kernel`: Closure returning the `(win, loss)` scores of a `(treatment, control)` pair.
+///   For an ordinary comparison these are `(1, 0)`, `(0, 1)` or `(0, 0)`.
+///
+/// ## Returns
+///
+/// The `PairwiseProbabilities`, including the U-statistic covariance matrix, or
+/// `Error::EmptyData` if an arm is empty.
+pub fn pairwise_probabilities_with<P, F>(treatment: &[P], control: &[P], kernel: F) -> Result<PairwiseProbabilities>
+where
+    F: Fn(&P, &P) -> (f64, f64),
+{
+    if treatment.is_empty() || control.is_empty() {
+        return Err(Error::EmptyData);
+    }
+    Ok(pairwise_probabilities_with_lenient(treatment, control, kernel))
+}
+
+/// Estimates the win and loss probabilities from a user-supplied pairwise kernel, without
+/// checking that both arms are non-empty.
+pub fn pairwise_probabilities_with_lenient<P, F>(treatment: &[P], control: &[P], kernel: F) -> PairwiseProbabilities
+where
+    F: Fn(&P, &P) -> (f64, f64),
+{
+    let n = treatment.len();
+    let m = control.len();
+
+    let mut treatment_means = vec![(0.0, 0.0); n];
+    let mut control_means = vec![(0.0, 0.0); m];
+    for (i, t) in treatment.iter().enumerate() {
+        for (j, c) in control.iter().enumerate() {
+            let (win, loss) = kernel(t, c);
+            treatment_means[i].0 += win / m as f64;
+            treatment_means[i].1 += loss / m as f64;
+            control_means[j].0 += win / n as f64;
+            control_means[j].1 += loss / n as f64;
+        }
+    }
+
+    let win_probability = treatment_means.iter().map(|s| s.0).sum::<f64>() / n as f64;
+    let loss_probability = treatment_means.iter().map(|s| s.1).sum::<f64>() / n as f64;
+
+    // Sample covariance matrix of the per-patient mean scores, scaled by the arm size.
+    let arm_covariance = |means: &[(f64, f64)]| {
+        let k = means.len() as f64;
+        let denominator = (k - 1.0).max(1.0) * k;
+        means.iter().fold((0.0, 0.0, 0.0), |acc, &(w, l)| {
+            let dw = w - win_probability;
+            let dl = l - loss_probability;
+            (acc.0 + dw * dw / denominator, acc.1 + dl * dl / denominator, acc.2 + dw * dl / denominator)
+        })
+    };
+    let treatment_part = arm_covariance(&treatment_means);
+    let control_part = arm_covariance(&control_means);
+
+    PairwiseProbabilities {
+        n_treatment: n,
+        n_control: m,
+        win_probability,
+        loss_probability,
+        win_variance: treatment_part.0 + control_part.0,
+        loss_variance: treatment_part.1 + control_part.1,
+        covariance: treatment_part.2 + control_part.2,
+    }
+}
+
+/// Estimates the win and loss probabilities using the Pocock hierarchy on patient records.
+///
+/// Returns `Error::EmptyData` if an arm is empty.
+pub fn pairwise_probabilities(treatment: &[PatientRecord], control: &[PatientRecord]) -> Result<PairwiseProbabilities> {
+    pairwise_probabilities_with(treatment, control, hierarchical_scores)
+}
+
+/// Estimates the win and loss probabilities using the Pocock hierarchy, without checking
+/// that both arms are non-empty.
+pub fn pairwise_probabilities_lenient(treatment: &[PatientRecord], control: &[PatientRecord]) -> PairwiseProbabilities {
+    pairwise_probabilities_with_lenient(treatment, control, hierarchical_scores)
+}
+
+/// Computes the win ratio, net benefit and win odds from estimated pairwise probabilities.
+///
+/// ## Parameters
+///
+/// * `probabilities`: The estimated win and loss probabilities with their covariance.
+/// * `confidence_level`: The confidence level of the intervals, e.g. `0.95`.
+pub fn analyze_pairwise_probabilities(
+    probabilities: &PairwiseProbabilities,
+    confidence_level: f64,
+) -> UnmatchedWinAnalysis {
+    UnmatchedWinAnalysis {
+        probabilities: *probabilities,
+        win_ratio: WinStatistic::from_log_scale(
+            probabilities.win_ratio_lenient(),
+            probabilities.log_win_ratio_variance(),
+            confidence_level,
+        ),
+        net_benefit: WinStatistic::from_linear_scale(
+            probabilities.net_benefit(),
+            probabilities.net_benefit_variance(),
+            confidence_level,
+        ),
+        win_odds: WinStatistic::from_log_scale(
+            probabilities.win_odds(),
+            probabilities.log_win_odds_variance(),
+            confidence_level,
+        ),
+    }
+}
+
+/// Performs the Finkelstein–Schoenfeld all-pairs win analysis on two unmatched arms.
+///
+/// ## Parameters
+///
+/// * `treatment`: Patients in the treatment arm.
+/// * `control`: Patients in the control arm.
+/// * `confidence_level`: The confidence level of the intervals, e.g. `0.95`.
+///
+/// ## Returns
+///
+/// An `UnmatchedWinAnalysis` with the win ratio, net benefit and win odds, or
+/// `Error::InvalidParameter` if `confidence_level` is outside [0, 1], `Error::EmptyData` if
+/// an arm is empty or every pair is tied, `Error::NoLosses` if there are wins but no losses,
+/// as then the win ratio is unbounded, and `Error::NoWins` if there are losses but no wins.
+pub fn finkelstein_schoenfeld(
+    treatment: &[PatientRecord],
+    control: &[PatientRecord],
+    confidence_level: f64,
+) -> Result<UnmatchedWinAnalysis> {
+    error::probability("confidence_level", confidence_level)?;
+    let probabilities = pairwise_probabilities(treatment, control)?;
+    probabilities.win_ratio()?;
+    Ok(analyze_pairwise_probabilities(&probabilities, confidence_level))
+}
+
+/// Performs the Finkelstein–Schoenfeld analysis, returning an infinite win ratio if there are
+/// no losses, a zero win ratio with a NaN interval if there are no wins, and NaN estimates if
+/// an arm is empty or every pair is tied.
+pub fn finkelstein_schoenfeld_lenient(
+    treatment: &[PatientRecord],
+    control: &[PatientRecord],
+    confidence_level: f64,
+) -> UnmatchedWinAnalysis {
+    analyze_pairwise_probabilities(&pairwise_probabilities_lenient(treatment, control), confidence_level)
+}
@@ -68,3 +68,216 @@ fn test_dcdt() {
     let result = cannibalism::dcdt(n, c, k_n, mu_c);
     assert_eq!(result, 4.0);
 }
+
+#[test]
+fn test_mckendrick_von_foerster_solver() {
+    // With constant mortality the exact solution is n_0(a - t) e^{-mu t} for a >= t
+    // and b(t - a) e^{-mu a} for a < t.
+    let mu = 0.3;
+    let initial = |a: f64| (-a).exp() * (1.0 + a);
+    let birth = |t: f64| 2.0 + t.sin();
+    let exact = |t: f64, a: f64| if a >= t { initial(a - t) * (-mu * t).exp() } else { birth(t - a) * (-mu * a).exp() };
+
+    let solver = cannibalism::McKendrickSolver::new(8.0, 400).unwrap();
+    let solution = solver.solve(|_, _| mu, birth, initial, 5.0).unwrap();
+    assert!((solution.times.last().unwrap() - 5.0).abs() < 1e-12);
+    assert_eq!(solution.density.len(), solution.times.len());
+    assert_eq!(solution.ages.len(), 401);
+    for (k, &t) in solution.times.iter().enumerate() {
+        for (j, &a) in solution.ages.iter().enumerate() {
+            assert!((solution.density[k][j] - exact(t, a)).abs() < 1e-12, "t = {t}, a = {a}");
+        }
+        if k > 0 {
+            assert!((solution.births[k] - birth(t)).abs() < 1e-12);
+        }
+    }
+
+    // Constant births: the total approaches the stationary population b (1 - e^{-mu L}) / mu,
+    // with an O(h^2) error from the trapezoidal rule.
+    let solution = cannibalism::solve_mckendrick_von_foerster(|_, _| mu, |_| 1.0, |_| 0.0, 8.0, 400, 10.0).unwrap();
+    let stationary = (1.0 - (-mu * 8.0_f64).exp()) / mu;
+    assert!((solution.final_total() - stationary).abs() < 1e-4);
+    assert!(solution.total.windows(2).all(|w| w[1] >= w[0]));
+
+    // Mortality linear in age is also integrated exactly along the characteristics.
+    let solution = solver.solve(|_, a| 0.1 + 0.05 * a, |_| 0.0, initial, 2.0).unwrap();
+    let a = 4.0;
+    let j = solution.ages.iter().position(|&x| (x - a).abs() < 1e-9).unwrap();
+    let survival = (-(0.1 * 2.0 + 0.025 * (a * a - (a - 2.0) * (a - 2.0)))).exp();
+    assert!((solution.final_density()[j] - initial(a - 2.0) * survival).abs() < 1e-12);
+
+    assert!(cannibalism::McKendrickSolver::new(0.0, 10).is_err());
+    assert!(cannibalism::McKendrickSolver::new(1.0, 0).is_err());
+    assert!(solver.solve(|_, _| mu, birth, initial, -1.0).is_err());
+}
+
+#[test]
+fn test_renewal_equation() {
+    // beta(a) = b0 a e^{-a} and mu = m give R_0 = b0 / (1 + m)^2 and r = sqrt(b0) - 1 - m.
+    let (b0, m) = (4.0, 0.5);
+    let fertility = move |a: f64| b0 * a * (-a).exp();
+    let model = cannibalism::RenewalModel::new(fertility, |_| m, 40.0);
+    assert!((model.survival(2.0) - (-1.0_f64).exp()).abs() < 1e-12);
+    assert!((model.net_reproductive_number() - b0 / (1.0 + m).powi(2)).abs() < 1e-9);
+    let r = model.intrinsic_growth_rate().unwrap();
+    assert!((r - 0.5).abs() < 1e-9);
+    assert!((model.euler_lotka(r) - 1.0).abs() < 1e-9);
+
+    // A declining population has a negative growth rate, and no fertility has no root.
+    let declining = cannibalism::RenewalModel::new(move |a: f64| 1.5 * a * (-a).exp(), |_| m, 40.0);
+    assert!(declining.net_reproductive_number() < 1.0);
+    assert!((declining.intrinsic_growth_rate().unwrap() - (1.5_f64.sqrt() - 1.5)).abs() < 1e-9);
+    let barren = cannibalism::RenewalModel::new(|_: f64| 0.0, |_| m, 40.0);
+    assert!(barren.intrinsic_growth_rate().is_err());
+
+    // The solver converges to exponential growth at rate r from any initial distribution.
+    let solver = cannibalism::McKendrickSolver::new(40.0, 2000).unwrap();
+    let solution = solver.solve_renewal(|_, _| m, &fertility, |a| if a < 1.0 { 1.0 } else { 0.0 }, 30.0).unwrap();
+    let index = |t: f64| solution.times.iter().position(|&x| (x - t).abs() < 1e-9).unwrap();
+    let (k1, k2) = (index(20.0), index(30.0));
+    let growth = (solution.total[k2] / solution.total[k1]).ln() / 10.0;
+    assert!((growth - r).abs() < 1e-3, "growth rate {growth}");
+    let births = cannibalism::renewal_births(&fertility, &solution.ages, solution.final_density());
+    assert!((births - solution.births[k2]).abs() < 1e-2 * births);
+
+    // Starting from the stable age distribution e^{-(r + m) a}, births grow as e^{r t}, up to
+    // the O(h^2) error of the discrete growth rate.
+    let solution = solver.solve_renewal(|_, _| m, &fertility, |a| (-(r + m) * a).exp(), 10.0).unwrap();
+    for (t, b) in solution.times.iter().zip(&solution.births).skip(1) {
+        assert!((b / (r * t).exp() - 1.0).abs() < 5e-3);
+    }
+}
+
+#[test]
+fn test_density_dependent_death_rate() {
+    assert!((cannibalism::holling_type_ii(2.0, 3.0) - 1.0 / 7.0).abs() < 1e-15);
+
+    let (births, m, c0, alpha, max_age) = (10.0, 0.5, 0.1, 1.0, 10.0);
+    let solver = cannibalism::McKendrickSolver::new(max_age, 500).unwrap();
+    let initial = |a: f64| (-a).exp();
+
+    // Without attacks the model reduces to the natural death rate.
+    let harmless = cannibalism::DeathRateModel::new(|_| m, |_| 0.0, |_| 1.0, |_| 1.0);
+    let solution = solver.solve_with_death_rate(&harmless, |_| births, initial, 5.0).unwrap();
+    let reference = solver.solve(|_, _| m, |_| births, initial, 5.0).unwrap();
+    assert_eq!(solution.population, reference);
+    assert_eq!(solution.states.len(), reference.times.len());
+    assert!(solution.states.iter().zip(&reference.total).all(|(s, total)| (s.cannibals - total).abs() < 1e-12));
+
+    // Adults (a >= alpha) eat juveniles at rate c0. At equilibrium the juveniles decay at
+    // rate m + c0 k and the adults at rate m, so k = b e^{-(m + c0 k) alpha} (1 - e^{-m (L - alpha)}) / m.
+    let juvenile = move |a: f64| if a < alpha { c0 } else { 0.0 };
+    let adult = move |a: f64| if a >= alpha { 1.0 } else { 0.0 };
+    let model = cannibalism::DeathRateModel::new(|_| m, juvenile, adult, |_| 1.0);
+    let solution = solver.solve_with_death_rate(&model, |_| births, initial, 60.0).unwrap();
+    let mut k = 1.0;
+    for _ in 0..200 {
+        let next = births * (-(m + c0 * k) * alpha).exp() * (1.0 - (-m * (max_age - alpha)).exp()) / m;
+        k = 0.5 * (k + next);
+    }
+    let last = solution.states.last().unwrap();
+    assert!((last.cannibals - k).abs() < 1e-2 * k, "k = {}, expected {k}", last.cannibals);
+    let rate = model.rate(0.5, last);
+    assert!((rate - (m + c0 * last.cannibals)).abs() < 1e-12);
+    let j = solution.population.ages.iter().position(|&a| (a - 0.5).abs() < 1e-9).unwrap();
+    let expected = births * (-(m + c0 * k) * 0.5).exp();
+    assert!((solution.population.final_density()[j] - expected).abs() < 1e-2 * expected);
+    assert!(solution.population.final_total() < reference.final_total());
+
+    // Satiation weakens the predation, and renewal births close the boundary.
+    let satiated = cannibalism::DeathRateModel::new(|_| m, juvenile, adult, |c| cannibalism::holling_type_ii(5.0, c));
+    let sated = solver.solve_with_death_rate(&satiated, |_| births, initial, 60.0).unwrap();
+    assert!(sated.states.last().unwrap().cannibals > last.cannibals);
+    let renewal = solver.solve_renewal_with_death_rate(&model, &adult, initial, 5.0).unwrap();
+    assert!(renewal.population.births.iter().all(|b| b.is_finite() && *b >= 0.0));
+    assert!(solver.solve_with_death_rate(&model, |_| births, initial, f64::NAN).is_err());
+}
+
+#[test]
+fn test_juvenile_adult_model() {
+    let h = 0.01;
+    let initial = |a: f64| 5.0 * (-a).exp();
+    let cumulative = |values: &[f64]| -> Vec<f64> {
+        let mut sums = vec![0.0];
+        for w in values.windows(2) {
+            sums.push(sums.last().unwrap() + 0.5 * h * (w[0] + w[1]));
+        }
+        sums
+    };
+
+    // Without mortality the juveniles plus adults change by exactly the births.
+    let model = cannibalism::JuvenileAdultModel::new(|_| 0.0, |_| 0.0, |_| 0.0, |_| 0.3, 2.0);
+    let solution = model.simulate(200, initial, 4.0, 6.0).unwrap();
+    assert_eq!(solution.adults.len(), solution.times().len());
+    assert_eq!(solution.intake.len(), solution.times().len());
+    let born = cumulative(&solution.population.births);
+    let mass0 = solution.juveniles()[0] + solution.adults[0];
+    for ((j, a), b) in solution.juveniles().iter().zip(&solution.adults).zip(&born) {
+        assert!((j + a - mass0 - b).abs() < 1e-10);
+    }
+    assert!(solution.intake.iter().all(|&i| i == 0.0));
+
+    // With cannibalism the change in mass matches births minus natural juvenile deaths,
+    // predation A * I and adult deaths f(I) * A, up to the discretization error.
+    let (nu, c) = (0.2, 0.05);
+    let adult_death = |i: f64| 0.3 + 0.2 / (1.0 + i);
+    let model = cannibalism::JuvenileAdultModel::new(|_| nu, |_| c, adult_death, |i: f64| 2.0 * i / (1.0 + i), 2.0);
+    let solution = model.simulate(200, initial, 4.0, 10.0).unwrap();
+    let juveniles = solution.juveniles();
+    let net: Vec<f64> = (0..solution.times().len())
+        .map(|k| {
+            let a = solution.adults[k];
+            let i = solution.intake[k];
+            solution.population.births[k] - nu * juveniles[k] - a * i - adult_death(i) * a
+        })
+        .collect();
+    let budget = cumulative(&net);
+    let mass0 = juveniles[0] + solution.adults[0];
+    let scale = solution.population.births.iter().sum::<f64>() * h;
+    for k in 0..solution.times().len() {
+        let mass = juveniles[k] + solution.adults[k];
+        assert!((mass - mass0 - budget[k]).abs() < 1e-2 * scale, "t = {}", solution.times()[k]);
+    }
+    // The intake is C times the juveniles and the maturation flux is the density at alpha.
+    assert!(solution.intake.iter().zip(juveniles).all(|(i, j)| (i - c * j).abs() < 1e-12));
+    assert!(solution.maturation.iter().zip(&solution.population.density).all(|(m, n)| m == n.last().unwrap()));
+    assert!(solution.adults.iter().all(|&a| a > 0.0));
+
+    assert!(model.simulate(0, initial, 4.0, 1.0).is_err());
+    assert!(model.simulate(10, initial, -1.0, 1.0).is_err());
+}
+
+#[test]
+fn test_cannibalism_ode() {
+    use math_explorer::ode::{Method, OdeSolver, OdeSystem};
+    use nalgebra::DVector;
+
+    // The right-hand side evaluates `dndt` and `dcdt` with the rates at (N, C).
+    let model = cannibalism::CannibalismOde::new(
+        |_, _| 0.1,
+        |_, _| 0.2,
+        |n, _| 0.0005 * n,
+        |n, c| 0.001 * n * c,
+        |_, _| 0.1,
+        |_, _| 0.1,
+    );
+    let rate = model.derivative(0.0, &DVector::from_vec(vec![100.0, 10.0]));
+    assert!((rate[0] - cannibalism::dndt(100.0, 10.0, 0.1, 0.2, 0.05, 1.0, 0.1)).abs() < 1e-12);
+    assert!((rate[1] - cannibalism::dcdt(100.0, 10.0, 0.05, 0.1)).abs() < 1e-12);
+
+    // With only conversion, N(t) = N0 e^{-k t} and N + C is conserved.
+    let (n0, c0, k) = (100.0, 10.0, 0.3);
+    let conversion = cannibalism::CannibalismOde::new(|_, _| 0.0, |_, _| 0.0, move |_, _| k, |_, _| 0.0, |_, _| 0.0, |_, _| 0.0);
+    let solver = OdeSolver::new(Method::DormandPrince, 1e-10, 1e-10);
+    let solution = conversion.solve(&solver, n0, c0, 5.0).unwrap();
+    let state = solution.final_state();
+    assert!((state[0] - n0 * (-k * 5.0f64).exp()).abs() < 1e-7);
+    assert!((state[0] + state[1] - n0 - c0).abs() < 1e-8);
+
+    // The three methods agree on the full model.
+    let reference = model.solve(&solver, 100.0, 10.0, 20.0).unwrap();
+    for method in [Method::RungeKutta4 { step: 0.01 }, Method::Rosenbrock] {
+        let solution = model.solve(&OdeSolver::new(method, 1e-8, 1e-8), 100.0, 10.0, 20.0).unwrap();
+        assert!((solution.final_state() - reference.final_state()).norm() < 1e-4 * reference.final_state().norm());
+    }
+}
@@ -0,0 +1,70 @@
+use math_explorer::integration::{Method, Quadrature};
+
+const METHODS: [Method; 3] = [Method::GaussKronrod, Method::TanhSinh, Method::ClenshawCurtis];
+
+#[test]
+fn test_finite_integrals() {
+    for method in METHODS {
+        let quadrature = Quadrature::new(method, 1e-10);
+        let result = quadrature.integrate(f64::sin, 0.0, std::f64::consts::PI);
+        assert!((result.integral - 2.0).abs() < 1e-9, "{method:?}");
+        assert!(result.converged && result.error_estimate <= 1e-10);
+        assert!(result.evaluations > 0);
+
+        // Reversed limits change the sign.
+        let reversed = quadrature.integrate(|x: f64| x * x, 1.0, 0.0);
+        assert!((reversed.integral + 1.0 / 3.0).abs() < 1e-12);
+    }
+    let empty = Quadrature::default().integrate(|x: f64| x, 2.0, 2.0);
+    assert_eq!((empty.integral, empty.evaluations), (0.0, 0));
+}
+
+#[test]
+fn test_adaptive_gauss_kronrod() {
+    // A kink at 1/3 forces subdivision; G7–K15 integrates each polynomial piece exactly.
+    let quadrature = Quadrature::new(Method::GaussKronrod, 1e-12);
+    let result = quadrature.integrate(|x: f64| (x - 1.0 / 3.0).abs(), 0.0, 1.0);
+    assert!((result.integral - 5.0 / 18.0).abs() < 1e-12);
+    assert!(result.converged);
+    assert!(result.evaluations > 15 && result.evaluations.is_multiple_of(15));
+
+    // A single subinterval cannot meet the tolerance, and says so.
+    let capped = quadrature.with_max_subdivisions(1).integrate(|x: f64| (x - 1.0 / 3.0).abs(), 0.0, 1.0);
+    assert_eq!(capped.evaluations, 15);
+    assert!(!capped.converged && capped.error_estimate > 1e-12);
+}
+
+#[test]
+fn test_tanh_sinh_endpoint_singularity() {
+    // ∫_0^1 x^(-1/2) dx = 2, with an infinite integrand at zero.
+    let tanh_sinh = Quadrature::new(Method::TanhSinh, 1e-10).integrate(|x: f64| x.powf(-0.5), 0.0, 1.0);
+    assert!((tanh_sinh.integral - 2.0).abs() < 1e-6);
+    // Tanh-sinh stops after about 385 evaluations; adaptive bisection reaches the tolerance.
+    assert!(tanh_sinh.evaluations < 400);
+    let kronrod = Quadrature::new(Method::GaussKronrod, 1e-10).integrate(|x: f64| x.powf(-0.5), 0.0, 1.0);
+    assert!(kronrod.converged && (kronrod.integral - 2.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_infinite_ranges() {
+    let gaussian = |x: f64| (-x * x / 2.0).exp();
+    let root_two_pi = (2.0 * std::f64::consts::PI).sqrt();
+    for method in [Method::GaussKronrod, Method::TanhSinh] {
+        let quadrature = Quadrature::new(method, 1e-10);
+        let whole = quadrature.integrate(gaussian, f64::NEG_INFINITY, f64::INFINITY);
+        assert!((whole.integral - root_two_pi).abs() < 1e-8, "{method:?}");
+        let lower = quadrature.integrate(gaussian, f64::NEG_INFINITY, 0.0);
+        assert!((lower.integral - root_two_pi / 2.0).abs() < 1e-8);
+
+        // ∫_1^∞ x^(-2) dx = 1.
+        let tail = quadrature.integrate(|x: f64| x.powi(-2), 1.0, f64::INFINITY);
+        assert!((tail.integral - 1.0).abs() < 1e-8);
+
+        // A NaN in the integrand is not mistaken for a zero contribution.
+        let broken = quadrature.integrate(|x: f64| if x > 5.0 { f64::NAN } else { (-x).exp() }, 0.0, f64::INFINITY);
+        assert!(!broken.converged, "{method:?}: {broken:?}");
+        if method == Method::GaussKronrod {
+            assert!(broken.integral.is_nan());
+        }
+    }
+}
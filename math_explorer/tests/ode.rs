@@ -0,0 +1,95 @@
+use math_explorer::error::Error;
+use math_explorer::ode::{Method, OdeSolver, OdeSystem};
+use nalgebra::{DMatrix, DVector};
+
+/// The harmonic oscillator y'' = -y, with solution (cos t, -sin t).
+fn oscillator(_t: f64, y: &DVector<f64>) -> DVector<f64> {
+    DVector::from_vec(vec![y[1], -y[0]])
+}
+
+/// A stiff problem y' = -10^4 (y - cos t) - sin t with solution y = cos t.
+struct Stiff;
+
+impl OdeSystem for Stiff {
+    fn derivative(&self, t: f64, y: &DVector<f64>) -> DVector<f64> {
+        DVector::from_vec(vec![-1e4 * (y[0] - t.cos()) - t.sin()])
+    }
+
+    fn jacobian(&self, _t: f64, _y: &DVector<f64>) -> DMatrix<f64> {
+        DMatrix::from_element(1, 1, -1e4)
+    }
+}
+
+#[test]
+fn test_runge_kutta_4() {
+    let y0 = DVector::from_vec(vec![1.0, 0.0]);
+    let error = |step: f64| {
+        let solution = OdeSolver::new(Method::RungeKutta4 { step }, 0.0, 0.0).solve(&oscillator, 0.0, y0.clone(), 5.0).unwrap();
+        assert_eq!(solution.final_time(), 5.0);
+        (solution.final_state()[0] - 5.0f64.cos()).abs()
+    };
+    // Fourth order: halving the step divides the error by about 16.
+    let (coarse, fine) = (error(0.1), error(0.05));
+    assert!(coarse < 1e-5);
+    assert!((coarse / fine - 16.0).abs() < 1.0, "ratio {}", coarse / fine);
+
+    // A step that does not divide the interval is shortened at the end.
+    let solution = OdeSolver::new(Method::RungeKutta4 { step: 0.3 }, 0.0, 0.0).solve(&oscillator, 0.0, y0.clone(), 1.0).unwrap();
+    assert_eq!(solution.times.len(), 5);
+    assert_eq!(solution.final_time(), 1.0);
+    let middle = solution.at(0.45).unwrap();
+    assert!((middle[0] - 0.45f64.cos()).abs() < 1e-3);
+
+    let invalid = OdeSolver::new(Method::RungeKutta4 { step: 0.0 }, 0.0, 0.0).solve(&oscillator, 0.0, y0, 1.0);
+    assert!(matches!(invalid, Err(Error::InvalidParameter { name: "step", .. })));
+}
+
+#[test]
+fn test_dormand_prince() {
+    let y0 = DVector::from_vec(vec![1.0, 0.0]);
+    let solver = OdeSolver::new(Method::DormandPrince, 1e-10, 1e-12);
+    let solution = solver.solve(&oscillator, 0.0, y0.clone(), 10.0).unwrap();
+    assert_eq!(solution.final_time(), 10.0);
+    assert!((solution.final_state()[0] - 10.0f64.cos()).abs() < 1e-8);
+    assert!((solution.final_state()[1] + 10.0f64.sin()).abs() < 1e-8);
+
+    // The dense output is fourth-order accurate between the steps.
+    for i in 0..=200 {
+        let t = i as f64 * 0.05;
+        let y = solution.at(t).unwrap();
+        assert!((y[0] - t.cos()).abs() < 1e-8, "t = {t}");
+        assert!((y[1] + t.sin()).abs() < 1e-8, "t = {t}");
+    }
+    assert!(solution.at(10.5).is_none());
+
+    // A looser tolerance takes far fewer steps, and the dense output still interpolates accurately.
+    let loose = OdeSolver::new(Method::DormandPrince, 1e-5, 1e-8).solve(&oscillator, 0.0, y0.clone(), 10.0).unwrap();
+    assert!(loose.steps() * 4 < solution.steps());
+    let middle = 0.5 * (loose.times[3] + loose.times[4]);
+    assert!((loose.at(middle).unwrap()[0] - middle.cos()).abs() < 1e-4);
+
+    // Step limits are reported as errors.
+    let limited = solver.with_max_steps(5).solve(&oscillator, 0.0, y0.clone(), 10.0);
+    assert!(matches!(limited, Err(Error::StepFailure { .. })));
+    let capped = solver.with_max_step(0.01).solve(&oscillator, 0.0, y0.clone(), 1.0).unwrap();
+    assert!(capped.steps() >= 100);
+    assert!(solver.solve(&oscillator, 0.0, y0, -1.0).is_err());
+}
+
+#[test]
+fn test_rosenbrock_on_stiff_problem() {
+    let y0 = DVector::from_vec(vec![1.0]);
+    let rosenbrock = OdeSolver::new(Method::Rosenbrock, 1e-5, 1e-7).solve(&Stiff, 0.0, y0.clone(), 10.0).unwrap();
+    let dormand_prince = OdeSolver::new(Method::DormandPrince, 1e-5, 1e-7).solve(&Stiff, 0.0, y0.clone(), 10.0).unwrap();
+    assert!((rosenbrock.final_state()[0] - 10.0f64.cos()).abs() < 1e-5);
+    assert!((dormand_prince.final_state()[0] - 10.0f64.cos()).abs() < 1e-5);
+    // The explicit method is limited by stability, the implicit one only by accuracy.
+    assert!(rosenbrock.steps() * 10 < dormand_prince.steps(), "{} vs {}", rosenbrock.steps(), dormand_prince.steps());
+    assert!((rosenbrock.at(5.05).unwrap()[0] - 5.05f64.cos()).abs() < 1e-4);
+
+    // The finite-difference Jacobian of a closure gives the same answer.
+    let closure = |t: f64, y: &DVector<f64>| Stiff.derivative(t, y);
+    let numerical = OdeSolver::new(Method::Rosenbrock, 1e-5, 1e-7).solve(&closure, 0.0, y0, 10.0).unwrap();
+    assert!((numerical.final_state()[0] - rosenbrock.final_state()[0]).abs() < 1e-6);
+    assert!((closure.jacobian(0.0, &DVector::from_vec(vec![0.5]))[(0, 0)] + 1e4).abs() < 1e-3);
+}
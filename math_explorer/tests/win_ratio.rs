@@ -1,4 +1,8 @@
-use math_explorer::win_ratio::{bmi, sample_win_ratio, probability_win_ratio, simulation};
+use math_explorer::error::Error;
+use math_explorer::integration::{Method, Quadrature};
+use math_explorer::win_ratio::{bmi, copula, diagnostics, distributions, group_sequential, hierarchy, ipcw, nonparametric, power, sample_win_ratio, probability_win_ratio, recurrent, regression, resampling, simulation, stratified, time_dependent, unmatched};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
 
 const FLOAT_TOLERANCE: f64 = 1e-3;
 
@@ -7,13 +11,13 @@ fn test_calculate_bmi() {
     let weight_kg = 70.0;
     let height_m = 1.75;
     let expected_bmi = 22.857;
-    let calculated_bmi = bmi::calculate_bmi(weight_kg, height_m);
+    let calculated_bmi = bmi::calculate_bmi(weight_kg, height_m).unwrap();
     assert!((calculated_bmi - expected_bmi).abs() < FLOAT_TOLERANCE);
 
     let weight_kg = 80.0;
     let height_m = 1.60;
     let expected_bmi = 31.25;
-    let calculated_bmi = bmi::calculate_bmi(weight_kg, height_m);
+    let calculated_bmi = bmi::calculate_bmi(weight_kg, height_m).unwrap();
     assert!((calculated_bmi - expected_bmi).abs() < FLOAT_TOLERANCE);
 }
 
@@ -28,18 +32,18 @@ fn test_sample_win_ratio() {
 
     // Test win ratio
     let expected_ratio = 35.0 / 15.0;
-    let ratio = sample_win_ratio::calculate_sample_win_ratio(&counts);
+    let ratio = sample_win_ratio::calculate_sample_win_ratio(&counts).unwrap();
     assert!((ratio - expected_ratio).abs() < FLOAT_TOLERANCE);
 
     // Test confidence interval
-    let (lower, upper) = sample_win_ratio::calculate_confidence_interval(n_w, n_l);
+    let (lower, upper) = sample_win_ratio::calculate_confidence_interval(n_w, n_l).unwrap();
     let expected_lower = 1.342;
     let expected_upper = 4.781;
     assert!((lower - expected_lower).abs() < FLOAT_TOLERANCE);
     assert!((upper - expected_upper).abs() < FLOAT_TOLERANCE);
 
     // Test significance test statistic
-    let statistic = sample_win_ratio::calculate_significance_test_statistic(n_w, n_l);
+    let statistic = sample_win_ratio::calculate_significance_test_statistic(n_w, n_l).unwrap();
     let expected_statistic = 3.086;
     assert!((statistic - expected_statistic).abs() < FLOAT_TOLERANCE);
 }
@@ -56,13 +60,13 @@ fn test_probability_win_ratio_with_simulation() {
     // Create closures for the survival and pdf functions from the simulation module
     let s0 = |t: f64| simulation::marginal_survival_t_control(t, &params);
     let s1 = |t: f64| simulation::marginal_survival_t_treatment(t, &params);
-    let pdf_t0 = |t: f64| simulation::pdf_t_control(t, &params);
-    let pdf_t1 = |t: f64| simulation::pdf_t_treatment(t, &params);
+    let pdf_t0 = |t: f64| simulation::pdf_t_control(t, &params).unwrap();
+    let pdf_t1 = |t: f64| simulation::pdf_t_treatment(t, &params).unwrap();
 
     let g0_given_c = |x: f64| simulation::conditional_survival_x_given_t_control(x, c, &params);
     let g1_given_c = |x: f64| simulation::conditional_survival_x_given_t_treatment(x, c, &params);
-    let pdf_x0_given_c = |x: f64| simulation::pdf_x_given_t_control(x, c, &params);
-    let pdf_x1_given_c = |x: f64| simulation::pdf_x_given_t_treatment(x, c, &params);
+    let pdf_x0_given_c = |x: f64| simulation::pdf_x_given_t_control(x, c, &params).unwrap();
+    let pdf_x1_given_c = |x: f64| simulation::pdf_x_given_t_treatment(x, c, &params).unwrap();
 
     let s0_at_c = s0(c);
     let s1_at_c = s1(c);
@@ -76,7 +80,7 @@ fn test_probability_win_ratio_with_simulation() {
     );
 
     // Calculate probability win ratio
-    let calculated_pr_c = probability_win_ratio::calculate_probability_win_ratio(win_prob, loss_prob);
+    let calculated_pr_c = probability_win_ratio::calculate_probability_win_ratio(win_prob, loss_prob).unwrap();
 
     // The probability win ratio PR(c) should be close to the theoretical parameter PR_W
     // when c is large enough for the integrals to stabilize.
@@ -87,3 +91,1246 @@ fn test_probability_win_ratio_with_simulation() {
     assert!((calculated_pr_c - expected_pr_w).abs() < comparison_tolerance,
             "Calculated PR(c) = {}, Expected PR_W = {}", calculated_pr_c, expected_pr_w);
 }
+
+#[test]
+fn test_compare_patients_pocock_hierarchy() {
+    use sample_win_ratio::{compare_patients, PairOutcome, PatientRecord};
+
+    // Treatment patient dies first within the shared follow-up.
+    let treatment = PatientRecord::new(Some(2.0), None, 2.0);
+    let control = PatientRecord::new(Some(3.0), None, 3.0);
+    assert_eq!(compare_patients(&treatment, &control), PairOutcome::TreatmentDiesFirst);
+
+    // Control death happens after the treatment patient is censored, so death is not comparable
+    // and the earlier control hospitalization decides the pair.
+    let treatment = PatientRecord::new(None, Some(3.5), 4.0);
+    let control = PatientRecord::new(Some(5.0), Some(1.0), 5.0);
+    assert_eq!(compare_patients(&treatment, &control), PairOutcome::ControlEventFirst);
+
+    // A hospitalization after the shared window does not count.
+    let treatment = PatientRecord::new(None, Some(4.5), 6.0);
+    let control = PatientRecord::new(None, None, 4.0);
+    assert_eq!(compare_patients(&treatment, &control), PairOutcome::Tie);
+}
+
+#[test]
+fn test_counts_from_patient_records() {
+    use sample_win_ratio::{count_matched_pairs, count_unmatched_pairs, PatientRecord};
+
+    let treatment = vec![
+        PatientRecord::new(None, None, 10.0),
+        PatientRecord::new(Some(6.0), Some(2.0), 6.0),
+        PatientRecord::new(None, Some(8.0), 10.0),
+    ];
+    let control = vec![
+        PatientRecord::new(Some(4.0), None, 4.0),
+        PatientRecord::new(None, Some(1.0), 10.0),
+        PatientRecord::new(None, None, 10.0),
+    ];
+
+    let pairs: Vec<_> = treatment.iter().copied().zip(control.iter().copied()).collect();
+    let matched = count_matched_pairs(&pairs);
+    assert_eq!(matched.counts, sample_win_ratio::WinLossCounts::new(1, 1, 1, 0));
+    assert_eq!(matched.n_ties, 0);
+    let ratio = sample_win_ratio::calculate_sample_win_ratio(&matched.counts).unwrap();
+    assert!((ratio - 0.5).abs() < FLOAT_TOLERANCE);
+
+    let unmatched = count_unmatched_pairs(&treatment, &control);
+    assert_eq!(unmatched.n_pairs(), 9);
+    assert_eq!(unmatched.counts, sample_win_ratio::WinLossCounts::new(2, 3, 1, 2));
+    assert_eq!(unmatched.n_ties, 1);
+}
+
+fn example_arms() -> (Vec<sample_win_ratio::PatientRecord>, Vec<sample_win_ratio::PatientRecord>) {
+    use sample_win_ratio::PatientRecord;
+    let treatment = vec![
+        PatientRecord::new(None, None, 10.0),
+        PatientRecord::new(Some(6.0), Some(2.0), 6.0),
+        PatientRecord::new(None, Some(8.0), 10.0),
+        PatientRecord::new(None, None, 9.0),
+        PatientRecord::new(Some(9.5), None, 9.5),
+    ];
+    let control = vec![
+        PatientRecord::new(Some(4.0), None, 4.0),
+        PatientRecord::new(None, Some(1.0), 10.0),
+        PatientRecord::new(None, None, 10.0),
+        PatientRecord::new(Some(3.0), Some(1.5), 3.0),
+    ];
+    (treatment, control)
+}
+
+#[test]
+fn test_normal_distribution_functions() {
+    assert!((distributions::standard_normal_cdf(1.96) - 0.975_002_104_851_779_5).abs() < 1e-12);
+    assert!((distributions::standard_normal_cdf(-1.0) - 0.158_655_253_931_457_07).abs() < 1e-12);
+    for &p in &[1e-10, 0.01, 0.3, 0.5, 0.9, 0.999] {
+        let x = distributions::standard_normal_quantile(p);
+        assert!((distributions::standard_normal_cdf(x) - p).abs() < 1e-12 * p.max(1e-3));
+    }
+}
+
+#[test]
+fn test_finkelstein_schoenfeld() {
+    let (treatment, control) = example_arms();
+    let analysis = unmatched::finkelstein_schoenfeld(&treatment, &control, 0.95).unwrap();
+
+    // Point estimates agree with the all-pairs counts.
+    let counts = sample_win_ratio::count_unmatched_pairs(&treatment, &control);
+    let n_pairs = counts.n_pairs() as f64;
+    let expected_ratio = sample_win_ratio::calculate_sample_win_ratio(&counts.counts).unwrap();
+    assert!((analysis.win_ratio.estimate - expected_ratio).abs() < 1e-12);
+    let expected_net_benefit = (counts.counts.n_wins() as f64 - counts.counts.n_losses() as f64) / n_pairs;
+    assert!((analysis.net_benefit.estimate - expected_net_benefit).abs() < 1e-12);
+
+    let (lower, upper) = analysis.win_ratio.confidence_interval;
+    assert!(lower < analysis.win_ratio.estimate && analysis.win_ratio.estimate < upper);
+    assert!(analysis.probabilities.win_variance > 0.0);
+
+    // Swapping the arms inverts the win ratio and win odds and negates the net benefit.
+    let swapped = unmatched::finkelstein_schoenfeld(&control, &treatment, 0.95).unwrap();
+    assert!((swapped.win_ratio.estimate * analysis.win_ratio.estimate - 1.0).abs() < 1e-12);
+    assert!((swapped.win_odds.estimate * analysis.win_odds.estimate - 1.0).abs() < 1e-12);
+    assert!((swapped.net_benefit.estimate + analysis.net_benefit.estimate).abs() < 1e-12);
+    assert!((swapped.win_ratio.standard_error - analysis.win_ratio.standard_error).abs() < 1e-12);
+    assert!((swapped.win_ratio.p_value - analysis.win_ratio.p_value).abs() < 1e-12);
+}
+
+#[test]
+fn test_chi_squared_survival() {
+    assert!((distributions::chi_squared_survival(3.841_458_820_694_124, 1.0) - 0.05).abs() < 1e-10);
+    assert!((distributions::chi_squared_survival(5.991_464_547_107_979, 2.0) - 0.05).abs() < 1e-10);
+    assert!((distributions::chi_squared_survival(20.0, 10.0) - 0.029_252_688_076_961).abs() < 1e-10);
+}
+
+#[test]
+fn test_stratified_win_ratio() {
+    use stratified::{stratified_win_ratio, Stratum, StratumWeighting};
+
+    let (treatment, control) = example_arms();
+    let single = unmatched::finkelstein_schoenfeld(&treatment, &control, 0.95).unwrap();
+
+    // Two identical strata are perfectly homogeneous and pool to the per-stratum estimate.
+    let strata = vec![
+        Stratum::new(treatment.clone(), control.clone()),
+        Stratum::new(treatment.clone(), control.clone()),
+    ];
+    for weighting in [StratumWeighting::MantelHaenszel, StratumWeighting::InverseVariance] {
+        let result = stratified_win_ratio(&strata, weighting, 0.95).unwrap();
+        assert!((result.win_ratio.estimate - single.win_ratio.estimate).abs() < 1e-12);
+        assert!((result.win_ratio.standard_error - single.win_ratio.standard_error / 2f64.sqrt()).abs() < 1e-12);
+        assert!((result.strata[0].weight - 0.5).abs() < 1e-12);
+        assert!(result.homogeneity.statistic.abs() < 1e-12);
+        assert_eq!(result.homogeneity.degrees_of_freedom, 1);
+        assert!((result.homogeneity.p_value - 1.0).abs() < 1e-9);
+    }
+
+    // Strata pointing in opposite directions are flagged as heterogeneous.
+    let strata = vec![
+        Stratum::new(treatment.clone(), control.clone()),
+        Stratum::new(control.clone(), treatment.clone()),
+    ];
+    let result = stratified_win_ratio(&strata, StratumWeighting::MantelHaenszel, 0.95).unwrap();
+    assert!((result.win_ratio.estimate - 1.0).abs() < 1e-12);
+    assert!(result.homogeneity.statistic > 0.0);
+    assert!(result.homogeneity.p_value < 1.0);
+    assert_eq!(result.strata[1].counts.n_pairs(), 20);
+
+    // Without a stratum that has both wins and losses, inverse-variance weights cannot pool.
+    let tied: Vec<_> = (0..3).map(|_| sample_win_ratio::PatientRecord::new(None, None, 5.0)).collect();
+    let dying: Vec<_> = (0..3).map(|i| sample_win_ratio::PatientRecord::new(Some(1.0 + i as f64), None, 5.0)).collect();
+    let strata = vec![Stratum::new(tied.clone(), dying.clone()), Stratum::new(dying.clone(), tied.clone())];
+    let result = stratified::stratified_win_ratio_lenient(&strata, StratumWeighting::InverseVariance, 0.95);
+    assert!(result.win_ratio.estimate.is_nan() && result.win_ratio.confidence_interval.1.is_nan());
+    assert!(result.strata.iter().all(|r| r.weight == 0.0));
+    assert_eq!(stratified_win_ratio(&strata, StratumWeighting::InverseVariance, 0.95), Err(Error::EmptyData));
+    // Mantel–Haenszel weights still pool the two strata.
+    let result = stratified_win_ratio(&strata, StratumWeighting::MantelHaenszel, 0.95).unwrap();
+    assert!((result.win_ratio.estimate - 1.0).abs() < 1e-12);
+}
+
+#[test]
+fn test_hierarchy_matches_pocock_counts() {
+    use hierarchy::{OutcomeHierarchy, TimeToEventTier};
+    use sample_win_ratio::PatientRecord;
+
+    let hierarchy = OutcomeHierarchy::new()
+        .with_tier(TimeToEventTier::new("death", |p: &PatientRecord| p.fatal_time, |p: &PatientRecord| p.censoring_time))
+        .with_tier(TimeToEventTier::new("hospitalization", |p: &PatientRecord| p.non_fatal_time, |p: &PatientRecord| p.censoring_time));
+
+    let (treatment, control) = example_arms();
+    let report = hierarchy.compare_unmatched(&treatment, &control);
+    let counts = sample_win_ratio::count_unmatched_pairs(&treatment, &control);
+    assert_eq!(report.to_win_loss_counts(), Some(counts.counts));
+    assert_eq!(report.n_ties, counts.n_ties);
+}
+
+#[test]
+fn test_multi_tier_hierarchy() {
+    use hierarchy::{ContinuousTier, CountTier, Direction, HierarchyOutcome, OutcomeHierarchy, TimeToEventTier};
+
+    struct Patient {
+        death: Option<f64>,
+        follow_up: f64,
+        hospitalizations: u32,
+        quality_of_life: f64,
+        height_m: f64,
+        baseline_weight_kg: f64,
+        final_weight_kg: f64,
+    }
+
+    let patient = |death, hospitalizations, quality_of_life, final_weight_kg| Patient {
+        death,
+        follow_up: 12.0,
+        hospitalizations,
+        quality_of_life,
+        height_m: 1.75,
+        baseline_weight_kg: 100.0,
+        final_weight_kg,
+    };
+
+    let hierarchy = OutcomeHierarchy::new()
+        .with_tier(TimeToEventTier::new("death", |p: &Patient| p.death, |p: &Patient| p.follow_up))
+        .with_tier(CountTier::new("hospitalizations", |p: &Patient| p.hospitalizations, Direction::LowerIsBetter))
+        .with_tier(ContinuousTier::new("quality of life", |p: &Patient| p.quality_of_life, 5.0, Direction::HigherIsBetter))
+        .with_tier(ContinuousTier::new(
+            "BMI change",
+            |p: &Patient| bmi::calculate_bmi_lenient(p.final_weight_kg, p.height_m) - bmi::calculate_bmi_lenient(p.baseline_weight_kg, p.height_m),
+            1.0,
+            Direction::LowerIsBetter,
+        ));
+    assert_eq!(hierarchy.len(), 4);
+
+    let pairs = vec![
+        // Control dies: win on the first tier.
+        (patient(None, 3, 50.0, 100.0), patient(Some(6.0), 0, 80.0, 90.0)),
+        // Fewer hospitalizations for the control patient: loss on the second tier.
+        (patient(None, 2, 80.0, 90.0), patient(None, 1, 50.0, 100.0)),
+        // Quality of life within the margin; a 10 kg larger weight loss wins on BMI change.
+        (patient(None, 1, 62.0, 85.0), patient(None, 1, 60.0, 95.0)),
+        // Everything within the margins.
+        (patient(None, 0, 70.0, 99.0), patient(None, 0, 72.0, 99.5)),
+    ];
+    assert_eq!(hierarchy.compare(&pairs[2].0, &pairs[2].1), HierarchyOutcome::Win { tier: 3 });
+
+    let report = hierarchy.compare_matched(&pairs);
+    let wins: Vec<u32> = report.tiers.iter().map(|t| t.wins).collect();
+    let losses: Vec<u32> = report.tiers.iter().map(|t| t.losses).collect();
+    assert_eq!(wins, vec![1, 0, 0, 1]);
+    assert_eq!(losses, vec![0, 1, 0, 0]);
+    assert_eq!(report.n_ties, 1);
+    assert_eq!(report.tiers[3].name, "BMI change");
+    assert!((report.win_ratio().unwrap() - 2.0).abs() < FLOAT_TOLERANCE);
+    assert_eq!(report.to_win_loss_counts(), None);
+}
+
+#[test]
+fn test_confidence_interval_methods() {
+    use sample_win_ratio::{calculate_confidence_interval_with_method, calculate_p_value, ConfidenceIntervalMethod};
+
+    let (n_w, n_l) = (35, 15);
+
+    // The Wald method at 95% reproduces the original interval.
+    let wald = calculate_confidence_interval_with_method(n_w, n_l, 0.95, ConfidenceIntervalMethod::Wald).unwrap();
+    let original = sample_win_ratio::calculate_confidence_interval(n_w, n_l).unwrap();
+    assert!((wald.0 - original.0).abs() < FLOAT_TOLERANCE);
+    assert!((wald.1 - original.1).abs() < FLOAT_TOLERANCE);
+
+    let expected = [
+        (ConfidenceIntervalMethod::Wilson, 0.95, (1.285_696, 4.234_628)),
+        (ConfidenceIntervalMethod::ClopperPearson, 0.95, (1.241_739, 4.598_545)),
+        (ConfidenceIntervalMethod::ClopperPearson, 0.90, (1.359_977, 4.131_234)),
+        (ConfidenceIntervalMethod::LogDelta, 0.95, (1.274_355, 4.272_315)),
+    ];
+    for (method, level, (lower, upper)) in expected {
+        let interval = calculate_confidence_interval_with_method(n_w, n_l, level, method).unwrap();
+        assert!((interval.0 - lower).abs() < 1e-5, "{:?} lower: {}", method, interval.0);
+        assert!((interval.1 - upper).abs() < 1e-5, "{:?} upper: {}", method, interval.1);
+    }
+
+    // Wald p-value matches the significance test statistic.
+    let wald_p = calculate_p_value(n_w, n_l, ConfidenceIntervalMethod::Wald).unwrap();
+    let z = sample_win_ratio::calculate_significance_test_statistic(n_w, n_l).unwrap();
+    assert!((wald_p - distributions::two_sided_p_value(z)).abs() < 1e-12);
+    let exact_p = calculate_p_value(n_w, n_l, ConfidenceIntervalMethod::ClopperPearson).unwrap();
+    assert!((exact_p - 0.006_600_448).abs() < 1e-8);
+
+    // With no losses the interval is unbounded above instead of collapsing to zero.
+    let all_wins = calculate_confidence_interval_with_method(10, 0, 0.95, ConfidenceIntervalMethod::Wald).unwrap();
+    assert_eq!(all_wins.1, f64::INFINITY);
+}
+
+#[test]
+fn test_bootstrap_is_reproducible() {
+    let (treatment, control) = example_arms();
+    let first = resampling::bootstrap(&treatment, &control, 2000, 0.95, &mut StdRng::seed_from_u64(42)).unwrap();
+    let second = resampling::bootstrap(&treatment, &control, 2000, 0.95, &mut StdRng::seed_from_u64(42)).unwrap();
+    assert_eq!(first, second);
+
+    let analysis = unmatched::finkelstein_schoenfeld(&treatment, &control, 0.95).unwrap();
+    let nb = first.net_benefit;
+    assert!((nb.estimate - analysis.net_benefit.estimate).abs() < 1e-12);
+    for (lower, upper) in [nb.percentile, nb.bca] {
+        assert!(lower < nb.estimate && nb.estimate < upper);
+        assert!(lower >= -1.0 && upper <= 1.0);
+    }
+    assert!(first.win_odds.percentile.0 < first.win_odds.estimate);
+
+    // When every pair ties the win ratio is undefined in every resample.
+    let tied: Vec<_> = (0..5).map(|_| sample_win_ratio::PatientRecord::new(None, None, 5.0)).collect();
+    assert_eq!(resampling::bootstrap(&tied, &tied, 200, 0.95, &mut StdRng::seed_from_u64(7)), Err(Error::EmptyData));
+    let result = resampling::bootstrap_lenient(&tied, &tied, 200, 0.95, &mut StdRng::seed_from_u64(7));
+    assert!(result.win_ratio.percentile.0.is_nan() && result.win_ratio.percentile.1.is_nan());
+    assert!(result.win_ratio.bca.0.is_nan() && result.win_ratio.bca.1.is_nan());
+    assert_eq!(result.net_benefit.percentile, (0.0, 0.0));
+    // So is every statistic when an arm is empty.
+    assert_eq!(resampling::bootstrap(&treatment, &[], 200, 0.95, &mut StdRng::seed_from_u64(7)), Err(Error::EmptyData));
+    let result = resampling::bootstrap_lenient(&treatment, &[], 200, 0.95, &mut StdRng::seed_from_u64(7));
+    assert!(result.net_benefit.percentile.0.is_nan() && result.win_odds.bca.1.is_nan());
+}
+
+#[test]
+fn test_permutation_test() {
+    let (treatment, control) = example_arms();
+
+    // C(9, 5) = 126 assignments are enumerated exactly.
+    let exact = resampling::permutation_test(&treatment, &control, 1000, 0, &mut StdRng::seed_from_u64(1)).unwrap();
+    assert!(exact.exact);
+    assert_eq!(exact.n_permutations, 126);
+    assert!(exact.p_value > 0.0 && exact.p_value <= 1.0);
+
+    let monte_carlo = resampling::permutation_test(&treatment, &control, 0, 4000, &mut StdRng::seed_from_u64(1)).unwrap();
+    assert!(!monte_carlo.exact);
+    assert!((monte_carlo.p_value - exact.p_value).abs() < 0.03);
+    assert_eq!(monte_carlo.observed_net_benefit, exact.observed_net_benefit);
+}
+
+#[test]
+fn test_sampler_matches_joint_survival() {
+    use simulation::{sample_event_times, Arm};
+
+    let params = simulation::SimulationParams::new(0.1, 0.2, 0.5, 0.7);
+    let mut rng = StdRng::seed_from_u64(7);
+    let n = 20_000;
+    let (t, x) = (4.0, 2.0);
+    for arm in [Arm::Control, Arm::Treatment] {
+        let survivors = (0..n)
+            .map(|_| sample_event_times(&params, arm, &mut rng).unwrap())
+            .filter(|&(ti, xi)| ti > t && xi > x)
+            .count();
+        let expected = match arm {
+            Arm::Control => simulation::joint_survival_function_control(t, x, &params),
+            Arm::Treatment => simulation::joint_survival_function_treatment(t, x, &params),
+        };
+        assert!((survivors as f64 / n as f64 - expected).abs() < 0.01);
+    }
+}
+
+#[test]
+fn test_simulation_study_recovers_win_ratio_parameter() {
+    use simulation::{run_simulation_study, CensoringDesign, SimulationStudySummary};
+
+    let params = simulation::SimulationParams::new(0.1, 0.2, 0.8, 0.7);
+    let mut rng = StdRng::seed_from_u64(2024);
+
+    let summary = run_simulation_study(&params, &CensoringDesign::administrative(5.0), 150, 40, &mut rng).unwrap();
+    assert_eq!(summary.unmatched_win_ratios.len(), 40);
+    let unmatched = SimulationStudySummary::geometric_mean(&summary.unmatched_win_ratios);
+    let matched = SimulationStudySummary::geometric_mean(&summary.matched_win_ratios);
+    assert!((unmatched - summary.theoretical_win_ratio).abs() < 0.05, "unmatched = {}", unmatched);
+    assert!((matched - summary.theoretical_win_ratio).abs() < 0.1, "matched = {}", matched);
+
+    // Random dropout changes which pairs are compared, not the win ratio under this model.
+    let summary = run_simulation_study(&params, &CensoringDesign::new(5.0, 0.1), 150, 40, &mut rng).unwrap();
+    let unmatched = SimulationStudySummary::geometric_mean(&summary.unmatched_win_ratios);
+    assert!((unmatched - summary.theoretical_win_ratio).abs() < 0.05, "unmatched = {}", unmatched);
+}
+
+#[test]
+fn test_analytic_sample_size() {
+    // Yu and Ganju: WR = 1.5 without ties, 1:1 allocation, 5% two-sided alpha and 80% power.
+    let n = power::unmatched_sample_size(1.5, 0.0, 0.5, 0.05, 0.8);
+    assert_eq!(n, 255);
+    assert!(power::unmatched_power(1.5, 0.0, 0.5, 0.05, n) >= 0.8);
+    assert!(power::unmatched_power(1.5, 0.0, 0.5, 0.05, n - 2) < 0.8);
+
+    // Ties inflate the sample size by (1 + p_tie) / (1 - p_tie), and the matched-pairs test
+    // needs more patients.
+    let with_ties = power::unmatched_sample_size(1.5, 0.3, 0.5, 0.05, 0.8);
+    assert_eq!(with_ties, 473);
+    assert!(power::unmatched_power(1.5, 0.3, 0.5, 0.05, with_ties) >= 0.8);
+    assert!(power::unmatched_power(1.5, 0.3, 0.5, 0.05, with_ties - 2) < 0.8);
+    let matched = power::matched_pairs_sample_size(1.5, 0.0, 0.05, 0.8);
+    assert!(matched > n);
+    assert!(power::matched_pairs_power(1.5, 0.0, 0.05, matched) >= 0.8);
+}
+
+#[test]
+fn test_simulated_power_curve() {
+    use power::{sample_size_for_model, simulate_power_curve, WinRatioTest};
+
+    let params = simulation::SimulationParams::new(0.1, 0.2, 0.8, 0.6);
+    let design = simulation::CensoringDesign::administrative(5.0);
+    let mut rng = StdRng::seed_from_u64(11);
+
+    for test in [WinRatioTest::Unmatched, WinRatioTest::MatchedPairs] {
+        let n = sample_size_for_model(&params, design.follow_up, test, 0.05, 0.8);
+        let curve = simulate_power_curve(&params, &[1.0, 0.6], &[n], &design, test, 0.05, 300, &mut rng);
+
+        // Under the null the rejection rate is close to alpha.
+        assert!((curve[0].win_ratio - 1.0).abs() < 1e-9);
+        assert!(curve[0].simulated_power < 0.1, "{:?}: {:?}", test, curve[0]);
+
+        // At the planned sample size the simulated power is close to the target.
+        assert!((curve[1].analytic_power - 0.8).abs() < 0.02);
+        assert!((curve[1].simulated_power - 0.8).abs() < 0.1, "{:?}: {:?}", test, curve[1]);
+    }
+
+    // With a short follow-up about half the pairs tie, and the planned trial keeps its power.
+    let design = simulation::CensoringDesign::administrative(1.0);
+    let n = sample_size_for_model(&params, design.follow_up, WinRatioTest::Unmatched, 0.05, 0.8);
+    let curve = simulate_power_curve(&params, &[0.6], &[n], &design, WinRatioTest::Unmatched, 0.05, 200, &mut rng);
+    assert!(curve[0].simulated_power >= 0.75, "{:?}", curve[0]);
+}
+
+#[test]
+fn test_simulated_power_counts_all_wins_matched_pairs_trials() {
+    use power::{simulate_power_curve, WinRatioTest};
+
+    // A large effect, so most small trials have only wins among their decided pairs.
+    let params = simulation::SimulationParams::new(1.0, 2.0, 1.0, 1.0);
+    let design = simulation::CensoringDesign::administrative(5.0);
+    let mut rng = StdRng::seed_from_u64(3);
+
+    let curve = simulate_power_curve(&params, &[0.01], &[20], &design, WinRatioTest::MatchedPairs, 0.05, 200, &mut rng);
+    assert!(curve[0].simulated_power > 0.9, "{:?}", curve[0]);
+}
+
+#[test]
+fn test_win_and_loss_probabilities_values() {
+    let params = simulation::SimulationParams::new(0.1, 0.2, 0.8, 0.6);
+    let c = 5.0;
+    let s0_at_c = simulation::marginal_survival_t_control(c, &params);
+    let s1_at_c = simulation::marginal_survival_t_treatment(c, &params);
+    let win_prob = probability_win_ratio::calculate_win_probability(
+        |t| simulation::marginal_survival_t_treatment(t, &params),
+        |t| simulation::pdf_t_control(t, &params).unwrap(),
+        |x| simulation::conditional_survival_x_given_t_treatment(x, c, &params),
+        |x| simulation::pdf_x_given_t_control(x, c, &params).unwrap(),
+        s0_at_c, s1_at_c, c, 1e-8,
+    );
+    let loss_prob = probability_win_ratio::calculate_loss_probability(
+        |t| simulation::marginal_survival_t_control(t, &params),
+        |t| simulation::pdf_t_treatment(t, &params).unwrap(),
+        |x| simulation::conditional_survival_x_given_t_control(x, c, &params),
+        |x| simulation::pdf_x_given_t_treatment(x, c, &params).unwrap(),
+        s0_at_c, s1_at_c, c, 1e-8,
+    );
+
+    // Reference values from high-precision quadrature of the paper's formulas. The fatal-event
+    // densities are singular at t = 0 for α < 1, which limits Clenshaw–Curtis to about 1e-4.
+    assert!((win_prob - 0.540_674_535).abs() < 1e-4);
+    assert!((loss_prob - 0.359_299_751).abs() < 1e-4);
+    // The non-fatal terms S1(c) S0(c) ∫ G f(x|c) dx are about 0.170_853 and 0.113_539. They used
+    // to be subtracted, which gave W(c) = 0.198_917 and L(c) = 0.132_189; adding them moves each
+    // probability by twice the term.
+    assert!((win_prob - (0.198_917_376 + 2.0 * 0.170_852_871)).abs() < 2e-4);
+    assert!((loss_prob - (0.132_188_514 + 2.0 * 0.113_538_534)).abs() < 2e-4);
+    // Ties can only occur when both patients survive past c.
+    assert!(1.0 - win_prob - loss_prob <= s0_at_c * s1_at_c);
+}
+
+#[test]
+fn test_model_probability_win_ratio() {
+    let params = simulation::SimulationParams::new(0.1, 0.2, 0.8, 0.6);
+
+    // Agrees with the hand-wired closures.
+    let at_five = probability_win_ratio::model_probability_win_ratio(&params, 5.0, 1e-8);
+    assert!((at_five.win_probability - 0.540_674_535).abs() < 1e-4);
+    assert!((at_five.loss_probability - 0.359_299_751).abs() < 1e-4);
+    assert!((at_five.win_ratio - at_five.win_probability / at_five.loss_probability).abs() < 1e-12);
+    assert!(at_five.tie_probability() > 0.0);
+
+    // No-censoring limit: ties vanish and PR(c) approaches 1 / θ^α.
+    let at_limit = probability_win_ratio::model_probability_win_ratio(&params, 100.0, 1e-8);
+    assert!(at_limit.tie_probability().abs() < 2e-3);
+    assert!((at_limit.win_ratio - simulation::win_ratio_parameter(&params)).abs() < 2e-3);
+
+    // Without censoring the limit is reached exactly; tanh-sinh handles the t^(α-1) singularity.
+    let quadrature = Quadrature::new(Method::TanhSinh, 1e-12);
+    let result = probability_win_ratio::model_probability_win_ratio_with(&params, f64::INFINITY, &quadrature);
+    let uncensored = result.probabilities;
+    assert!(uncensored.tie_probability().abs() < 1e-9);
+    assert!((uncensored.win_ratio - simulation::win_ratio_parameter(&params)).abs() < 1e-8);
+    assert!(result.evaluations() > 0 && result.error_estimate() < 1e-6);
+    assert_eq!(result.evaluations(), result.win_integration.evaluations + result.loss_integration.evaluations);
+    assert!(probability_win_ratio::no_censoring_consistency(&params, 1e-12).unwrap() < 1e-8);
+    let harmful = simulation::SimulationParams::new(0.05, 0.3, 0.5, 1.5);
+    assert!(probability_win_ratio::no_censoring_consistency(&harmful, 1e-12).unwrap() < 1e-8);
+    // A stronger singularity exhausts the tanh-sinh levels before the tolerance is met.
+    let singular = simulation::SimulationParams::new(0.05, 0.3, 0.4, 1.5);
+    assert!(matches!(
+        probability_win_ratio::no_censoring_consistency(&singular, 1e-12),
+        Err(Error::NoConvergence { .. })
+    ));
+    assert!(matches!(
+        probability_win_ratio::no_censoring_consistency(&simulation::SimulationParams::new(0.1, 0.2, 0.0, 0.6), 1e-12),
+        Err(Error::InvalidParameter { name: "alpha", .. })
+    ));
+    assert!(matches!(
+        probability_win_ratio::no_censoring_consistency(&params, 0.0),
+        Err(Error::InvalidParameter { name: "tolerance", .. })
+    ));
+
+    // The backends agree at a finite follow-up, and report their error and evaluations.
+    let s0 = |t: f64| simulation::marginal_survival_t_control(t, &params);
+    let s1_at_c = simulation::marginal_survival_t_treatment(5.0, &params);
+    let kronrod = probability_win_ratio::calculate_loss_probability_with(
+        s0,
+        |t| simulation::pdf_t_treatment_lenient(t, &params),
+        |x| simulation::conditional_survival_x_given_t_control(x, 5.0, &params),
+        |x| simulation::pdf_x_given_t_treatment_lenient(x, 5.0, &params),
+        s0(5.0),
+        s1_at_c,
+        5.0,
+        &Quadrature::new(Method::GaussKronrod, 1e-10),
+    );
+    assert!((kronrod.integral - at_five.loss_probability).abs() < 1e-4);
+    assert!(kronrod.evaluations > 0 && kronrod.error_estimate >= 0.0);
+}
+
+#[test]
+fn test_time_dependent_curves() {
+    let (treatment, control) = example_arms();
+    let curve = time_dependent::empirical_curve(&treatment, &control, &[2.0, 10.0], 0.95).unwrap();
+
+    // At c = 2 only the non-fatal events at 1.0, 1.5 (control) and 2.0 (treatment) are seen:
+    // 10 wins, 2 losses and 8 ties over 20 pairs.
+    let early = curve.points[0];
+    assert!((early.win_probability - 0.5).abs() < 1e-12);
+    assert!((early.loss_probability - 0.1).abs() < 1e-12);
+    assert!((early.win_ratio - 5.0).abs() < 1e-12);
+    let (lower, upper) = early.confidence_interval.unwrap();
+    assert!(lower < 5.0 && 5.0 < upper);
+
+    // Beyond the last censoring time the curve matches the full analysis.
+    let full = unmatched::finkelstein_schoenfeld(&treatment, &control, 0.95).unwrap();
+    assert_eq!(curve.points[1].win_ratio, full.win_ratio.estimate);
+    assert_eq!(curve.points[1].confidence_interval, Some(full.win_ratio.confidence_interval));
+
+    let csv = curve.to_csv();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0], "follow_up,win_probability,loss_probability,tie_probability,win_ratio,lower,upper");
+    assert!(lines[1].starts_with("2,0.5,0.1,"));
+
+    let params = simulation::SimulationParams::new(0.1, 0.2, 0.8, 0.6);
+    let model = time_dependent::model_curve(&params, &[1.0, 5.0], 1e-8).unwrap();
+    let at_five = probability_win_ratio::model_probability_win_ratio(&params, 5.0, 1e-8);
+    assert_eq!(model.points[1].win_ratio, at_five.win_ratio);
+    assert!(model.points[0].tie_probability() > model.points[1].tie_probability());
+    assert!(model.to_csv().lines().nth(1).unwrap().ends_with(",,"));
+}
+
+#[test]
+fn test_copula_families() {
+    use copula::{ClaytonCopula, Copula, FrankCopula, GaussianCopula, GumbelHougaardCopula, IndependenceCopula};
+
+    fn check<C: Copula>(copula: &C) {
+        let h = 1e-6;
+        for &(u, v) in &[(0.2, 0.7), (0.5, 0.5), (0.9, 0.3)] {
+            // Uniform margins and the conditional distributions are the partial derivatives.
+            assert!((copula.cdf(u, 1.0) - u).abs() < 1e-9);
+            assert!((copula.cdf(1.0, v) - v).abs() < 1e-9);
+            let numeric_u = (copula.cdf(u + h, v) - copula.cdf(u - h, v)) / (2.0 * h);
+            let numeric_v = (copula.cdf(u, v + h) - copula.cdf(u, v - h)) / (2.0 * h);
+            assert!((copula.partial_u(u, v) - numeric_u).abs() < 1e-5);
+            assert!((copula.partial_v(u, v) - numeric_v).abs() < 1e-5);
+            let w = copula.partial_u(u, v);
+            assert!((copula.inverse_partial_u(u, w) - v).abs() < 1e-8);
+        }
+
+        let mut rng = StdRng::seed_from_u64(11);
+        let n = 20_000;
+        let hits = (0..n)
+            .filter(|_| {
+                let (u, v) = copula.sample(&mut rng);
+                u <= 0.3 && v <= 0.6
+            })
+            .count();
+        assert!((hits as f64 / n as f64 - copula.cdf(0.3, 0.6)).abs() < 0.015);
+    }
+
+    check(&IndependenceCopula);
+    check(&GumbelHougaardCopula::new(0.6));
+    check(&ClaytonCopula::new(2.0));
+    check(&FrankCopula::new(-4.0));
+    check(&GaussianCopula::new(0.5));
+    // Deep in the tail the Clayton conditional distribution stays finite instead of inf * 0.
+    assert!((ClaytonCopula::new(2.0).partial_u(1e-200, 1e-150) - 1.0).abs() < 1e-12);
+    assert!((GaussianCopula::from_kendall_tau(1.0 / 3.0).rho - 0.5).abs() < 1e-12);
+}
+
+#[test]
+fn test_copula_models() {
+    use copula::{ClaytonCopula, CopulaModel, GumbelHougaardCopula};
+    use simulation::{Arm, JointSurvivalModel};
+
+    let params = simulation::SimulationParams::new(0.1, 0.2, 0.8, 0.6);
+    let c = 5.0;
+
+    // The Gumbel–Hougaard copula reproduces the paper's model.
+    let gumbel = CopulaModel::from_params(&params, GumbelHougaardCopula::new(params.alpha));
+    for &arm in &[Arm::Control, Arm::Treatment] {
+        for &(t, x) in &[(1.0, 2.0), (3.0, 0.5)] {
+            assert!((gumbel.joint_survival(arm, t, x) - params.joint_survival(arm, t, x)).abs() < 1e-12);
+            assert!((gumbel.pdf_x_given_t(arm, x, c) - params.pdf_x_given_t(arm, x, c)).abs() < 1e-12);
+        }
+    }
+    let expected = probability_win_ratio::model_probability_win_ratio(&params, c, 1e-8);
+    let from_copula = probability_win_ratio::model_probability_win_ratio(&gumbel, c, 1e-8);
+    // The integrands only differ at x = 0, where the copula form of f(x|c) is 0 * ∞.
+    assert!((expected.win_probability - from_copula.win_probability).abs() < 1e-5);
+    assert!((expected.loss_probability - from_copula.loss_probability).abs() < 1e-5);
+
+    // Under a Clayton copula the integrals agree with simulated trials.
+    let clayton = CopulaModel::from_params(&params, ClaytonCopula::new(2.0));
+    let model = probability_win_ratio::model_probability_win_ratio(&clayton, c, 1e-8);
+    let mut rng = StdRng::seed_from_u64(17);
+    let design = simulation::CensoringDesign::administrative(c);
+    let trial = simulation::simulate_trial(&clayton, &design, 600, 600, &mut rng).unwrap();
+    let observed = unmatched::pairwise_probabilities(&trial.treatment, &trial.control).unwrap();
+    assert!((observed.win_probability - model.win_probability).abs() < 0.03);
+    assert!((observed.loss_probability - model.loss_probability).abs() < 0.03);
+}
+
+#[test]
+fn test_recurrent_event_comparisons() {
+    use recurrent::RecurrentEventHistory;
+
+    let treatment = vec![
+        RecurrentEventHistory::new(None, vec![4.0, 1.0], 10.0).unwrap(),
+        RecurrentEventHistory::new(Some(6.0), vec![2.0], 6.0).unwrap(),
+        RecurrentEventHistory::new(None, vec![9.0], 10.0).unwrap(),
+    ];
+    let control = vec![
+        RecurrentEventHistory::new(None, vec![1.5, 3.0, 8.0], 10.0).unwrap(),
+        RecurrentEventHistory::new(Some(5.0), vec![], 5.0).unwrap(),
+    ];
+    assert_eq!(treatment[0].event_times, vec![1.0, 4.0]);
+    assert_eq!(treatment[0].events_within(3.0), 1);
+    assert!(matches!(
+        RecurrentEventHistory::new(None, vec![1.0, f64::NAN], 10.0),
+        Err(Error::InvalidParameter { name: "event_times", .. })
+    ));
+
+    // Pairs: (2 vs 3 events) win, (control death at 5) win, (treatment death at 6) loss,
+    // (control death at 5 before treatment death at 6) win, (1 vs 3 events) win, (death 5) win.
+    let report = recurrent::recurrent_hierarchy().compare_unmatched(&treatment, &control);
+    assert_eq!(report.tiers[0].wins, 3);
+    assert_eq!(report.tiers[0].losses, 1);
+    assert_eq!(report.tiers[1].wins, 2);
+    assert_eq!(report.tiers[1].losses, 0);
+    assert_eq!(report.n_ties, 0);
+
+    let analysis = recurrent::finkelstein_schoenfeld_recurrent(&treatment, &control, 0.95).unwrap();
+    assert!((analysis.probabilities.win_probability - 5.0 / 6.0).abs() < 1e-12);
+    assert!((analysis.win_ratio.estimate - 5.0).abs() < 1e-12);
+}
+
+#[test]
+fn test_recurrent_event_simulation() {
+    let mut rng = StdRng::seed_from_u64(23);
+    let design = simulation::CensoringDesign::administrative(5.0);
+
+    // Without deaths, the counts have mean λ2 c and variance μ + σ²μ².
+    let params = simulation::SimulationParams::new(1e-9, 0.4, 1.0, 1.0).with_recurrent_events(0.5);
+    let trial = recurrent::simulate_recurrent_trial(&params, &design, 0, 20_000, &mut rng).unwrap();
+    let counts: Vec<f64> = trial.control.iter().map(|p| p.event_times.len() as f64).collect();
+    let mean = counts.iter().sum::<f64>() / counts.len() as f64;
+    let variance = counts.iter().map(|k| (k - mean).powi(2)).sum::<f64>() / (counts.len() - 1) as f64;
+    assert!((mean - 2.0).abs() < 0.05);
+    assert!((variance - 4.0).abs() < 0.3);
+
+    // A beneficial treatment gives a win ratio above one.
+    let params = simulation::SimulationParams::new(0.1, 0.3, 0.8, 0.6).with_recurrent_events(0.5);
+    let trial = recurrent::simulate_recurrent_trial(&params, &design, 300, 300, &mut rng).unwrap();
+    assert!(trial.control.iter().all(|p| p.event_times.iter().all(|&e| e < p.censoring_time)));
+    let analysis = recurrent::finkelstein_schoenfeld_recurrent(&trial.treatment, &trial.control, 0.95).unwrap();
+    assert!(analysis.win_ratio.estimate > 1.0);
+    assert!(analysis.win_ratio.p_value < 0.05);
+}
+
+#[test]
+fn test_kaplan_meier_and_nelson_aalen() {
+    let observations = [(1.0, true), (2.0, false), (3.0, true), (3.0, true), (4.0, false), (5.0, true)];
+
+    let km = nonparametric::KaplanMeier::new(&observations);
+    assert_eq!(km.times, vec![1.0, 3.0, 5.0]);
+    assert_eq!(km.at_risk, vec![6, 4, 1]);
+    assert_eq!(km.survival_at(0.5), 1.0);
+    assert!((km.survival_at(2.0) - 5.0 / 6.0).abs() < 1e-12);
+    assert!((km.survival_at(3.0) - 5.0 / 12.0).abs() < 1e-12);
+    assert_eq!(km.survival_at(5.0), 0.0);
+    // Greenwood: S(3)² (1 / (6 * 5) + 2 / (4 * 2)).
+    assert!((km.variance_at(3.5) - 25.0 / 144.0 * (1.0 / 30.0 + 0.25)).abs() < 1e-12);
+    let (lower, upper) = km.confidence_interval_at(3.0, 0.95);
+    assert!(0.0 < lower && lower < 5.0 / 12.0 && 5.0 / 12.0 < upper && upper < 1.0);
+
+    let na = nonparametric::NelsonAalen::new(&observations);
+    assert!((na.cumulative_hazard_at(3.0) - 2.0 / 3.0).abs() < 1e-12);
+    assert!((na.cumulative_hazard_at(6.0) - 5.0 / 3.0).abs() < 1e-12);
+    assert!((na.variance_at(3.0) - (1.0 / 36.0 + 2.0 / 16.0)).abs() < 1e-12);
+    assert!((na.survival_at(3.0) - (-2.0_f64 / 3.0).exp()).abs() < 1e-12);
+
+    // The smoothed hazard of censored exponential data recovers the constant rate.
+    let mut rng = StdRng::seed_from_u64(29);
+    let data: Vec<(f64, bool)> = (0..20_000)
+        .map(|_| {
+            let t = distributions::sample_standard_exponential(&mut rng) / 0.5;
+            let c = distributions::sample_standard_exponential(&mut rng) / 0.2;
+            (t.min(c), t <= c)
+        })
+        .collect();
+    let smoothed = nonparametric::SmoothedSurvival::new(&data, 0.5);
+    assert!((smoothed.hazard.hazard_at(2.0) - 0.5).abs() < 0.05);
+    assert!((smoothed.density_at(2.0) - 0.5 * (-1.0_f64).exp()).abs() < 0.02);
+}
+
+#[test]
+fn test_empirical_probability_win_ratio() {
+    let params = simulation::SimulationParams::new(0.1, 0.2, 0.8, 0.6);
+    let c = 5.0;
+    let mut rng = StdRng::seed_from_u64(31);
+    let design = simulation::CensoringDesign::new(8.0, 0.02);
+    let trial = simulation::simulate_trial(&params, &design, 2_000, 2_000, &mut rng).unwrap();
+
+    let model = probability_win_ratio::model_probability_win_ratio(&params, c, 1e-8);
+    let estimate = nonparametric::empirical_probability_win_ratio(&trial.treatment, &trial.control, c, 0.2, 1e-6);
+    assert!((estimate.win_probability - model.win_probability).abs() < 0.03);
+    assert!((estimate.loss_probability - model.loss_probability).abs() < 0.03);
+    assert!((estimate.win_ratio - model.win_ratio).abs() < 0.15);
+
+    // The step-function integrands are integrated adaptively, with the diagnostics exposed:
+    // 1e-6 is out of reach with thousands of jumps, but the error stays well below 1e-4.
+    let integrate = |method, tolerance| {
+        let quadrature = Quadrature::new(method, tolerance);
+        nonparametric::empirical_probability_win_ratio_with(&trial.treatment, &trial.control, c, 0.2, &quadrature)
+    };
+    let fine = integrate(Method::GaussKronrod, 1e-6);
+    assert_eq!(fine.probabilities, estimate);
+    assert!(!fine.converged() && fine.error_estimate() < 1e-4);
+    let coarse = integrate(Method::GaussKronrod, 1e-4);
+    assert!(coarse.converged() && coarse.evaluations() < fine.evaluations());
+    assert!((coarse.probabilities.win_probability - estimate.win_probability).abs() < 1e-4);
+}
+
+#[test]
+fn test_ipcw_win_ratio() {
+    let params = simulation::SimulationParams::new(0.1, 0.2, 0.8, 0.6);
+    let tau = 5.0;
+    let mut rng = StdRng::seed_from_u64(37);
+
+    // With administrative censoring at τ every comparison is observed and the weights are one.
+    let design = simulation::CensoringDesign::administrative(tau);
+    let trial = simulation::simulate_trial(&params, &design, 200, 200, &mut rng).unwrap();
+    let result = ipcw::ipcw_win_ratio(&trial.treatment, &trial.control, tau, 0.95).unwrap();
+    assert!((result.weighted.win_ratio.estimate - result.unweighted.win_ratio.estimate).abs() < 1e-12);
+    assert!((result.adjustment_ratio() - 1.0).abs() < 1e-12);
+
+    // Heavy dropout in the treatment arm only: the unweighted estimate drifts, IPCW does not.
+    let treatment = simulation::simulate_trial(&params, &simulation::CensoringDesign::new(tau, 0.3), 1_500, 0, &mut rng).unwrap().treatment;
+    let control = simulation::simulate_trial(&params, &design, 0, 1_500, &mut rng).unwrap().control;
+    let result = ipcw::ipcw_win_ratio(&treatment, &control, tau, 0.95).unwrap();
+    let model = probability_win_ratio::model_probability_win_ratio(&params, tau, 1e-8);
+    let weighted = result.weighted.probabilities;
+    let unweighted = result.unweighted.probabilities;
+    assert!((weighted.win_probability - model.win_probability).abs() < 0.03);
+    assert!((weighted.loss_probability - model.loss_probability).abs() < 0.03);
+    assert!((unweighted.win_probability - model.win_probability).abs() > 0.1);
+    let (lower, upper) = result.weighted.win_ratio.confidence_interval;
+    assert!(lower < model.win_ratio && model.win_ratio < upper);
+
+    // The variance accounts for the estimated censoring weights: it matches the spread of
+    // W and L across repeated trials with heavy dropout in both arms, where treating the
+    // weights as known overstates it more than twofold.
+    let dropout = simulation::CensoringDesign::new(tau, 0.25);
+    let replicates: Vec<_> = (0..200)
+        .map(|_| {
+            let trial = simulation::simulate_trial(&params, &dropout, 150, 150, &mut rng).unwrap();
+            ipcw::ipcw_win_ratio(&trial.treatment, &trial.control, tau, 0.95).unwrap().weighted.probabilities
+        })
+        .collect();
+    let check = |estimate: fn(&unmatched::PairwiseProbabilities) -> (f64, f64)| {
+        let (values, variances): (Vec<f64>, Vec<f64>) = replicates.iter().map(estimate).unzip();
+        let mean = values.iter().sum::<f64>() / 200.0;
+        let empirical = values.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / 199.0;
+        let estimated = variances.iter().sum::<f64>() / 200.0;
+        assert!((estimated / empirical - 1.0).abs() < 0.25, "estimated {estimated}, empirical {empirical}");
+    };
+    check(|p| (p.win_probability, p.win_variance));
+    check(|p| (p.loss_probability, p.loss_variance));
+}
+
+#[test]
+fn test_win_ratio_regression() {
+    use nalgebra::DMatrix;
+
+    // With only a treatment indicator, exp(β) is the all-pairs win ratio.
+    let (treatment, control) = example_arms();
+    let patients: Vec<_> = treatment.iter().chain(control.iter()).copied().collect();
+    let indicator = DMatrix::from_fn(patients.len(), 1, |i, _| if i < treatment.len() { 1.0 } else { 0.0 });
+    let fit = regression::win_ratio_regression(&patients, &indicator, &["treatment"], 0.95).unwrap();
+    let fs = unmatched::finkelstein_schoenfeld(&treatment, &control, 0.95).unwrap();
+    assert!((fit.coefficients[0].win_ratio - fs.win_ratio.estimate).abs() < 1e-9);
+
+    // Rates scale with θ for treatment and with exp(0.05 (BMI - 25)), so in the uncensored
+    // limit the log win ratios are -α log θ and -0.05 α per unit of BMI.
+    let base = simulation::SimulationParams::new(0.1, 0.2, 0.8, 1.0);
+    let design = simulation::CensoringDesign::administrative(60.0);
+    let mut rng = StdRng::seed_from_u64(41);
+    let n = 400;
+    let mut patients = Vec::with_capacity(n);
+    let mut covariates = DMatrix::zeros(n, 2);
+    for i in 0..n {
+        let treated = i % 2 == 0;
+        let weight = 60.0 + 40.0 * rand::Rng::gen_range(&mut rng, 0.0..1.0_f64);
+        let height = 1.6 + 0.25 * rand::Rng::gen_range(&mut rng, 0.0..1.0_f64);
+        let bmi = bmi::calculate_bmi(weight, height).unwrap();
+        let multiplier = if treated { 0.6 } else { 1.0 } * (0.05 * (bmi - 25.0)).exp();
+        let params = simulation::SimulationParams { theta: multiplier, ..base };
+        patients.push(simulation::sample_patient(&params, simulation::Arm::Treatment, &design, &mut rng).unwrap());
+        covariates[(i, 0)] = if treated { 1.0 } else { 0.0 };
+        covariates[(i, 1)] = bmi;
+    }
+    let fit = regression::win_ratio_regression(&patients, &covariates, &["treatment", "bmi"], 0.95).unwrap();
+    let treatment_effect = &fit.coefficients[0];
+    let bmi_effect = &fit.coefficients[1];
+    assert!((treatment_effect.estimate - (-0.8 * 0.6_f64.ln())).abs() < 2.5 * treatment_effect.standard_error);
+    assert!((bmi_effect.estimate - (-0.8 * 0.05)).abs() < 2.5 * bmi_effect.standard_error);
+    assert!(treatment_effect.p_value < 0.05 && bmi_effect.p_value < 0.05);
+    assert_eq!(bmi_effect.name, "bmi");
+
+    let joint = fit.wald_test(&[0, 1]).unwrap();
+    assert_eq!(joint.degrees_of_freedom, 2);
+    assert!(joint.statistic > treatment_effect.z_statistic.powi(2));
+    let single = fit.wald_test(&[1]).unwrap();
+    assert!((single.statistic - bmi_effect.z_statistic.powi(2)).abs() < 1e-9);
+    assert!((single.p_value - bmi_effect.p_value).abs() < 1e-9);
+}
+
+#[test]
+fn test_group_sequential_boundaries() {
+    use group_sequential::{boundaries, monitor, Decision, GroupSequentialDesign, SpendingFunction};
+
+    // A single look is the fixed design.
+    let fixed = boundaries(&GroupSequentialDesign::new(0.025, SpendingFunction::Pocock), &[1.0]);
+    assert!((fixed[0].efficacy - 1.959_964).abs() < 1e-4);
+
+    // Lan–DeMets O'Brien–Fleming spending with two equally spaced looks (gsDesign: 2.9626, 1.9686).
+    let obf = GroupSequentialDesign::new(0.025, SpendingFunction::OBrienFleming);
+    let looks = boundaries(&obf, &[0.5, 1.0]);
+    assert!((looks[0].efficacy - 2.9626).abs() < 1e-3);
+    assert!((looks[1].efficacy - 1.9686).abs() < 1e-3);
+    assert!((looks[1].cumulative_alpha - 0.025).abs() < 1e-12);
+
+    let pocock = boundaries(&GroupSequentialDesign::new(0.025, SpendingFunction::Pocock), &[1.0 / 3.0, 2.0 / 3.0, 1.0]);
+    assert!(pocock.windows(2).all(|w| (w[0].efficacy - w[1].efficacy).abs() < 0.1));
+    let power = SpendingFunction::Power(2.0);
+    assert!((power.spent(0.025, 0.5) - 0.00625).abs() < 1e-12);
+
+    // Futility boundaries lie below the efficacy boundaries and meet them at the end.
+    let design = obf.with_futility(0.1, SpendingFunction::Power(2.0), 3.0);
+    let looks = boundaries(&design, &[0.5, 1.0]);
+    assert!(looks[0].futility.unwrap() < looks[0].efficacy);
+    assert_eq!(looks[1].futility, Some(looks[1].efficacy));
+
+    // 90 wins and 30 losses at half information cross the efficacy boundary (Z ≈ 5.48).
+    let counts = [sample_win_ratio::WinLossCounts::new(10, 40, 20, 50), sample_win_ratio::WinLossCounts::new(20, 80, 40, 100)];
+    let analyses = monitor(&design, &counts, 240.0);
+    assert_eq!(analyses.len(), 1);
+    assert_eq!(analyses[0].decision, Decision::StopForEfficacy);
+    assert!((analyses[0].boundaries.information_fraction - 0.5).abs() < 1e-12);
+
+    // Balanced counts continue, then fail to reject.
+    let counts = [sample_win_ratio::WinLossCounts::new(30, 30, 20, 20), sample_win_ratio::WinLossCounts::new(60, 62, 40, 40)];
+    let analyses = monitor(&GroupSequentialDesign::new(0.025, SpendingFunction::OBrienFleming), &counts, 200.0);
+    assert_eq!(analyses[0].decision, Decision::Continue);
+    assert_eq!(analyses[1].decision, Decision::FailToReject);
+
+    // All wins is the strongest evidence for efficacy, not a zero statistic that crosses futility.
+    let counts = [sample_win_ratio::WinLossCounts::new(0, 10, 0, 10), sample_win_ratio::WinLossCounts::new(0, 20, 0, 20)];
+    let analyses = monitor(&design, &counts, 120.0);
+    assert_eq!(analyses.len(), 1);
+    assert_eq!(analyses[0].z_statistic, f64::INFINITY);
+    assert_eq!(analyses[0].decision, Decision::StopForEfficacy);
+    let counts = [sample_win_ratio::WinLossCounts::new(10, 0, 10, 0)];
+    assert_eq!(monitor(&design, &counts, 120.0)[0].decision, Decision::StopForFutility);
+    // Without decided pairs there is no evidence either way.
+    let counts = [sample_win_ratio::WinLossCounts::new(0, 0, 0, 0), sample_win_ratio::WinLossCounts::new(0, 20, 0, 20)];
+    let analyses = monitor(&design, &counts, 120.0);
+    assert!(analyses[0].z_statistic.is_nan());
+    assert_eq!(analyses[0].decision, Decision::Continue);
+}
+
+#[test]
+fn test_group_sequential_simulation() {
+    use group_sequential::{simulate_operating_characteristics, GroupSequentialDesign, SpendingFunction};
+
+    let design = simulation::CensoringDesign::administrative(5.0);
+    let looks = [100, 200, 300];
+    let obf = GroupSequentialDesign::new(0.025, SpendingFunction::OBrienFleming);
+    let mut rng = StdRng::seed_from_u64(43);
+
+    // Under the null hypothesis the overall type I error is α.
+    let null = simulation::SimulationParams::new(0.1, 0.2, 0.8, 1.0);
+    let tie = probability_win_ratio::model_probability_win_ratio(&null, 5.0, 1e-8).tie_probability();
+    let max_decided = 300.0 * (1.0 - tie);
+    let result = simulate_operating_characteristics(&null, &design, &obf, &looks, max_decided, 2_000, &mut rng).unwrap();
+    assert!((result.rejection_probability() - 0.025).abs() < 0.012);
+
+    // Under the alternative the design has high power and often stops early.
+    let alternative = simulation::SimulationParams::new(0.1, 0.2, 0.8, 0.6);
+    let model = probability_win_ratio::model_probability_win_ratio(&alternative, 5.0, 1e-8);
+    let max_decided = 300.0 * (1.0 - model.tie_probability());
+    let drift = group_sequential::matched_pairs_drift(model.win_ratio, max_decided);
+    let with_futility = obf.with_futility(0.1, SpendingFunction::Power(2.0), drift);
+    let result = simulate_operating_characteristics(&alternative, &design, &with_futility, &looks, max_decided, 500, &mut rng).unwrap();
+    assert!(result.rejection_probability() > 0.85);
+    assert!(result.expected_pairs < 300.0);
+
+    // The looks must be non-empty and strictly increasing.
+    assert_eq!(
+        simulate_operating_characteristics(&null, &design, &obf, &[], 100.0, 10, &mut rng).unwrap_err(),
+        Error::EmptyData
+    );
+    for looks in [[100, 100, 300], [200, 100, 300], [0, 100, 300]] {
+        assert!(matches!(
+            simulate_operating_characteristics(&null, &design, &obf, &looks, 100.0, 10, &mut rng),
+            Err(Error::InvalidParameter { name: "pairs_at_look", .. })
+        ));
+    }
+}
+
+#[test]
+fn test_bmi_validation_and_categories() {
+    use bmi::{Bmi, BmiError, WhoCategory};
+
+    assert_eq!(Bmi::new(-70.0, 1.75), Err(BmiError::InvalidWeight(-70.0)));
+    assert!(matches!(Bmi::new(70.0, f64::NAN), Err(BmiError::InvalidHeight(h)) if h.is_nan()));
+    assert_eq!(Bmi::from_value(0.0), Err(BmiError::InvalidBmi(0.0)));
+    assert_eq!(BmiError::InvalidHeight(0.0).to_string(), "invalid height: 0 m");
+
+    let bmi = Bmi::new(70.0, 1.75).unwrap();
+    assert!((bmi.value() - bmi::calculate_bmi(70.0, 1.75).unwrap()).abs() < 1e-12);
+    assert_eq!(bmi.who_category(), WhoCategory::Normal);
+
+    assert_eq!(WhoCategory::from_value(15.9), WhoCategory::SevereThinness);
+    assert_eq!(WhoCategory::from_value(18.5), WhoCategory::Normal);
+    assert_eq!(WhoCategory::from_value(29.99), WhoCategory::PreObese);
+    assert_eq!(WhoCategory::from_value(30.0), WhoCategory::ObeseClassI);
+    assert_eq!(WhoCategory::from_value(40.0), WhoCategory::ObeseClassIII);
+    assert!(WhoCategory::MildThinness.is_underweight() && !WhoCategory::Normal.is_underweight());
+    assert!(WhoCategory::PreObese.is_overweight() && !WhoCategory::PreObese.is_obese());
+}
+
+#[test]
+fn test_bmi_for_age() {
+    use bmi::{bmi_for_age_lms, Bmi, BmiError, PaediatricCategory, Sex};
+
+    // The WHO 2007 cut-offs: a 10-year-old boy with BMI 21.4 is at +2 SD, a girl with 16.6 at the median.
+    let boy = Bmi::from_value(21.4).unwrap().for_age(Sex::Male, 120.0).unwrap();
+    assert!((boy.z_score - 2.0).abs() < 0.05);
+    assert_eq!(boy.category(), PaediatricCategory::Overweight);
+    let girl = Bmi::from_value(16.6).unwrap().for_age(Sex::Female, 120.0).unwrap();
+    assert!(girl.z_score.abs() < 1e-9);
+    assert!((girl.percentile - 50.0).abs() < 1e-6);
+
+    // Published LMS values at 61 months and linear interpolation between birthdays.
+    let lms = bmi_for_age_lms(Sex::Male, 61.0).unwrap();
+    assert_eq!((lms.l, lms.m, lms.s), (-0.7387, 15.2641, 0.0839));
+    let midway = bmi_for_age_lms(Sex::Female, 126.0).unwrap();
+    assert!((midway.m - 16.9).abs() < 1e-12);
+    assert!((lms.z_score(lms.value_at(1.5)) - 1.5).abs() < 1e-12);
+
+    // Beyond +3 SD the z-score grows linearly with the 2-to-3 SD distance.
+    let sd2 = lms.value_at(2.0);
+    let sd3 = lms.value_at(3.0);
+    assert!((lms.z_score(sd3 + 0.5 * (sd3 - sd2)) - 3.5).abs() < 1e-12);
+    let low = lms.value_at(-3.0) - (lms.value_at(-2.0) - lms.value_at(-3.0));
+    let thin = Bmi::from_value(low).unwrap().for_age(Sex::Male, 61.0).unwrap();
+    assert!((thin.z_score + 4.0).abs() < 1e-12);
+    assert_eq!(thin.category(), PaediatricCategory::SevereThinness);
+
+    assert_eq!(bmi_for_age_lms(Sex::Female, 48.0), Err(BmiError::AgeOutOfRange(48.0)));
+    assert!(Bmi::from_value(20.0).unwrap().for_age(Sex::Male, 240.0).is_err());
+}
+
+#[test]
+fn test_percent_bmi_change_tier() {
+    use bmi::{Bmi, BmiChange, PercentBmiChangeTier};
+    use hierarchy::{Direction, OutcomeTier, TierOutcome};
+
+    let change = BmiChange::new(Bmi::new(120.0, 2.0).unwrap(), Bmi::new(108.0, 2.0).unwrap());
+    assert!((change.absolute() + 3.0).abs() < 1e-12);
+    assert!((change.percent() + 10.0).abs() < 1e-12);
+
+    // (height, baseline weight, final weight); a missing final weight is `None`.
+    type Visit = (f64, f64, Option<f64>);
+    let tier = PercentBmiChangeTier::new(
+        "percent BMI change",
+        |p: &Visit| Some(BmiChange::new(Bmi::new(p.1, p.0).ok()?, Bmi::new(p.2?, p.0).ok()?)),
+        2.0,
+        Direction::LowerIsBetter,
+    );
+    assert_eq!(tier.name(), "percent BMI change");
+    // A 10% loss beats a 5% loss even though the absolute BMI changes are similar.
+    assert_eq!(tier.compare(&(1.6, 100.0, Some(90.0)), &(1.8, 200.0, Some(190.0))), TierOutcome::Win);
+    assert_eq!(tier.compare(&(1.8, 200.0, Some(190.0)), &(1.6, 100.0, Some(90.0))), TierOutcome::Loss);
+    assert_eq!(tier.compare(&(1.6, 100.0, Some(90.0)), &(1.6, 100.0, Some(91.0))), TierOutcome::Tie);
+    assert_eq!(tier.compare(&(1.6, 100.0, None), &(1.6, 100.0, Some(120.0))), TierOutcome::Tie);
+}
+
+#[test]
+fn test_typed_errors_and_lenient_helpers() {
+    use bmi::BmiError;
+    use sample_win_ratio::{ConfidenceIntervalMethod, WinLossCounts};
+
+    assert_eq!(bmi::calculate_bmi(70.0, 0.0), Err(Error::Bmi(BmiError::InvalidHeight(0.0))));
+    assert_eq!(bmi::calculate_bmi_lenient(70.0, 0.0), 0.0);
+
+    let no_losses = WinLossCounts::new(0, 3, 0, 2);
+    assert_eq!(sample_win_ratio::calculate_sample_win_ratio(&no_losses), Err(Error::NoLosses));
+    assert_eq!(sample_win_ratio::calculate_sample_win_ratio_lenient(&no_losses), f64::INFINITY);
+    assert_eq!(sample_win_ratio::calculate_sample_win_ratio(&WinLossCounts::default()), Err(Error::EmptyData));
+
+    assert_eq!(sample_win_ratio::calculate_win_proportion(0, 0), Err(Error::EmptyData));
+    assert_eq!(sample_win_ratio::calculate_confidence_interval(0, 0), Err(Error::EmptyData));
+    assert_eq!(sample_win_ratio::calculate_confidence_interval_lenient(0, 0), (0.0, 0.0));
+    assert_eq!(
+        sample_win_ratio::calculate_confidence_interval_with_method(5, 0, 0.95, ConfidenceIntervalMethod::LogDelta),
+        Err(Error::NoLosses)
+    );
+    assert_eq!(
+        sample_win_ratio::calculate_confidence_interval_with_method(5, 5, 1.5, ConfidenceIntervalMethod::Wald),
+        Err(Error::InvalidParameter { name: "confidence_level", value: 1.5 })
+    );
+    assert_eq!(sample_win_ratio::calculate_p_value(0, 4, ConfidenceIntervalMethod::LogDelta), Err(Error::NoWins));
+    assert!(sample_win_ratio::calculate_p_value_lenient(0, 4, ConfidenceIntervalMethod::LogDelta).is_nan());
+    assert_eq!(sample_win_ratio::calculate_p_value(10, 0, ConfidenceIntervalMethod::Wald), Err(Error::ZeroVariance));
+    assert_eq!(sample_win_ratio::calculate_p_value(0, 10, ConfidenceIntervalMethod::Wald), Err(Error::ZeroVariance));
+    assert_eq!(sample_win_ratio::calculate_p_value_lenient(10, 0, ConfidenceIntervalMethod::Wald), 1.0);
+    assert!(sample_win_ratio::calculate_p_value(10, 0, ConfidenceIntervalMethod::ClopperPearson).unwrap() < 0.01);
+    assert_eq!(sample_win_ratio::calculate_significance_test_statistic(6, 0), Err(Error::ZeroVariance));
+    assert_eq!(sample_win_ratio::calculate_significance_test_statistic_lenient(6, 0), 0.0);
+
+    assert_eq!(probability_win_ratio::calculate_probability_win_ratio(0.3, 0.0), Err(Error::NoLosses));
+    assert_eq!(
+        probability_win_ratio::calculate_probability_win_ratio(1.2, 0.1),
+        Err(Error::InvalidParameter { name: "win_probability", value: 1.2 })
+    );
+
+    let params = simulation::SimulationParams::new(0.1, 0.2, 0.8, 1.0);
+    assert_eq!(simulation::pdf_t_control(-1.0, &params), Err(Error::InvalidParameter { name: "t", value: -1.0 }));
+    assert_eq!(simulation::pdf_t_control_lenient(-1.0, &params), 0.0);
+    assert_eq!(
+        simulation::pdf_x_given_t_treatment(0.5, -2.0, &params),
+        Err(Error::InvalidParameter { name: "c", value: -2.0 })
+    );
+
+    let report = hierarchy::OutcomeHierarchy::<f64>::new().compare_matched(&[]);
+    assert_eq!(report.win_ratio(), Err(Error::EmptyData));
+
+    // Errors describe themselves and expose the underlying BMI error.
+    assert_eq!(Error::NoLosses.to_string(), "there are no losses, so the win ratio is unbounded");
+    let error: Box<dyn std::error::Error> = Box::new(Error::from(BmiError::InvalidWeight(-1.0)));
+    assert_eq!(error.source().unwrap().to_string(), "invalid weight: -1 kg");
+}
+
+#[test]
+fn test_all_pairs_errors_and_lenient_helpers() {
+    use nalgebra::DMatrix;
+    use sample_win_ratio::PatientRecord;
+    use stratified::{stratified_win_ratio, stratified_win_ratio_lenient, Stratum, StratumWeighting};
+
+    let (treatment, control) = example_arms();
+    let tied: Vec<_> = (0..3).map(|_| PatientRecord::new(None, None, 5.0)).collect();
+    let dying: Vec<_> = (0..3).map(|i| PatientRecord::new(Some(1.0 + i as f64), None, 5.0)).collect();
+
+    // An empty arm or all ties leave the win ratio undefined; no losses leave it unbounded.
+    assert_eq!(unmatched::finkelstein_schoenfeld(&treatment, &[], 0.95), Err(Error::EmptyData));
+    assert_eq!(unmatched::finkelstein_schoenfeld(&tied, &tied, 0.95), Err(Error::EmptyData));
+    assert_eq!(unmatched::finkelstein_schoenfeld(&tied, &dying, 0.95), Err(Error::NoLosses));
+    assert!(unmatched::finkelstein_schoenfeld_lenient(&treatment, &[], 0.95).win_ratio.estimate.is_nan());
+    let lenient = unmatched::finkelstein_schoenfeld_lenient(&tied, &dying, 0.95);
+    assert_eq!(lenient.win_ratio.estimate, f64::INFINITY);
+    assert_eq!(lenient.probabilities.win_ratio(), Err(Error::NoLosses));
+    assert_eq!(lenient.net_benefit.estimate, 1.0);
+
+    let strata = vec![Stratum::new(tied.clone(), tied.clone()), Stratum::new(treatment.clone(), Vec::new())];
+    assert_eq!(stratified_win_ratio(&strata, StratumWeighting::MantelHaenszel, 0.95), Err(Error::EmptyData));
+    let strata = vec![Stratum::new(tied.clone(), dying.clone())];
+    assert_eq!(stratified_win_ratio(&strata, StratumWeighting::MantelHaenszel, 0.95), Err(Error::NoLosses));
+    let lenient = stratified_win_ratio_lenient(&strata, StratumWeighting::MantelHaenszel, 0.95);
+    assert_eq!(lenient.win_ratio.estimate, f64::INFINITY);
+
+    assert_eq!(ipcw::ipcw_win_ratio(&treatment, &[], 5.0, 0.95), Err(Error::EmptyData));
+    assert_eq!(
+        ipcw::ipcw_win_ratio(&treatment, &control, -1.0, 0.95),
+        Err(Error::InvalidParameter { name: "tau", value: -1.0 })
+    );
+    assert!(ipcw::ipcw_win_ratio_lenient(&treatment, &[], 5.0, 0.95).weighted.win_ratio.estimate.is_nan());
+
+    let mut rng = StdRng::seed_from_u64(5);
+    assert_eq!(resampling::permutation_test(&[], &control, 100, 100, &mut rng), Err(Error::EmptyData));
+    assert!(resampling::permutation_test_lenient(&[], &control, 100, 100, &mut rng).observed_net_benefit.is_nan());
+
+    // The building blocks taking a user-supplied kernel reject an empty arm in the same way.
+    let kernel = |t: &f64, c: &f64| ((t > c) as u8 as f64, (t < c) as u8 as f64);
+    let scores = [1.0, 2.0, 3.0];
+    assert_eq!(unmatched::pairwise_probabilities_with(&scores, &[], kernel), Err(Error::EmptyData));
+    assert!(unmatched::pairwise_probabilities_with_lenient(&[], &scores, kernel).win_probability.is_nan());
+    assert_eq!(unmatched::pairwise_probabilities_with(&scores, &[0.5, 2.5], kernel).unwrap().win_probability, 4.0 / 6.0);
+    assert_eq!(resampling::bootstrap_with(&[], &scores, kernel, 10, 0.95, &mut rng), Err(Error::EmptyData));
+    assert!(resampling::bootstrap_with_lenient(&[], &scores, kernel, 10, 0.95, &mut rng).win_ratio.estimate.is_nan());
+    assert_eq!(resampling::permutation_test_with(&scores, &[], kernel, 100, 100, &mut rng), Err(Error::EmptyData));
+
+    // The simulators check the model and the censoring design.
+    let params = simulation::SimulationParams::new(0.1, 0.2, 0.8, 0.6);
+    let design = simulation::CensoringDesign::administrative(5.0);
+    let invalid = simulation::SimulationParams::new(0.1, 0.2, 1.5, 0.6);
+    assert_eq!(
+        simulation::simulate_trial(&invalid, &design, 5, 5, &mut rng),
+        Err(Error::InvalidParameter { name: "alpha", value: 1.5 })
+    );
+    assert_eq!(
+        simulation::simulate_trial(&params, &simulation::CensoringDesign::new(5.0, -0.1), 5, 5, &mut rng),
+        Err(Error::InvalidParameter { name: "dropout_rate", value: -0.1 })
+    );
+    let clayton = copula::CopulaModel::from_params(&simulation::SimulationParams { theta: 0.0, ..params }, copula::ClaytonCopula::new(2.0));
+    assert_eq!(
+        simulation::simulate_trial(&clayton, &design, 5, 5, &mut rng),
+        Err(Error::InvalidParameter { name: "theta", value: 0.0 })
+    );
+    assert_eq!(
+        recurrent::simulate_recurrent_trial(&params.with_recurrent_events(-1.0), &design, 5, 5, &mut rng),
+        Err(Error::InvalidParameter { name: "frailty_variance", value: -1.0 })
+    );
+    assert_eq!(simulation::simulate_trial_lenient(&params, &design, 5, 0, &mut rng).treatment.len(), 5);
+
+    // A curve fails if any of its points is undefined, here at c = 0 where no pair is decided.
+    assert_eq!(time_dependent::model_curve(&params, &[0.0, 5.0], 1e-8), Err(Error::NoLosses));
+    assert!(!time_dependent::model_curve_lenient(&params, &[0.0, 5.0], 1e-8).points[0].win_ratio.is_finite());
+    assert_eq!(
+        time_dependent::model_curve(&params, &[-1.0], 1e-8),
+        Err(Error::InvalidParameter { name: "follow_up_time", value: -1.0 })
+    );
+    assert_eq!(time_dependent::empirical_curve(&treatment, &control, &[0.0], 0.95), Err(Error::EmptyData));
+    assert!(time_dependent::empirical_curve_lenient(&treatment, &control, &[0.0], 0.95).points[0].win_ratio.is_nan());
+
+    // The regression needs decided pairs and covariates that are not collinear.
+    let patients: Vec<_> = tied.iter().chain(tied.iter()).copied().collect();
+    let indicator = DMatrix::from_fn(patients.len(), 1, |i, _| if i < 3 { 1.0 } else { 0.0 });
+    assert_eq!(regression::win_ratio_regression(&patients, &indicator, &["treatment"], 0.95).unwrap_err(), Error::EmptyData);
+    let patients: Vec<_> = treatment.iter().chain(control.iter()).copied().collect();
+    let collinear = DMatrix::from_fn(patients.len(), 2, |i, _| if i < treatment.len() { 1.0 } else { 0.0 });
+    assert_eq!(
+        regression::win_ratio_regression(&patients, &collinear, &["a", "b"], 0.95).unwrap_err(),
+        Error::ZeroVariance
+    );
+
+    // Losses without any wins leave log(WR) unbounded below, whatever the order of the arms.
+    let strata = vec![Stratum::new(dying.clone(), tied.clone())];
+    assert_eq!(unmatched::finkelstein_schoenfeld(&dying, &tied, 0.95), Err(Error::NoWins));
+    assert_eq!(unmatched::finkelstein_schoenfeld_lenient(&dying, &tied, 0.95).win_ratio.estimate, 0.0);
+    assert_eq!(stratified_win_ratio(&strata, StratumWeighting::MantelHaenszel, 0.95), Err(Error::NoWins));
+    assert_eq!(resampling::bootstrap(&dying, &tied, 20, 0.95, &mut rng), Err(Error::NoWins));
+    assert_eq!(resampling::bootstrap(&tied, &dying, 20, 0.95, &mut rng), Err(Error::NoLosses));
+    assert_eq!(ipcw::ipcw_win_ratio(&dying, &tied, 5.0, 0.95), Err(Error::NoWins));
+    for (patients, first_is_treated) in [([&dying[..], &tied[..]].concat(), true), ([&tied[..], &dying[..]].concat(), false)] {
+        let indicator = DMatrix::from_fn(6, 1, |i, _| if (i < 3) == first_is_treated { 1.0 } else { 0.0 });
+        assert_eq!(regression::win_ratio_regression(&patients, &indicator, &["treatment"], 0.95).unwrap_err(), Error::NoWins);
+    }
+
+    // The confidence level must be a probability.
+    let invalid = Error::InvalidParameter { name: "confidence_level", value: 1.5 };
+    let strata = vec![Stratum::new(treatment.clone(), control.clone())];
+    assert_eq!(unmatched::finkelstein_schoenfeld(&treatment, &control, 1.5).unwrap_err(), invalid);
+    assert_eq!(stratified_win_ratio(&strata, StratumWeighting::MantelHaenszel, 1.5).unwrap_err(), invalid);
+    assert_eq!(resampling::bootstrap(&treatment, &control, 20, 1.5, &mut rng).unwrap_err(), invalid);
+    assert_eq!(ipcw::ipcw_win_ratio(&treatment, &control, 5.0, 1.5).unwrap_err(), invalid);
+    let indicator = collinear.columns(0, 1).into_owned();
+    assert_eq!(regression::win_ratio_regression(&patients, &indicator, &["treatment"], 1.5).unwrap_err(), invalid);
+}
+
+#[test]
+fn test_density_diagnostics() {
+    use diagnostics::{diagnose_grid, diagnose_model, parameter_grid, DensityKind, DiagnosticSettings};
+    use simulation::{Arm, JointSurvivalModel, SimulationParams};
+
+    // The hand-derived densities of the paper's model match its survival functions.
+    let settings = DiagnosticSettings::default();
+    let grid = parameter_grid(&[0.1, 0.5], &[0.2, 1.0], &[0.6, 1.0, 1.5], &[0.6, 1.0]);
+    assert_eq!(grid.len(), 24);
+    let diagnostics = diagnose_grid(&grid, &settings);
+    assert!(diagnostics.passes(1e-6), "worst: {:?}", diagnostics.worst());
+    assert!(diagnostics.max_derivative_error() > 0.0);
+
+    // So do the copula models.
+    let params = SimulationParams::new(0.1, 0.2, 0.8, 0.6);
+    let clayton = copula::CopulaModel::from_params(&params, copula::ClaytonCopula::new(2.0));
+    assert!(diagnose_model(&clayton, &settings).passes(1e-6));
+    let frank = copula::CopulaModel::from_params(&params, copula::FrankCopula::new(-3.0));
+    assert!(diagnose_model(&frank, &settings).passes(1e-6));
+
+    // A sign error in the conditional density is caught and located.
+    #[derive(Clone)]
+    struct SignError(SimulationParams);
+    impl JointSurvivalModel for SignError {
+        fn joint_survival(&self, arm: Arm, t: f64, x: f64) -> f64 {
+            self.0.joint_survival(arm, t, x)
+        }
+        fn pdf_t(&self, arm: Arm, t: f64) -> f64 {
+            self.0.pdf_t(arm, t)
+        }
+        fn pdf_x_given_t(&self, arm: Arm, x: f64, c: f64) -> f64 {
+            let density = self.0.pdf_x_given_t(arm, x, c);
+            if arm == Arm::Treatment { -density } else { density }
+        }
+        fn sample_event_times<R: rand::Rng + ?Sized>(&self, arm: Arm, rng: &mut R) -> (f64, f64) {
+            self.0.sample_event_times(arm, rng)
+        }
+    }
+    let report = diagnose_model(&SignError(params), &settings);
+    assert!(!report.passes(1e-6));
+    let worst = report.worst().unwrap();
+    assert_eq!(worst.arm, Arm::Treatment);
+    assert!(matches!(worst.kind, DensityKind::NonFatalEvent { .. }));
+    assert!((worst.normalisation_error - 2.0).abs() < 1e-6);
+
+    // A density that returns NaN makes every maximum NaN instead of being skipped.
+    #[derive(Clone)]
+    struct NanDensity(SimulationParams);
+    impl JointSurvivalModel for NanDensity {
+        fn joint_survival(&self, arm: Arm, t: f64, x: f64) -> f64 {
+            self.0.joint_survival(arm, t, x)
+        }
+        fn pdf_t(&self, arm: Arm, t: f64) -> f64 {
+            if arm == Arm::Control && t == 1.0 { f64::NAN } else { self.0.pdf_t(arm, t) }
+        }
+        fn pdf_x_given_t(&self, arm: Arm, x: f64, c: f64) -> f64 {
+            self.0.pdf_x_given_t(arm, x, c)
+        }
+        fn sample_event_times<R: rand::Rng + ?Sized>(&self, arm: Arm, rng: &mut R) -> (f64, f64) {
+            self.0.sample_event_times(arm, rng)
+        }
+    }
+    let report = diagnose_model(&NanDensity(params), &settings);
+    assert!(!report.passes(1e-6));
+    assert!(report.max_derivative_error().is_nan());
+    let worst = report.worst().unwrap();
+    assert_eq!((worst.arm, worst.kind, worst.worst_time), (Arm::Control, DensityKind::FatalEvent, 1.0));
+    assert!(worst.max_error().is_nan());
+    let grid = diagnostics::GridDiagnostics { reports: vec![(0, diagnose_model(&params, &settings)), (1, report)] };
+    assert!(grid.max_derivative_error().is_nan());
+    assert_eq!(grid.worst().unwrap().0, 1);
+}